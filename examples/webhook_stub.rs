@@ -0,0 +1,61 @@
+//! Local webhook receiver for trying out notification delivery.
+//!
+//! ```sh
+//! WEBHOOK_SECRET=change-me cargo run --example webhook_stub
+//! WEBHOOK_URLS=http://localhost:8089/hook WEBHOOK_SECRET=change-me cargo run --bin consumer
+//! ```
+//!
+//! Set `WEBHOOK_STUB_FAIL_FIRST=3` to answer the first three requests with a
+//! 503 and watch the consumer retry, and `WEBHOOK_STUB_PORT` to change the port.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use warp::http::{HeaderMap, StatusCode};
+use warp::Filter;
+
+use trading_system::consumer::{sign, WebhookPayload, WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER};
+
+#[tokio::main]
+async fn main() {
+    let port: u16 = std::env::var("WEBHOOK_STUB_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(8089);
+    let fail_first: u32 = std::env::var("WEBHOOK_STUB_FAIL_FIRST")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(0);
+    let secret = Arc::new(std::env::var("WEBHOOK_SECRET").ok());
+    let received = Arc::new(AtomicU32::new(0));
+
+    let hook = warp::path("hook")
+        .and(warp::post())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .map(move |headers: HeaderMap, body: warp::hyper::body::Bytes| {
+            let count = received.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = String::from_utf8_lossy(&body).to_string();
+            let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or("");
+
+            if let Some(secret) = secret.as_ref() {
+                let timestamp = header(WEBHOOK_TIMESTAMP_HEADER).parse().unwrap_or_default();
+                if header(WEBHOOK_SIGNATURE_HEADER) != sign(secret, timestamp, &body) {
+                    println!("❌ #{} rejected: bad signature", count);
+                    return StatusCode::UNAUTHORIZED;
+                }
+            }
+            if count <= fail_first {
+                println!("💥 #{} failing on purpose ({}/{})", count, count, fail_first);
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+
+            match serde_json::from_str::<WebhookPayload>(&body) {
+                Ok(payload) => println!("📨 #{} {} {}: {}", count, payload.event, payload.id, payload.data),
+                Err(e) => println!("⚠️  #{} unexpected body ({}): {}", count, e, body),
+            }
+            StatusCode::OK
+        });
+
+    println!("🪝 Webhook stub listening on http://localhost:{}/hook", port);
+    warp::serve(hook).run(([127, 0, 0, 1], port)).await;
+}
@@ -26,6 +26,9 @@ export default function TradingDashboard() {
   const [isConnected, setIsConnected] = useState(false);
   const [isClient, setIsClient] = useState(false);
   const API_BASE = process.env.NEXT_PUBLIC_API_BASE || 'http://localhost:3001';
+  // Read-scoped key, needed when the consumer has API keys configured
+  const API_KEY = process.env.NEXT_PUBLIC_API_KEY;
+  const API_HEADERS: HeadersInit = API_KEY ? { 'X-API-Key': API_KEY } : {};
 
   // Fallback demo data to render while API is empty/unavailable
   const SAMPLE_PRICES: Record<string, number> = {
@@ -51,8 +54,12 @@ export default function TradingDashboard() {
     const fetchData = async () => {
       try {
         const [pricesRes, rsiRes] = await Promise.all([
-          fetch(`${API_BASE}/prices`).catch(() => ({ json: () => ({}) })),
-          fetch(`${API_BASE}/rsi`).catch(() => ({ json: () => ({}) }))
+          fetch(`${API_BASE}/api/v1/prices`, { headers: API_HEADERS })
+            .then(res => (res.ok ? res : { json: () => ({}) }))
+            .catch(() => ({ json: () => ({}) })),
+          fetch(`${API_BASE}/api/v1/rsi?compact=true`, { headers: API_HEADERS })
+            .then(res => (res.ok ? res : { json: () => ({}) }))
+            .catch(() => ({ json: () => ({}) }))
         ]);
         
         const pricesData = await pricesRes.json();
@@ -0,0 +1,173 @@
+use serde::Deserialize;
+use std::sync::Arc;
+use warp::http::StatusCode;
+use warp::reply::json;
+use warp::Reply;
+use utoipa::IntoParams;
+
+use crate::api::errors::{error_reply, ApiError};
+use crate::api::handlers::ApiState;
+use crate::consumer::{AlertRule, AlertRuleRequest, FiredAlert, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FiredAlertsQuery {
+    pub symbol: Option<String>,
+    pub limit: Option<usize>,
+}
+
+fn unknown_rule(id: &str) -> Box<dyn Reply> {
+    error_reply(StatusCode::NOT_FOUND, format!("unknown alert rule '{}'", id))
+}
+
+#[utoipa::path(get, path = "/api/v1/alerts", tag = "alerts",
+    responses((status = 200, description = "All alert rules", body = Vec<AlertRule>)))]
+pub async fn list_alerts(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    let processor = state.data_processor.read().await;
+    Ok(json(&processor.alerts().list()))
+}
+
+#[utoipa::path(get, path = "/api/v1/alerts/{id}", tag = "alerts", params(("id" = String, Path, description = "Rule id")),
+    responses(
+        (status = 200, description = "Alert rule", body = AlertRule),
+        (status = 404, description = "Unknown rule", body = ApiError),
+    ))]
+pub async fn get_alert(id: String, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let processor = state.data_processor.read().await;
+    Ok(match processor.alerts().get(&id) {
+        Some(rule) => Box::new(json(&rule)),
+        None => unknown_rule(&id),
+    })
+}
+
+#[utoipa::path(post, path = "/api/v1/alerts", tag = "alerts", request_body = AlertRuleRequest,
+    security(("bearer" = ["admin"]), ("api_key" = ["admin"])),
+    responses(
+        (status = 201, description = "Rule created", body = AlertRule),
+        (status = 400, description = "Invalid rule", body = ApiError),
+    ))]
+pub async fn create_alert(request: AlertRuleRequest, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let processor = state.data_processor.read().await;
+    Ok(match processor.alerts().create(request) {
+        Ok(rule) => {
+            tracing::info!(rule_id = %rule.id, symbol = %rule.symbol, "alert rule created");
+            Box::new(warp::reply::with_status(json(&rule), StatusCode::CREATED))
+        }
+        Err(message) => error_reply(StatusCode::BAD_REQUEST, message),
+    })
+}
+
+#[utoipa::path(put, path = "/api/v1/alerts/{id}", tag = "alerts", params(("id" = String, Path, description = "Rule id")), request_body = AlertRuleRequest,
+    security(("bearer" = ["admin"]), ("api_key" = ["admin"])),
+    responses(
+        (status = 200, description = "Rule replaced", body = AlertRule),
+        (status = 400, description = "Invalid rule", body = ApiError),
+        (status = 404, description = "Unknown rule", body = ApiError),
+    ))]
+pub async fn update_alert(
+    id: String,
+    request: AlertRuleRequest,
+    state: Arc<ApiState>,
+) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let processor = state.data_processor.read().await;
+    Ok(match processor.alerts().update(&id, request) {
+        Ok(Some(rule)) => Box::new(json(&rule)),
+        Ok(None) => unknown_rule(&id),
+        Err(message) => error_reply(StatusCode::BAD_REQUEST, message),
+    })
+}
+
+#[utoipa::path(delete, path = "/api/v1/alerts/{id}", tag = "alerts", params(("id" = String, Path, description = "Rule id")),
+    security(("bearer" = ["admin"]), ("api_key" = ["admin"])),
+    responses(
+        (status = 204, description = "Rule deleted"),
+        (status = 404, description = "Unknown rule", body = ApiError),
+    ))]
+pub async fn delete_alert(id: String, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let processor = state.data_processor.read().await;
+    if processor.alerts().delete(&id) {
+        tracing::info!(rule_id = %id, "alert rule deleted");
+        Ok(Box::new(StatusCode::NO_CONTENT))
+    } else {
+        Ok(unknown_rule(&id))
+    }
+}
+
+/// `GET /alerts/fired?symbol=AAPL&limit=50`, newest first.
+#[utoipa::path(get, path = "/api/v1/alerts/fired", tag = "alerts", params(FiredAlertsQuery),
+    responses((status = 200, description = "Recently fired alerts", body = Vec<FiredAlert>)))]
+pub async fn list_fired_alerts(query: FiredAlertsQuery, state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    let symbol = query.symbol.map(|symbol| symbol.to_uppercase());
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let processor = state.data_processor.read().await;
+    Ok(json(&processor.alerts().fired(symbol.as_deref(), limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiConfig, AuthMode, RateLimitConfig};
+    use crate::consumer::{AlertCondition, DataProcessor};
+    use serde_json::Value;
+
+    fn state() -> Arc<ApiState> {
+        let config = ApiConfig {
+            auth: AuthMode::Disabled,
+            keys: Vec::new(),
+            cors_origins: Vec::new(),
+            rate_limit: RateLimitConfig::default(),
+        };
+        Arc::new(ApiState::new(DataProcessor::new(), &config))
+    }
+
+    fn request(symbol: &str, threshold: f64) -> AlertRuleRequest {
+        AlertRuleRequest { symbol: symbol.to_string(), condition: AlertCondition::RsiAbove { threshold }, enabled: true }
+    }
+
+    async fn reply(reply: impl Reply) -> (StatusCode, Value) {
+        let response = reply.into_response();
+        let status = response.status();
+        let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn rules_can_be_created_read_replaced_and_deleted() {
+        let state = state();
+        let (status, created) = reply(create_alert(request("aapl", 70.0), state.clone()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["symbol"], "AAPL");
+        let id = created["id"].as_str().unwrap().to_string();
+
+        let (status, fetched) = reply(get_alert(id.clone(), state.clone()).await.unwrap()).await;
+        assert_eq!((status, &fetched["condition"]["threshold"]), (StatusCode::OK, &Value::from(70.0)));
+
+        let (status, replaced) = reply(update_alert(id.clone(), request("AAPL", 80.0), state.clone()).await.unwrap()).await;
+        assert_eq!((status, &replaced["condition"]["threshold"]), (StatusCode::OK, &Value::from(80.0)));
+        let (_, listed) = reply(list_alerts(state.clone()).await.unwrap()).await;
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+
+        let (status, _) = reply(delete_alert(id.clone(), state.clone()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, error) = reply(delete_alert(id.clone(), state.clone()).await.unwrap()).await;
+        assert_eq!((status, &error["code"]), (StatusCode::NOT_FOUND, &Value::from("not_found")));
+    }
+
+    #[tokio::test]
+    async fn invalid_or_unknown_rules_are_errors() {
+        let state = state();
+        let (status, error) = reply(create_alert(request("AAPL", 150.0), state.clone()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["code"], "bad_request");
+        assert_eq!(error["message"], "RSI threshold must be between 0 and 100");
+
+        let (status, error) = reply(get_alert("missing".to_string(), state.clone()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["message"], "unknown alert rule 'missing'");
+        let (status, _) = reply(update_alert("missing".to_string(), request("AAPL", 70.0), state.clone()).await.unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        // Validation comes before the lookup
+        let (status, _) = reply(update_alert("missing".to_string(), request("", 70.0), state).await.unwrap()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,192 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use warp::reject::Reject;
+use warp::{Filter, Rejection};
+
+use crate::config::{ApiConfig, ApiScope, AuthMode};
+
+/// No usable key was presented.
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl Reject for Unauthorized {}
+
+/// The key is valid but lacks the scope the endpoint requires.
+#[derive(Debug)]
+pub struct Forbidden {
+    pub required: ApiScope,
+}
+
+impl Reject for Forbidden {}
+
+struct StoredKey {
+    name: String,
+    digest: [u8; 32],
+    scopes: Vec<ApiScope>,
+}
+
+/// Checks presented API keys against the configured SHA-256 digests. Unless
+/// auth is explicitly disabled, a request without a matching key is refused,
+/// even when no keys are configured.
+#[derive(Clone)]
+pub struct ApiAuth {
+    keys: Arc<Vec<StoredKey>>,
+    enabled: bool,
+}
+
+impl ApiAuth {
+    pub fn new(config: &ApiConfig) -> Self {
+        let keys = config
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let digest = hex::decode(&key.sha256).ok()?.try_into().ok()?;
+                Some(StoredKey { name: key.name.clone(), digest, scopes: key.scopes.clone() })
+            })
+            .collect();
+        Self { keys: Arc::new(keys), enabled: config.auth == AuthMode::Required }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn find(&self, presented: &str) -> Option<&StoredKey> {
+        let digest: [u8; 32] = Sha256::digest(presented.as_bytes()).into();
+        self.keys.iter().find(|key| constant_time_eq(&key.digest, &digest))
+    }
+
+    /// Name of the key, if it is a known one.
+    pub fn identify(&self, presented: Option<&str>) -> Option<String> {
+        presented.and_then(|presented| self.find(presented)).map(|key| key.name.clone())
+    }
+
+    /// Name of the key allowed to use endpoints requiring `scope`.
+    pub fn authorize(&self, presented: Option<&str>, scope: ApiScope) -> Result<Option<String>, Rejection> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let key = presented.and_then(|presented| self.find(presented)).ok_or_else(|| warp::reject::custom(Unauthorized))?;
+        if !key.scopes.iter().any(|granted| granted.grants(scope)) {
+            return Err(warp::reject::custom(Forbidden { required: scope }));
+        }
+        Ok(Some(key.name.clone()))
+    }
+
+    /// Rejects requests without a key granting `scope`.
+    pub fn require(&self, scope: ApiScope) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        let auth = self.clone();
+        credentials()
+            .and_then(move |presented: Option<String>| {
+                let auth = auth.clone();
+                async move {
+                    if let Some(name) = auth.authorize(presented.as_deref(), scope)? {
+                        tracing::Span::current().record("api_key", name.as_str());
+                    }
+                    Ok::<_, Rejection>(())
+                }
+            })
+            .untuple_one()
+    }
+}
+
+/// The presented API key, read from `Authorization: Bearer`, `X-API-Key` or,
+/// for clients that cannot set headers such as `EventSource`, the `api_key`
+/// query parameter.
+pub fn credentials() -> impl Filter<Extract = (Option<String>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::header::optional::<String>("x-api-key"))
+        .and(warp::query::<HashMap<String, String>>().or(warp::any().map(HashMap::new)).unify())
+        .map(|authorization: Option<String>, api_key: Option<String>, mut query: HashMap<String, String>| {
+            let bearer = authorization
+                .as_deref()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .map(|key| key.trim().to_string());
+            bearer.or(api_key).or_else(|| query.remove("api_key"))
+        })
+}
+
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiKey, RateLimit, RateLimitConfig};
+    use std::collections::BTreeMap;
+
+    fn config(auth: AuthMode, keys: Vec<ApiKey>) -> ApiConfig {
+        ApiConfig {
+            auth,
+            keys,
+            cors_origins: Vec::new(),
+            rate_limit: RateLimitConfig {
+                enabled: false,
+                default: RateLimit { requests_per_second: 1.0, burst: 1 },
+                routes: BTreeMap::new(),
+            },
+        }
+    }
+
+    fn key(name: &str, secret: &str, scopes: Vec<ApiScope>) -> ApiKey {
+        ApiKey { name: name.to_string(), sha256: hex::encode(Sha256::digest(secret.as_bytes())), scopes }
+    }
+
+    fn status(result: Result<Option<String>, Rejection>) -> Result<Option<String>, &'static str> {
+        result.map_err(|rejection| {
+            if rejection.find::<Unauthorized>().is_some() {
+                "unauthorized"
+            } else if rejection.find::<Forbidden>().is_some() {
+                "forbidden"
+            } else {
+                "other"
+            }
+        })
+    }
+
+    #[test]
+    fn scopes_limit_what_a_key_may_do() {
+        let auth = ApiAuth::new(&config(
+            AuthMode::Required,
+            vec![key("dashboard", "read-key", vec![ApiScope::Read]), key("ops", "admin-key", vec![ApiScope::Admin])],
+        ));
+        assert_eq!(status(auth.authorize(Some("read-key"), ApiScope::Read)), Ok(Some("dashboard".to_string())));
+        assert_eq!(status(auth.authorize(Some("read-key"), ApiScope::Admin)), Err("forbidden"));
+        assert_eq!(status(auth.authorize(Some("admin-key"), ApiScope::Read)), Ok(Some("ops".to_string())));
+        assert_eq!(status(auth.authorize(Some("admin-key"), ApiScope::Admin)), Ok(Some("ops".to_string())));
+        assert_eq!(status(auth.authorize(Some("guess"), ApiScope::Read)), Err("unauthorized"));
+        assert_eq!(status(auth.authorize(None, ApiScope::Read)), Err("unauthorized"));
+        assert_eq!(auth.identify(Some("admin-key")), Some("ops".to_string()));
+    }
+
+    #[test]
+    fn required_auth_without_keys_refuses_everything() {
+        let auth = ApiAuth::new(&config(AuthMode::Required, Vec::new()));
+        assert!(auth.is_enabled());
+        assert_eq!(status(auth.authorize(None, ApiScope::Read)), Err("unauthorized"));
+        assert_eq!(status(auth.authorize(Some(""), ApiScope::Admin)), Err("unauthorized"));
+    }
+
+    #[test]
+    fn disabled_auth_must_be_chosen() {
+        let auth = ApiAuth::new(&config(AuthMode::Disabled, Vec::new()));
+        assert!(!auth.is_enabled());
+        assert_eq!(status(auth.authorize(None, ApiScope::Admin)), Ok(None));
+    }
+
+    #[tokio::test]
+    async fn credentials_are_read_from_headers_or_query() {
+        let presented = |request: warp::test::RequestBuilder| async move {
+            request.filter(&credentials()).await.unwrap()
+        };
+        let bearer = warp::test::request().header("authorization", "Bearer abc ");
+        assert_eq!(presented(bearer).await.as_deref(), Some("abc"));
+        let header = warp::test::request().header("x-api-key", "def");
+        assert_eq!(presented(header).await.as_deref(), Some("def"));
+        let query = warp::test::request().path("/stream?api_key=ghi");
+        assert_eq!(presented(query).await.as_deref(), Some("ghi"));
+        assert_eq!(presented(warp::test::request()).await, None);
+    }
+}
@@ -0,0 +1,195 @@
+use serde::Serialize;
+use std::convert::Infallible;
+use std::error::Error;
+use utoipa::ToSchema;
+use warp::filters::body::BodyDeserializeError;
+use warp::filters::cors::CorsForbidden;
+use warp::http::StatusCode;
+use warp::reject::{
+    InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader, PayloadTooLarge,
+    UnsupportedMediaType,
+};
+use warp::reply::json;
+
+use crate::api::auth::{Forbidden, Unauthorized};
+use crate::api::rate_limit::{RateLimited, RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER};
+use warp::{Rejection, Reply};
+
+/// Body of every error response.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiError {
+    /// Stable machine-readable code, e.g. `not_found` or `invalid_query`.
+    pub code: &'static str,
+    pub message: String,
+    /// Extra context such as the parser error behind `invalid_body`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), details: None }
+    }
+
+    pub fn with_details(mut self, details: Option<impl Into<serde_json::Value>>) -> Self {
+        self.details = details.map(Into::into);
+        self
+    }
+
+    pub fn into_reply(self, status: StatusCode) -> Box<dyn Reply> {
+        Box::new(warp::reply::with_status(json(&self), status))
+    }
+}
+
+/// Code used for errors that carry no more specific one.
+fn status_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+        _ if status.is_server_error() => "internal_error",
+        _ => "error",
+    }
+}
+
+pub(crate) fn error_reply(status: StatusCode, message: String) -> Box<dyn Reply> {
+    ApiError::new(status_code(status), message).into_reply(status)
+}
+
+/// The underlying parser error, which warp exposes as the source when it keeps one.
+fn cause(error: &dyn Error) -> Option<String> {
+    error.source().map(ToString::to_string)
+}
+
+/// Turns warp's rejections into `ApiError` bodies so every failure, including
+/// unknown routes and malformed input, has the same shape.
+pub async fn handle_rejection(rejection: Rejection) -> Result<Box<dyn Reply>, Infallible> {
+    let (status, error) = if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, ApiError::new("not_found", "no such endpoint"))
+    } else if rejection.find::<Unauthorized>().is_some() {
+        let error = ApiError::new("unauthorized", "a valid API key is required");
+        let reply = warp::reply::with_status(json(&error), StatusCode::UNAUTHORIZED);
+        return Ok(Box::new(warp::reply::with_header(reply, "www-authenticate", "Bearer")));
+    } else if let Some(forbidden) = rejection.find::<Forbidden>() {
+        let message = format!("this API key lacks the '{}' scope", forbidden.required);
+        (StatusCode::FORBIDDEN, ApiError::new("forbidden", message))
+    } else if let Some(limited) = rejection.find::<RateLimited>() {
+        let error = ApiError::new("rate_limited", "too many requests")
+            .with_details(Some(serde_json::json!({ "retry_after_secs": limited.retry_after_secs })));
+        let reply = warp::reply::with_status(json(&error), StatusCode::TOO_MANY_REQUESTS);
+        let reply = warp::reply::with_header(reply, "retry-after", limited.retry_after_secs.to_string());
+        let reply = warp::reply::with_header(reply, RATE_LIMIT_LIMIT_HEADER, limited.limit.to_string());
+        return Ok(Box::new(warp::reply::with_header(reply, RATE_LIMIT_REMAINING_HEADER, "0")));
+    } else if let Some(e) = rejection.find::<InvalidQuery>() {
+        (StatusCode::BAD_REQUEST, ApiError::new("invalid_query", "invalid query string").with_details(cause(e)))
+    } else if let Some(e) = rejection.find::<BodyDeserializeError>() {
+        (StatusCode::BAD_REQUEST, ApiError::new("invalid_body", "invalid JSON body").with_details(cause(e)))
+    } else if let Some(e) = rejection.find::<MissingHeader>() {
+        (StatusCode::BAD_REQUEST, ApiError::new("missing_header", e.to_string()))
+    } else if let Some(e) = rejection.find::<InvalidHeader>() {
+        (StatusCode::BAD_REQUEST, ApiError::new("invalid_header", e.to_string()))
+    } else if rejection.find::<LengthRequired>().is_some() {
+        (StatusCode::LENGTH_REQUIRED, ApiError::new("length_required", "a content-length header is required"))
+    } else if rejection.find::<PayloadTooLarge>().is_some() {
+        (StatusCode::PAYLOAD_TOO_LARGE, ApiError::new("payload_too_large", "request body is too large"))
+    } else if rejection.find::<UnsupportedMediaType>().is_some() {
+        (StatusCode::UNSUPPORTED_MEDIA_TYPE, ApiError::new("unsupported_media_type", "expected application/json"))
+    } else if let Some(e) = rejection.find::<CorsForbidden>() {
+        (StatusCode::FORBIDDEN, ApiError::new("cors_forbidden", e.to_string()))
+    } else if rejection.find::<MethodNotAllowed>().is_some() {
+        (StatusCode::METHOD_NOT_ALLOWED, ApiError::new("method_not_allowed", "method not allowed for this endpoint"))
+    } else {
+        tracing::error!(?rejection, "unhandled rejection");
+        (StatusCode::INTERNAL_SERVER_ERROR, ApiError::new("internal_error", "internal server error"))
+    };
+    Ok(error.into_reply(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ApiScope;
+    use serde::Deserialize;
+    use serde_json::Value;
+    use warp::Filter;
+
+    #[derive(Debug, Deserialize)]
+    struct Query {
+        limit: usize,
+    }
+
+    async fn reply(reply: Box<dyn Reply>) -> (warp::http::Response<warp::hyper::Body>, Value) {
+        let (parts, body) = reply.into_response().into_parts();
+        let body = warp::hyper::body::to_bytes(body).await.unwrap();
+        (warp::http::Response::from_parts(parts, Default::default()), serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn rejected(rejection: Rejection) -> (warp::http::Response<warp::hyper::Body>, Value) {
+        reply(handle_rejection(rejection).await.unwrap()).await
+    }
+
+    #[tokio::test]
+    async fn access_rejections_map_to_401_403_and_429() {
+        let (response, body) = rejected(warp::reject::custom(Unauthorized)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+        assert_eq!(body, serde_json::json!({ "code": "unauthorized", "message": "a valid API key is required" }));
+
+        let (response, body) = rejected(warp::reject::custom(Forbidden { required: ApiScope::Admin })).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(body["message"], "this API key lacks the 'admin' scope");
+
+        let (response, body) = rejected(warp::reject::custom(RateLimited { limit: 5, retry_after_secs: 3 })).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["retry-after"], "3");
+        assert_eq!(response.headers()[RATE_LIMIT_LIMIT_HEADER], "5");
+        assert_eq!(response.headers()[RATE_LIMIT_REMAINING_HEADER], "0");
+        assert_eq!(body["code"], "rate_limited");
+        assert_eq!(body["details"]["retry_after_secs"], 3);
+    }
+
+    #[tokio::test]
+    async fn malformed_requests_map_to_4xx_codes() {
+        let routes = warp::path!("items")
+            .and(warp::get())
+            .and(warp::query::<Query>())
+            .map(|query: Query| query.limit.to_string())
+            .or(warp::path!("items")
+                .and(warp::post())
+                .and(warp::body::json::<Query>())
+                .map(|query: Query| query.limit.to_string()))
+            .recover(handle_rejection);
+        let send = |request: warp::test::RequestBuilder| {
+            async move {
+                let response = request.reply(&routes).await;
+                (response.status(), serde_json::from_slice::<Value>(response.body()).unwrap())
+            }
+        };
+
+        let (status, body) = send(warp::test::request().path("/nowhere")).await;
+        assert_eq!((status, body), (StatusCode::NOT_FOUND, serde_json::json!({ "code": "not_found", "message": "no such endpoint" })));
+
+        let (status, body) = send(warp::test::request().path("/items?limit=lots")).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &Value::from("invalid_query")));
+        assert_eq!(body["message"], "invalid query string");
+
+        let (status, body) = send(warp::test::request().method("POST").path("/items").json(&serde_json::json!({ "limit": -1 }))).await;
+        assert_eq!((status, &body["code"]), (StatusCode::BAD_REQUEST, &Value::from("invalid_body")));
+        assert!(body["details"].is_string(), "{}", body);
+
+        let (status, body) = send(warp::test::request().method("DELETE").path("/items")).await;
+        assert_eq!((status, &body["code"]), (StatusCode::METHOD_NOT_ALLOWED, &Value::from("method_not_allowed")));
+    }
+
+    #[tokio::test]
+    async fn error_replies_use_the_status_code_name() {
+        let (response, body) = reply(error_reply(StatusCode::SERVICE_UNAVAILABLE, "history store is offline".to_string())).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, serde_json::json!({ "code": "unavailable", "message": "history store is offline" }));
+        assert_eq!(status_code(StatusCode::BAD_GATEWAY), "internal_error");
+        assert_eq!(status_code(StatusCode::CONFLICT), "error");
+    }
+}
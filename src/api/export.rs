@@ -0,0 +1,359 @@
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+use warp::http::{header, HeaderValue, Response, StatusCode};
+use warp::hyper::Body;
+use warp::Reply;
+
+use crate::api::errors::{error_reply, ApiError};
+use crate::api::handlers::{split_list, unknown_symbol, ApiState};
+use crate::consumer::{DataProcessor, HistoryQuery, Page, MAX_PAGE_LIMIT};
+use crate::models::{Candle, RsiData, TradeData};
+
+/// Symbols and time range to export.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// Comma-separated symbols; every tracked symbol when omitted.
+    pub symbols: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[param(inline)]
+    pub format: Option<ExportFormat>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Comma-separated values with a header row.
+    #[default]
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// A history record that can be exported.
+trait ExportRow: Serialize + Send + Sized + 'static {
+    const NAME: &'static str;
+    const CSV_HEADER: &'static str;
+
+    fn csv_row(&self) -> String;
+
+    fn page(processor: &DataProcessor, symbol: &str, query: &HistoryQuery) -> impl Future<Output = Option<Page<Self>>> + Send;
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or line break, and
+/// prefixes a `'` to text a spreadsheet would otherwise run as a formula.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+impl ExportRow for TradeData {
+    const NAME: &'static str = "trades";
+    const CSV_HEADER: &'static str = "id,symbol,price,volume,timestamp,side,exchange,outlier";
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:?},{},{}",
+            csv_field(&self.id),
+            csv_field(&self.symbol),
+            self.price,
+            self.volume,
+            self.timestamp.to_rfc3339(),
+            self.side,
+            csv_field(&self.exchange),
+            self.outlier.map(|status| status.as_str()).unwrap_or_default(),
+        )
+    }
+
+    fn page(processor: &DataProcessor, symbol: &str, query: &HistoryQuery) -> impl Future<Output = Option<Page<Self>>> + Send {
+        processor.get_trades(symbol, query)
+    }
+}
+
+impl ExportRow for Candle {
+    const NAME: &'static str = "candles";
+    const CSV_HEADER: &'static str = "symbol,interval_secs,open_time,close_time,open,high,low,close,volume,notional,trade_count,closed";
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(&self.symbol),
+            self.interval_secs,
+            self.open_time.to_rfc3339(),
+            self.close_time.to_rfc3339(),
+            self.open,
+            self.high,
+            self.low,
+            self.close,
+            self.volume,
+            self.notional,
+            self.trade_count,
+            self.closed,
+        )
+    }
+
+    fn page(processor: &DataProcessor, symbol: &str, query: &HistoryQuery) -> impl Future<Output = Option<Page<Self>>> + Send {
+        processor.get_candles(symbol, query)
+    }
+}
+
+impl ExportRow for RsiData {
+    const NAME: &'static str = "rsi";
+    const CSV_HEADER: &'static str = "id,symbol,rsi_value,timestamp,period,signal";
+
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{:?}",
+            csv_field(&self.id),
+            csv_field(&self.symbol),
+            self.rsi_value,
+            self.timestamp.to_rfc3339(),
+            self.period,
+            self.signal,
+        )
+    }
+
+    fn page(processor: &DataProcessor, symbol: &str, query: &HistoryQuery) -> impl Future<Output = Option<Page<Self>>> + Send {
+        processor.get_rsi_history(symbol, query)
+    }
+}
+
+fn render<T: ExportRow>(format: ExportFormat, rows: &[T]) -> String {
+    let mut chunk = String::new();
+    for row in rows {
+        match format {
+            ExportFormat::Csv => chunk.push_str(&row.csv_row()),
+            ExportFormat::Ndjson => match serde_json::to_string(row) {
+                Ok(line) => chunk.push_str(&line),
+                Err(e) => {
+                    tracing::error!(error = %e, "failed to serialize export row");
+                    continue;
+                }
+            },
+        }
+        chunk.push('\n');
+    }
+    chunk
+}
+
+/// One chunk per history page, so only a page is held in memory at a time.
+fn rows<T: ExportRow>(
+    processor: DataProcessor,
+    symbols: Vec<String>,
+    range: HistoryQuery,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<String, Infallible>> + Send {
+    let header = match format {
+        ExportFormat::Csv => Some(Ok(format!("{}\n", T::CSV_HEADER))),
+        ExportFormat::Ndjson => None,
+    };
+    let pages = stream::unfold(
+        (processor, VecDeque::from(symbols), range),
+        move |(processor, mut symbols, mut query)| async move {
+            loop {
+                let symbol = symbols.front()?;
+                let (items, next_cursor) = T::page(&processor, symbol, &query)
+                    .await
+                    .map(|page| (page.items, page.next_cursor))
+                    .unwrap_or_default();
+                query.cursor = next_cursor;
+                if query.cursor.is_none() {
+                    symbols.pop_front();
+                }
+                if !items.is_empty() {
+                    return Some((Ok(render(format, &items)), (processor, symbols, query)));
+                }
+            }
+        },
+    );
+    stream::iter(header).chain(pages)
+}
+
+/// Streams the history of each symbol in turn, newest first, as a chunked
+/// download.
+async fn export<T: ExportRow>(query: ExportQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let range = HistoryQuery { from: query.from, to: query.to, limit: Some(MAX_PAGE_LIMIT), cursor: None };
+    if let Err(message) = range.validate() {
+        return Ok(error_reply(StatusCode::BAD_REQUEST, message));
+    }
+
+    let processor = state.data_processor.read().await.clone();
+    let mut symbols: Vec<String> = split_list(query.symbols.as_ref()).iter().map(|symbol| symbol.to_uppercase()).collect();
+    if symbols.is_empty() {
+        symbols = processor.get_symbols().await.into_iter().map(|summary| summary.symbol).collect();
+    }
+    for symbol in &symbols {
+        if processor.get_symbol(symbol).await.is_none() {
+            return Ok(unknown_symbol(symbol));
+        }
+    }
+
+    let format = query.format.unwrap_or_default();
+    let mut response = Response::new(Body::wrap_stream(rows::<T>(processor, symbols, range, format)));
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()));
+    let disposition = format!("attachment; filename=\"{}.{}\"", T::NAME, format.extension());
+    if let Ok(disposition) = HeaderValue::from_str(&disposition) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    Ok(Box::new(response))
+}
+
+/// Trades of the selected symbols as CSV or NDJSON.
+#[utoipa::path(get, path = "/api/v1/export/trades", tag = "export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Trades, one symbol after another, newest first",
+            content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Invalid range or format", body = ApiError),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn export_trades(query: ExportQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    export::<TradeData>(query, state).await
+}
+
+/// One-minute candles of the selected symbols as CSV or NDJSON.
+#[utoipa::path(get, path = "/api/v1/export/candles", tag = "export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Candles, one symbol after another, newest first",
+            content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Invalid range or format", body = ApiError),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn export_candles(query: ExportQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    export::<Candle>(query, state).await
+}
+
+/// RSI values of the selected symbols as CSV or NDJSON.
+#[utoipa::path(get, path = "/api/v1/export/rsi", tag = "export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "RSI values, one symbol after another, newest first",
+            content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Invalid range or format", body = ApiError),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn export_rsi(query: ExportQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    export::<RsiData>(query, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiConfig, AuthMode, RateLimitConfig};
+    use crate::models::TradeSide;
+    use chrono::TimeZone;
+
+    #[test]
+    fn csv_fields_are_quoted_and_defused() {
+        assert_eq!(csv_field("NASDAQ"), "NASDAQ");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("=HYPERLINK(\"x\",\"y\")"), "\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\"");
+        assert_eq!(csv_field("a=b"), "a=b");
+    }
+
+    /// Minutes past 10:00 on a fixed day.
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 10, minute, 0).unwrap()
+    }
+
+    async fn state() -> Arc<ApiState> {
+        let processor = DataProcessor::new();
+        let trades = [("AAPL", "187.5", 0, "NASDAQ"), ("AAPL", "188", 10, "=cmd|' /C calc'!A0"), ("AAPL", "188.25", 20, "NASDAQ"), ("MSFT", "410", 15, "NYSE")];
+        for (symbol, price, minute, exchange) in trades {
+            let mut trade = TradeData::new(symbol.to_string(), price.parse().unwrap(), 100, TradeSide::Buy, exchange.to_string());
+            trade.id = format!("{}-{}", symbol, minute);
+            trade.timestamp = at(minute);
+            processor.process_trade_data(trade).await;
+        }
+        let config = ApiConfig {
+            auth: AuthMode::Disabled,
+            keys: Vec::new(),
+            cors_origins: Vec::new(),
+            rate_limit: RateLimitConfig::default(),
+        };
+        Arc::new(ApiState::new(processor, &config))
+    }
+
+    async fn download(query: ExportQuery, state: Arc<ApiState>) -> (StatusCode, String, String) {
+        let response = export_trades(query, state).await.unwrap().into_response();
+        let status = response.status();
+        let content_type = response.headers().get(header::CONTENT_TYPE).map(|value| value.to_str().unwrap().to_string()).unwrap_or_default();
+        let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn csv_exports_have_a_header_and_only_rows_in_range() {
+        let query = ExportQuery { symbols: Some("aapl".to_string()), from: Some(at(5)), to: Some(at(20)), format: None };
+        let (status, content_type, body) = download(query, state().await).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::OK, "text/csv; charset=utf-8"));
+        let lines: Vec<&str> = body.lines().collect();
+        assert_eq!(lines[0], TradeData::CSV_HEADER);
+        let ids: Vec<&str> = lines[1..].iter().map(|line| line.split(',').next().unwrap()).collect();
+        assert_eq!(ids, ["AAPL-20", "AAPL-10"]);
+        assert!(lines[2].contains(",'=cmd|' /C calc'!A0,"), "{}", lines[2]);
+    }
+
+    #[tokio::test]
+    async fn ndjson_exports_every_symbol_one_object_per_line() {
+        let query = ExportQuery { format: Some(ExportFormat::Ndjson), ..Default::default() };
+        let (status, content_type, body) = download(query, state().await).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::OK, "application/x-ndjson"));
+        let mut ids: Vec<String> = body
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["AAPL-0", "AAPL-10", "AAPL-20", "MSFT-15"]);
+        // The exchange is data here, not a formula
+        assert!(body.contains(r#""exchange":"=cmd|' /C calc'!A0""#), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn bad_ranges_and_unknown_symbols_are_errors() {
+        let state = state().await;
+        let reversed = ExportQuery { from: Some(at(20)), to: Some(at(5)), ..Default::default() };
+        assert_eq!(download(reversed, state.clone()).await.0, StatusCode::BAD_REQUEST);
+        let unknown = ExportQuery { symbols: Some("AAPL,TSLA".to_string()), ..Default::default() };
+        assert_eq!(download(unknown, state).await.0, StatusCode::NOT_FOUND);
+    }
+}
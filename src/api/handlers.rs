@@ -1,37 +1,273 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use warp::http::StatusCode;
 use warp::reply::json;
 use warp::Reply;
+use utoipa::IntoParams;
 
-use crate::consumer::DataProcessor;
+use crate::api::auth::ApiAuth;
+use crate::api::rate_limit::RateLimiter;
+use crate::api::errors::{error_reply, ApiError};
+use crate::config::ApiConfig;
+use crate::consumer::{
+    ConsumerHealth, ConsumerMetrics, DataProcessor, EndpointStatus, HealthReport, HistoryQuery, Page, SymbolSummary,
+    WebhookDispatcher,
+};
+use crate::models::{Candle, RsiData, RsiSignal, TradeData};
 
 pub struct ApiState {
     pub data_processor: Arc<RwLock<DataProcessor>>,
+    pub webhooks: Option<WebhookDispatcher>,
+    pub metrics: ConsumerMetrics,
+    pub health: ConsumerHealth,
+    pub auth: ApiAuth,
+    pub limiter: RateLimiter,
+    /// Browser origins allowed by CORS; `*` allows any.
+    pub cors_origins: Vec<String>,
 }
 
 impl ApiState {
-    pub fn new(data_processor: DataProcessor) -> Self {
+    /// Takes the API keys, rate limits and CORS origins from `config`.
+    pub fn new(data_processor: DataProcessor, config: &ApiConfig) -> Self {
         Self {
+            metrics: data_processor.metrics().clone(),
+            health: data_processor.health().clone(),
             data_processor: Arc::new(RwLock::new(data_processor)),
+            webhooks: None,
+            auth: ApiAuth::new(config),
+            limiter: RateLimiter::new(&config.rate_limit),
+            cors_origins: config.cors_origins.clone(),
         }
     }
+
+    pub fn with_webhooks(mut self, webhooks: WebhookDispatcher) -> Self {
+        self.webhooks = Some(webhooks);
+        self
+    }
 }
 
+/// Latest trade price per symbol.
+#[utoipa::path(get, path = "/api/v1/prices", tag = "market",
+    responses((status = 200, description = "Price per symbol", body = HashMap<String, String>)))]
 pub async fn get_prices(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
     let processor = state.data_processor.read().await;
     let prices = processor.get_latest_prices().await;
     Ok(json(&prices))
 }
 
-pub async fn get_rsi(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+/// Query parameters accepted by `GET /rsi`.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RsiQuery {
+    /// Comma-separated symbols to include.
+    pub symbols: Option<String>,
+    /// Comma-separated signals to include, e.g. `Overbought`.
+    pub signal: Option<String>,
+    /// Return the legacy `{symbol: value}` map instead of full records.
+    #[serde(default)]
+    pub compact: bool,
+}
+
+/// `GET /rsi?symbols=AAPL,MSFT&signal=Overbought[&compact=true]`
+#[utoipa::path(get, path = "/api/v1/rsi", tag = "market", params(RsiQuery), responses(
+    (status = 200, description = "Latest RSI per symbol; a `{symbol: value}` map with `compact=true`", body = Vec<RsiData>),
+    (status = 400, description = "Unknown signal", body = ApiError),
+))]
+pub async fn get_rsi(query: RsiQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let symbols: Vec<String> = split_list(query.symbols.as_ref())
+        .into_iter()
+        .map(|symbol| symbol.to_uppercase())
+        .collect();
+    let signals = match split_list(query.signal.as_ref())
+        .iter()
+        .map(|signal| signal.parse::<RsiSignal>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(signals) => signals,
+        Err(message) => return Ok(error_reply(StatusCode::BAD_REQUEST, message)),
+    };
+
     let processor = state.data_processor.read().await;
-    let rsi_values = processor.get_latest_rsi().await;
-    Ok(json(&rsi_values))
+    let rsi_values: Vec<_> = processor
+        .get_latest_rsi()
+        .await
+        .into_iter()
+        .filter(|rsi| symbols.is_empty() || symbols.contains(&rsi.symbol))
+        .filter(|rsi| signals.is_empty() || signals.contains(&rsi.signal))
+        .collect();
+
+    if query.compact {
+        let compact: BTreeMap<String, f64> = rsi_values
+            .into_iter()
+            .map(|rsi| (rsi.symbol, rsi.rsi_value))
+            .collect();
+        return Ok(Box::new(json(&compact)));
+    }
+    Ok(Box::new(json(&rsi_values)))
+}
+
+/// Delivery status of every configured webhook endpoint.
+#[utoipa::path(get, path = "/api/v1/webhooks", tag = "webhooks", security(("bearer" = ["admin"]), ("api_key" = ["admin"])),
+    responses((status = 200, description = "Status per endpoint", body = Vec<EndpointStatus>)))]
+pub async fn get_webhooks(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    let status = state.webhooks.as_ref().map(WebhookDispatcher::status).unwrap_or_default();
+    Ok(json(&status))
+}
+
+/// Consumer and API metrics in Prometheus text format.
+#[utoipa::path(get, path = "/metrics", tag = "operations", security(()),
+    responses((status = 200, description = "Prometheus exposition", body = String, content_type = "text/plain")))]
+pub async fn get_metrics(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    Ok(warp::reply::with_header(state.metrics.render(), "content-type", "text/plain; version=0.0.4"))
+}
+
+fn health_reply(report: HealthReport) -> impl Reply {
+    let status = if report.is_healthy() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    warp::reply::with_status(json(&report), status)
 }
 
-pub async fn get_health() -> Result<impl Reply, warp::Rejection> {
-    Ok(json(&serde_json::json!({
-        "status": "healthy",
+/// Legacy health check, kept for existing callers; reflects liveness.
+#[utoipa::path(get, path = "/health", tag = "operations", security(()), responses(
+    (status = 200, description = "Consume loop running"),
+    (status = 503, description = "Consume loop stopped or stalled"),
+))]
+pub async fn get_health(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    let healthy = state.health.liveness().is_healthy();
+    let status = if healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    Ok(warp::reply::with_status(json(&serde_json::json!({
+        "status": if healthy { "healthy" } else { "unhealthy" },
         "timestamp": chrono::Utc::now()
-    })))
+    })), status))
+}
+
+/// `GET /health/live`: 503 only when the consume loop has died or stalled.
+#[utoipa::path(get, path = "/health/live", tag = "operations", security(()), responses(
+    (status = 200, description = "Alive", body = HealthReport),
+    (status = 503, description = "Consume loop stopped or stalled", body = HealthReport),
+))]
+pub async fn get_liveness(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    Ok(health_reply(state.health.liveness()))
+}
+
+/// `GET /health/ready`: 503 with per-component detail until the consumer is
+/// connected, assigned, caught up and receiving data.
+#[utoipa::path(get, path = "/health/ready", tag = "operations", security(()), responses(
+    (status = 200, description = "Ready", body = HealthReport),
+    (status = 503, description = "At least one component is not ready", body = HealthReport),
+))]
+pub async fn get_readiness(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    Ok(health_reply(state.health.readiness()))
+}
+
+/// Splits a comma-separated query value, dropping empty entries.
+pub(crate) fn split_list(value: Option<&String>) -> Vec<String> {
+    value
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn unknown_symbol(symbol: &str) -> Box<dyn Reply> {
+    error_reply(StatusCode::NOT_FOUND, format!("unknown symbol '{}'", symbol))
+}
+
+/// Summary of every tracked symbol.
+#[utoipa::path(get, path = "/api/v1/symbols", tag = "market",
+    responses((status = 200, description = "Tracked symbols", body = Vec<SymbolSummary>)))]
+pub async fn get_symbols(state: Arc<ApiState>) -> Result<impl Reply, warp::Rejection> {
+    let processor = state.data_processor.read().await;
+    let symbols = processor.get_symbols().await;
+    Ok(json(&symbols))
+}
+
+#[utoipa::path(get, path = "/api/v1/symbols/{symbol}", tag = "market",
+    params(("symbol" = String, Path, description = "Ticker, case-insensitive")),
+    responses(
+        (status = 200, description = "Symbol summary", body = SymbolSummary),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn get_symbol(symbol: String, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let symbol = symbol.to_uppercase();
+    let processor = state.data_processor.read().await;
+    Ok(match processor.get_symbol(&symbol).await {
+        Some(summary) => Box::new(json(&summary)),
+        None => unknown_symbol(&symbol),
+    })
+}
+
+/// Which per-symbol history a request asks for.
+#[derive(Debug, Clone, Copy)]
+enum HistoryKind {
+    Trades,
+    Rsi,
+    Candles,
+}
+
+/// `GET /symbols/{symbol}/{trades,rsi/history,candles}?from=&to=&limit=&cursor=`,
+/// newest first.
+async fn get_symbol_history(
+    symbol: String,
+    kind: HistoryKind,
+    query: HistoryQuery,
+    state: Arc<ApiState>,
+) -> Result<Box<dyn Reply>, warp::Rejection> {
+    if let Err(message) = query.validate() {
+        return Ok(error_reply(StatusCode::BAD_REQUEST, message));
+    }
+
+    let symbol = symbol.to_uppercase();
+    let processor = state.data_processor.read().await;
+    let page = match kind {
+        HistoryKind::Trades => processor.get_trades(&symbol, &query).await.map(|page| json(&page)),
+        HistoryKind::Rsi => processor.get_rsi_history(&symbol, &query).await.map(|page| json(&page)),
+        HistoryKind::Candles => processor.get_candles(&symbol, &query).await.map(|page| json(&page)),
+    };
+    Ok(match page {
+        Some(page) => Box::new(page),
+        None => unknown_symbol(&symbol),
+    })
+}
+
+/// Trades for a symbol, newest first.
+#[utoipa::path(get, path = "/api/v1/symbols/{symbol}/trades", tag = "history",
+    params(("symbol" = String, Path, description = "Ticker, case-insensitive"), HistoryQuery),
+    responses(
+        (status = 200, description = "One page of trades", body = Page<TradeData>),
+        (status = 400, description = "Invalid range or cursor", body = ApiError),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn get_trades(symbol: String, query: HistoryQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    get_symbol_history(symbol, HistoryKind::Trades, query, state).await
+}
+
+/// RSI values for a symbol, newest first.
+#[utoipa::path(get, path = "/api/v1/symbols/{symbol}/rsi/history", tag = "history",
+    params(("symbol" = String, Path, description = "Ticker, case-insensitive"), HistoryQuery),
+    responses(
+        (status = 200, description = "One page of RSI values", body = Page<RsiData>),
+        (status = 400, description = "Invalid range or cursor", body = ApiError),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn get_rsi_history(symbol: String, query: HistoryQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    get_symbol_history(symbol, HistoryKind::Rsi, query, state).await
+}
+
+/// One-minute candles for a symbol, newest first.
+#[utoipa::path(get, path = "/api/v1/symbols/{symbol}/candles", tag = "history",
+    params(("symbol" = String, Path, description = "Ticker, case-insensitive"), HistoryQuery),
+    responses(
+        (status = 200, description = "One page of candles", body = Page<Candle>),
+        (status = 400, description = "Invalid range or cursor", body = ApiError),
+        (status = 404, description = "Unknown symbol", body = ApiError),
+    ))]
+pub async fn get_candles(symbol: String, query: HistoryQuery, state: Arc<ApiState>) -> Result<Box<dyn Reply>, warp::Rejection> {
+    get_symbol_history(symbol, HistoryKind::Candles, query, state).await
 }
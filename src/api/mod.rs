@@ -1,5 +1,21 @@
 pub mod handlers;
 pub mod routes;
+pub mod websocket;
+pub mod sse;
+pub mod alerts;
+pub mod auth;
+pub mod errors;
+pub mod openapi;
+pub mod rate_limit;
+pub mod export;
 
 pub use handlers::*;
 pub use routes::*;
+pub use websocket::*;
+pub use sse::*;
+pub use alerts::*;
+pub use auth::*;
+pub use errors::*;
+pub use openapi::*;
+pub use rate_limit::*;
+pub use export::*;
@@ -0,0 +1,145 @@
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{Content, Ref, RefOr, Response};
+use utoipa::{Modify, OpenApi};
+
+use crate::api::{alerts, errors, export, handlers, sse, websocket};
+use crate::consumer::{AlertCondition, CrossDirection};
+use crate::models::{RsiData, RsiSignal, TradeData, TradeSide};
+
+/// Prefix of every versioned endpoint; health, metrics and this document
+/// stay at the root for probes and scrapers.
+pub const API_PREFIX: &str = "/api/v1";
+
+/// OpenAPI description of the consumer API, served at `/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Trading System API", description = "Market data, RSI, alerts and streaming from the trading consumer."),
+    paths(
+        handlers::get_prices,
+        handlers::get_rsi,
+        handlers::get_symbols,
+        handlers::get_symbol,
+        handlers::get_trades,
+        handlers::get_rsi_history,
+        handlers::get_candles,
+        export::export_trades,
+        export::export_candles,
+        export::export_rsi,
+        alerts::list_alerts,
+        alerts::create_alert,
+        alerts::list_fired_alerts,
+        alerts::get_alert,
+        alerts::update_alert,
+        alerts::delete_alert,
+        handlers::get_webhooks,
+        sse::get_stream,
+        websocket::ws_upgrade,
+        handlers::get_health,
+        handlers::get_liveness,
+        handlers::get_readiness,
+        handlers::get_metrics,
+    ),
+    modifiers(&AccessControl),
+    security(("bearer" = ["read"]), ("api_key" = ["read"])),
+    components(schemas(TradeData, TradeSide, RsiData, RsiSignal, AlertCondition, CrossDirection, export::ExportFormat, errors::ApiError)),
+    tags(
+        (name = "market", description = "Latest prices, RSI and symbol summaries"),
+        (name = "history", description = "Paginated per-symbol history"),
+        (name = "export", description = "Bulk history downloads as CSV or NDJSON"),
+        (name = "alerts", description = "Alert rules and fired alerts"),
+        (name = "webhooks", description = "Webhook delivery status"),
+        (name = "streaming", description = "Live updates over SSE and WebSocket"),
+        (name = "operations", description = "Health probes and metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the API key schemes and the 401/403/429 responses every
+/// versioned operation can return.
+struct AccessControl;
+
+impl Modify for AccessControl {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+
+        let error = |description: &str| {
+            RefOr::T(
+                Response::builder()
+                    .description(description)
+                    .content("application/json", Content::new(Some(RefOr::Ref(Ref::from_schema_name("ApiError")))))
+                    .build(),
+            )
+        };
+        // Everything outside the versioned prefix is public
+        let secured = openapi.paths.paths.iter_mut().filter(|(path, _)| path.starts_with(API_PREFIX));
+        for (_, item) in secured {
+            let operations = [&mut item.get, &mut item.post, &mut item.put, &mut item.delete];
+            for operation in operations.into_iter().flatten() {
+                let responses = &mut operation.responses.responses;
+                responses.entry("401".to_string()).or_insert_with(|| error("Missing or unknown API key"));
+                responses.entry("403".to_string()).or_insert_with(|| error("API key lacks the required scope"));
+                responses.entry("429".to_string()).or_insert_with(|| error("Rate limit exceeded; see Retry-After"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{create_routes, ApiState};
+    use crate::config::{ApiConfig, AuthMode, RateLimitConfig};
+    use crate::consumer::metrics::ROUTES;
+    use crate::consumer::DataProcessor;
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use warp::http::{Request, StatusCode};
+    use warp::hyper::service::Service;
+    use warp::hyper::Body;
+
+    #[test]
+    fn every_route_is_documented() {
+        let doc = ApiDoc::openapi();
+        let documented: BTreeSet<&str> = doc.paths.paths.keys().map(String::as_str).collect();
+        // The document does not describe itself
+        let served: BTreeSet<&str> = ROUTES.into_iter().filter(|route| *route != "/openapi.json").collect();
+        assert_eq!(documented, served);
+
+        let secured = &doc.paths.paths["/api/v1/prices"].get.as_ref().unwrap().responses.responses;
+        assert!(["401", "403", "429"].iter().all(|status| secured.contains_key(*status)));
+        let public = &doc.paths.paths["/health"].get.as_ref().unwrap().responses.responses;
+        assert!(!public.contains_key("401"));
+    }
+
+    #[tokio::test]
+    async fn every_documented_operation_is_served() {
+        let config = ApiConfig {
+            auth: AuthMode::Disabled,
+            keys: Vec::new(),
+            cors_origins: Vec::new(),
+            rate_limit: RateLimitConfig::default(),
+        };
+        let mut service = warp::service(create_routes(Arc::new(ApiState::new(DataProcessor::new(), &config))));
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            let uri = path.replace("{symbol}", "AAPL").replace("{id}", "unknown");
+            let operations = [("GET", &item.get), ("POST", &item.post), ("PUT", &item.put), ("DELETE", &item.delete)];
+            for (method, _) in operations.into_iter().filter(|(_, operation)| operation.is_some()) {
+                // Calling the service directly leaves the endless SSE body unread
+                let request = Request::builder().method(method).uri(&uri).body(Body::empty()).unwrap();
+                let response = service.call(request).await.unwrap();
+                let status = response.status();
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {}", method, uri);
+                if status == StatusCode::NOT_FOUND {
+                    let body = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+                    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                    assert_ne!(body["message"], "no such endpoint", "{} {}", method, uri);
+                }
+            }
+        }
+    }
+}
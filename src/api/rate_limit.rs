@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use warp::http::HeaderValue;
+use warp::reject::Reject;
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use crate::api::auth::{credentials, ApiAuth};
+use crate::config::{RateLimit, RateLimitConfig};
+use crate::consumer::route_label;
+
+/// Most buckets kept; full buckets go first, then the least recently used.
+const MAX_BUCKETS: usize = 10_000;
+
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "x-ratelimit-limit";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+pub const RATE_LIMIT_RESET_HEADER: &str = "x-ratelimit-reset";
+
+/// The client has used up its bucket for this route.
+#[derive(Debug)]
+pub struct RateLimited {
+    pub limit: u32,
+    pub retry_after_secs: u64,
+}
+
+impl Reject for RateLimited {}
+
+/// Bucket state after a request was let through, sent back as headers.
+#[derive(Debug, Clone, Copy)]
+pub struct RateStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again.
+    pub reset_secs: u64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.requests_per_second).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    fn is_full(&self, limit: &RateLimit, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * limit.requests_per_second >= limit.burst as f64
+    }
+
+    fn status(&self, limit: &RateLimit) -> RateStatus {
+        let missing = limit.burst as f64 - self.tokens;
+        RateStatus {
+            limit: limit.burst,
+            remaining: self.tokens.max(0.0).floor() as u32,
+            reset_secs: (missing / limit.requests_per_second).ceil() as u64,
+        }
+    }
+}
+
+type BucketKey = (String, &'static str);
+
+/// Token buckets keyed by client and route, kept apart from the
+/// `DataProcessor` lock so rejected requests never touch it.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    buckets: Arc<Mutex<HashMap<BucketKey, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+            buckets: Arc::default(),
+        }
+    }
+
+    fn limit(&self, route: &str) -> &RateLimit {
+        self.config.routes.get(route).unwrap_or(&self.config.default)
+    }
+
+    /// Takes a token from the client's bucket for `route`.
+    pub fn check(&self, client: &str, route: &'static str) -> Result<RateStatus, RateLimited> {
+        self.check_at(client, route, Instant::now())
+    }
+
+    fn check_at(&self, client: &str, route: &'static str, now: Instant) -> Result<RateStatus, RateLimited> {
+        let limit = *self.limit(route);
+        let mut buckets = self.buckets.lock().unwrap();
+        let key = (client.to_string(), route);
+        if !buckets.contains_key(&key) && buckets.len() >= MAX_BUCKETS {
+            self.evict(&mut buckets, now);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket { tokens: limit.burst as f64, updated: now });
+        bucket.refill(&limit, now);
+
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) / limit.requests_per_second;
+            return Err(RateLimited { limit: limit.burst, retry_after_secs: wait.ceil().max(1.0) as u64 });
+        }
+        bucket.tokens -= 1.0;
+        Ok(bucket.status(&limit))
+    }
+
+    /// Drops buckets that have refilled, then the least recently used tenth
+    /// if that was not enough.
+    fn evict(&self, buckets: &mut HashMap<BucketKey, Bucket>, now: Instant) {
+        buckets.retain(|(_, route), bucket| !bucket.is_full(self.limit(route), now));
+        if buckets.len() < MAX_BUCKETS {
+            return;
+        }
+        let mut used: Vec<(Instant, BucketKey)> = buckets.iter().map(|(key, bucket)| (bucket.updated, key.clone())).collect();
+        used.sort_unstable_by_key(|(updated, _)| *updated);
+        for (_, key) in used.into_iter().take(MAX_BUCKETS / 10) {
+            buckets.remove(&key);
+        }
+    }
+
+    /// The client's bucket for `route` without taking a token.
+    fn status(&self, client: &str, route: &'static str) -> Option<RateStatus> {
+        let limit = *self.limit(route);
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.get_mut(&(client.to_string(), route))?;
+        bucket.refill(&limit, Instant::now());
+        Some(bucket.status(&limit))
+    }
+
+    /// Charges the request to its API key, or its IP address without one, and
+    /// rejects it with `RateLimited` when the bucket is empty. Goes after the
+    /// route and auth filters so only requests that will be served count.
+    pub fn filter(&self, auth: ApiAuth) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        let limiter = self.clone();
+        client_route(auth)
+            .and_then(move |client: String, route: &'static str| {
+                let limiter = limiter.clone();
+                async move {
+                    if !limiter.config.enabled {
+                        return Ok(());
+                    }
+                    limiter.check(&client, route).map(|_| ()).map_err(warp::reject::custom)
+                }
+            })
+            .untuple_one()
+    }
+
+    /// The bucket the request was charged to, for [`with_rate_headers`].
+    pub fn headers(&self, auth: ApiAuth) -> impl Filter<Extract = (Option<RateStatus>,), Error = Rejection> + Clone {
+        let limiter = self.clone();
+        client_route(auth).map(move |client: String, route: &'static str| {
+            if limiter.config.enabled {
+                limiter.status(&client, route)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// The client a request is charged to and its route label.
+fn client_route(auth: ApiAuth) -> impl Filter<Extract = (String, &'static str), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(warp::addr::remote())
+        .and(credentials())
+        .map(move |path: warp::path::FullPath, remote: Option<SocketAddr>, presented: Option<String>| {
+            let client = match auth.identify(presented.as_deref()) {
+                Some(name) => format!("key:{}", name),
+                None => remote.map(|addr| format!("ip:{}", addr.ip())).unwrap_or_else(|| "unknown".to_string()),
+            };
+            (client, route_label(path.as_str()))
+        })
+        .untuple_one()
+}
+
+/// Adds the `x-ratelimit-*` headers describing the client's bucket.
+pub fn with_rate_headers(reply: impl Reply, status: Option<RateStatus>) -> Response {
+    let mut response = reply.into_response();
+    if let Some(status) = status {
+        let headers = response.headers_mut();
+        headers.insert(RATE_LIMIT_LIMIT_HEADER, HeaderValue::from(status.limit));
+        headers.insert(RATE_LIMIT_REMAINING_HEADER, HeaderValue::from(status.remaining));
+        headers.insert(RATE_LIMIT_RESET_HEADER, HeaderValue::from(status.reset_secs));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    const PRICES: &str = "/api/v1/prices";
+    const TRADES: &str = "/api/v1/symbols/{symbol}/trades";
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            enabled: true,
+            default: RateLimit { requests_per_second: 2.0, burst: 3 },
+            routes: BTreeMap::from([(TRADES.to_string(), RateLimit { requests_per_second: 1.0, burst: 1 })]),
+        })
+    }
+
+    #[test]
+    fn bucket_drains_then_refills_at_the_configured_rate() {
+        let limiter = limiter();
+        let start = Instant::now();
+        let remaining: Vec<u32> = (0..3).map(|_| limiter.check_at("ip:1", PRICES, start).unwrap().remaining).collect();
+        assert_eq!(remaining, [2, 1, 0]);
+        let limited = limiter.check_at("ip:1", PRICES, start).unwrap_err();
+        assert_eq!((limited.limit, limited.retry_after_secs), (3, 1));
+
+        // Two tokens a second: one is back after half a second
+        assert!(limiter.check_at("ip:1", PRICES, start + Duration::from_millis(500)).is_ok());
+        assert!(limiter.check_at("ip:1", PRICES, start + Duration::from_millis(500)).is_err());
+        let status = limiter.check_at("ip:1", PRICES, start + Duration::from_secs(10)).unwrap();
+        assert_eq!((status.remaining, status.reset_secs), (2, 1));
+    }
+
+    #[test]
+    fn buckets_are_per_client_and_route() {
+        let limiter = limiter();
+        let now = Instant::now();
+        assert!(limiter.check_at("ip:1", TRADES, now).is_ok());
+        assert_eq!(limiter.check_at("ip:1", TRADES, now).unwrap_err().retry_after_secs, 1);
+        assert!(limiter.check_at("ip:2", TRADES, now).is_ok());
+        assert!(limiter.check_at("ip:1", PRICES, now).is_ok());
+        assert_eq!(limiter.status("ip:1", PRICES).map(|status| status.remaining), Some(2));
+        assert!(limiter.status("ip:3", PRICES).is_none());
+    }
+
+    #[test]
+    fn bucket_count_is_capped_dropping_the_least_recently_used() {
+        let limiter = limiter();
+        let start = Instant::now();
+        for client in 0..MAX_BUCKETS {
+            limiter.check_at(&format!("ip:{}", client), PRICES, start + Duration::from_micros(client as u64)).unwrap();
+        }
+        let later = start + Duration::from_millis(100);
+        limiter.check_at("ip:0", PRICES, later).unwrap();
+        limiter.check_at("ip:new", PRICES, later).unwrap();
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.len() <= MAX_BUCKETS);
+        assert!(buckets.contains_key(&("ip:0".to_string(), PRICES)));
+        assert!(buckets.contains_key(&("ip:new".to_string(), PRICES)));
+        assert!(!buckets.contains_key(&("ip:1".to_string(), PRICES)));
+        assert!(buckets.contains_key(&(format!("ip:{}", MAX_BUCKETS - 1), PRICES)));
+    }
+}
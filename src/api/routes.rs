@@ -1,32 +1,285 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
+use utoipa::OpenApi;
 use warp::Filter;
 
-use crate::api::handlers::{ApiState, get_prices, get_rsi, get_health};
+use crate::api::errors::handle_rejection;
+use crate::api::export::{export_candles, export_rsi, export_trades, ExportQuery};
+use crate::api::handlers::{
+    get_candles, get_health, get_liveness, get_metrics, get_prices, get_readiness, get_rsi, get_rsi_history, get_symbol,
+    get_symbols, get_trades, get_webhooks, ApiState, RsiQuery,
+};
+use crate::api::openapi::ApiDoc;
+use crate::api::rate_limit::{
+    with_rate_headers, RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER, RATE_LIMIT_RESET_HEADER,
+};
+use crate::api::alerts::{
+    create_alert, delete_alert, get_alert, list_alerts, list_fired_alerts, update_alert, FiredAlertsQuery,
+};
+use crate::api::sse::get_stream;
+use crate::api::websocket::ws_upgrade;
+use crate::config::ApiScope;
+use crate::consumer::{route_label, AlertRuleRequest, HistoryQuery};
+use crate::telemetry;
 
-pub fn create_routes(state: Arc<ApiState>) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+/// Largest accepted JSON request body.
+const MAX_BODY_BYTES: u64 = 16 * 1024;
+
+/// All API routes. Data endpoints live under `/api/v1`; health, metrics and
+/// the OpenAPI document are served at the root.
+pub fn create_routes(state: Arc<ApiState>) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
+    let metrics = state.metrics.clone();
+    // Requests are charged to the rate limit only once a route and its auth match
+    let rate_limit = state.limiter.filter(state.auth.clone());
+    let rate_headers = state.limiter.headers(state.auth.clone());
+    let read = state.auth.require(ApiScope::Read).and(rate_limit.clone());
+    let admin = state.auth.require(ApiScope::Admin).and(rate_limit);
+    let cors = cors(&state.cors_origins);
     let state_filter = warp::any().map(move || state.clone());
 
-    let health = warp::path("health")
+    let health = warp::path!("health")
         .and(warp::get())
+        .and(state_filter.clone())
         .and_then(get_health);
 
-    let prices = warp::path("prices")
+    let liveness = warp::path!("health" / "live")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(get_liveness);
+
+    let readiness = warp::path!("health" / "ready")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(get_readiness);
+
+    let prices = warp::path!("prices")
         .and(warp::get())
+        .and(read.clone())
         .and(state_filter.clone())
         .and_then(get_prices);
 
-    let rsi = warp::path("rsi")
+    let rsi = warp::path!("rsi")
         .and(warp::get())
-        .and(state_filter)
+        .and(read.clone())
+        .and(warp::query::<RsiQuery>())
+        .and(state_filter.clone())
         .and_then(get_rsi);
 
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_headers(vec!["content-type"])
-        .allow_methods(vec!["GET", "POST", "DELETE"]);
+    let symbols = warp::path!("symbols")
+        .and(warp::get())
+        .and(read.clone())
+        .and(state_filter.clone())
+        .and_then(get_symbols);
+
+    let symbol = warp::path!("symbols" / String)
+        .and(warp::get())
+        .and(read.clone())
+        .and(state_filter.clone())
+        .and_then(get_symbol);
 
-    health
-        .or(prices)
+    let history = || warp::get().and(read.clone()).and(warp::query::<HistoryQuery>()).and(state_filter.clone());
+
+    let trades = warp::path!("symbols" / String / "trades")
+        .and(history())
+        .and_then(get_trades);
+
+    let rsi_history = warp::path!("symbols" / String / "rsi" / "history")
+        .and(history())
+        .and_then(get_rsi_history);
+
+    let candles = warp::path!("symbols" / String / "candles")
+        .and(history())
+        .and_then(get_candles);
+
+    let export = || warp::get().and(read.clone()).and(warp::query::<ExportQuery>()).and(state_filter.clone());
+
+    let export_trades = warp::path!("export" / "trades")
+        .and(export())
+        .and_then(export_trades);
+
+    let export_candles = warp::path!("export" / "candles")
+        .and(export())
+        .and_then(export_candles);
+
+    let export_rsi = warp::path!("export" / "rsi")
+        .and(export())
+        .and_then(export_rsi);
+
+    let alert_body = || warp::body::content_length_limit(MAX_BODY_BYTES).and(warp::body::json::<AlertRuleRequest>());
+
+    let alerts_list = warp::path!("alerts")
+        .and(warp::get())
+        .and(read.clone())
+        .and(state_filter.clone())
+        .and_then(list_alerts);
+
+    let alerts_create = warp::path!("alerts")
+        .and(warp::post())
+        .and(admin.clone())
+        .and(alert_body())
+        .and(state_filter.clone())
+        .and_then(create_alert);
+
+    let alerts_fired = warp::path!("alerts" / "fired")
+        .and(warp::get())
+        .and(read.clone())
+        .and(warp::query::<FiredAlertsQuery>())
+        .and(state_filter.clone())
+        .and_then(list_fired_alerts);
+
+    let alert_get = warp::path!("alerts" / String)
+        .and(warp::get())
+        .and(read.clone())
+        .and(state_filter.clone())
+        .and_then(get_alert);
+
+    let alert_update = warp::path!("alerts" / String)
+        .and(warp::put())
+        .and(admin.clone())
+        .and(alert_body())
+        .and(state_filter.clone())
+        .and_then(update_alert);
+
+    let alert_delete = warp::path!("alerts" / String)
+        .and(warp::delete())
+        .and(admin.clone())
+        .and(state_filter.clone())
+        .and_then(delete_alert);
+
+    let webhooks = warp::path!("webhooks")
+        .and(warp::get())
+        .and(admin.clone())
+        .and(state_filter.clone())
+        .and_then(get_webhooks);
+
+    let metrics_route = warp::path!("metrics")
+        .and(warp::get())
+        .and(state_filter.clone())
+        .and_then(get_metrics);
+
+    let stream = warp::path!("stream")
+        .and(warp::get())
+        .and(read.clone())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::optional::<String>("last-event-id"))
+        .and(state_filter.clone())
+        .and_then(get_stream);
+
+    let ws = warp::path!("ws")
+        .and(read)
+        .and(warp::ws())
+        .and(state_filter)
+        .map(ws_upgrade);
+
+    let openapi = ApiDoc::openapi();
+    let openapi_route = warp::path!("openapi.json")
+        .and(warp::get())
+        .map(move || warp::reply::json(&openapi));
+
+    let v1 = prices
         .or(rsi)
+        .or(symbols)
+        .or(symbol)
+        .or(trades)
+        .or(rsi_history)
+        .or(candles)
+        .or(export_trades)
+        .or(export_candles)
+        .or(export_rsi)
+        .or(alerts_list)
+        .or(alerts_create)
+        .or(alerts_fired)
+        .or(alert_get)
+        .or(alert_update)
+        .or(alert_delete)
+        .or(webhooks)
+        .or(stream)
+        .or(ws)
+        // Type-erased to keep the combined filter within the compiler's recursion limit
+        .boxed();
+
+    warp::path!("api" / "v1" / ..)
+        .and(v1)
+        .and(rate_headers)
+        .map(with_rate_headers)
+        .or(health)
+        .or(liveness)
+        .or(readiness)
+        .or(metrics_route)
+        .or(openapi_route)
+        .recover(handle_rejection)
         .with(cors)
+        // Disallowed CORS requests are rejected by the wrapper itself
+        .recover(handle_rejection)
+        .with(warp::log::custom(move |info| {
+            metrics.record_request(info.method().as_str(), route_label(info.path()), info.status().as_u16(), info.elapsed());
+            tracing::debug!(status = info.status().as_u16(), elapsed = ?info.elapsed(), "request served");
+        }))
+        .with(warp::trace(telemetry::request_span))
+}
+
+fn cors(origins: &[String]) -> warp::cors::Builder {
+    let cors = warp::cors()
+        .allow_headers(vec!["content-type", "last-event-id", "authorization", "x-api-key"])
+        .expose_headers(vec!["retry-after", RATE_LIMIT_LIMIT_HEADER, RATE_LIMIT_REMAINING_HEADER, RATE_LIMIT_RESET_HEADER])
+        .allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
+    if origins.iter().any(|origin| origin == "*") {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(origins.iter().map(String::as_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiConfig, ApiKey, AuthMode, RateLimit, RateLimitConfig};
+    use crate::consumer::DataProcessor;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    fn state() -> Arc<ApiState> {
+        let config = ApiConfig {
+            auth: AuthMode::Required,
+            keys: vec![ApiKey {
+                name: "dashboard".to_string(),
+                sha256: hex::encode(Sha256::digest(b"read-key")),
+                scopes: vec![ApiScope::Read],
+            }],
+            cors_origins: Vec::new(),
+            rate_limit: RateLimitConfig {
+                enabled: true,
+                default: RateLimit { requests_per_second: 0.001, burst: 2 },
+                routes: BTreeMap::new(),
+            },
+        };
+        Arc::new(ApiState::new(DataProcessor::new(), &config))
+    }
+
+    #[tokio::test]
+    async fn only_matched_authorised_requests_use_up_tokens() {
+        let routes = create_routes(state());
+        let get = |path: &str, key: Option<&str>| {
+            let request = warp::test::request().path(path).remote_addr(([10, 0, 0, 1], 4000).into());
+            match key {
+                Some(key) => request.header("x-api-key", key),
+                None => request,
+            }
+        };
+
+        for _ in 0..5 {
+            assert_eq!(get("/api/v1/nope", Some("read-key")).reply(&routes).await.status(), 404);
+            assert_eq!(get("/api/v1/prices", None).reply(&routes).await.status(), 401);
+            assert_eq!(get("/api/v1/prices", Some("guess")).reply(&routes).await.status(), 401);
+        }
+
+        let first = get("/api/v1/prices", Some("read-key")).reply(&routes).await;
+        assert_eq!(first.status(), 200);
+        assert_eq!(first.headers()[RATE_LIMIT_REMAINING_HEADER], "1");
+        assert_eq!(get("/api/v1/prices", Some("read-key")).reply(&routes).await.status(), 200);
+        assert_eq!(get("/api/v1/prices", Some("read-key")).reply(&routes).await.status(), 429);
+        // Other routes have their own bucket
+        assert_eq!(get("/api/v1/symbols", Some("read-key")).reply(&routes).await.status(), 200);
+    }
 }
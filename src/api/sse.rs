@@ -0,0 +1,329 @@
+use futures::Stream;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use warp::http::StatusCode;
+use warp::sse::Event;
+use warp::Reply;
+
+use crate::api::errors::{error_reply, ApiError};
+use crate::api::handlers::{split_list, ApiState};
+use crate::api::websocket::Subscription;
+use crate::consumer::{Channel, DataProcessor, StreamEvent};
+
+/// Events buffered per client before updates start being coalesced.
+const CLIENT_BUFFER: usize = 64;
+/// Trades, signal changes and alerts queued for a client that has fallen
+/// behind before it is told to resync instead.
+const MAX_QUEUED_EVENTS: usize = 1024;
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// `GET /stream?symbols=AAPL,MSFT&channels=rsi` as Server-Sent Events.
+///
+/// Each event carries the publishing id, so a reconnecting client sending
+/// `Last-Event-ID` gets the events it missed from the replay buffer. When a
+/// client falls behind, price, RSI and candle updates are coalesced to the
+/// latest one per symbol while trades, signals and alerts stay queued. A
+/// `reset` event tells the client that events were lost and it should
+/// reload current state.
+#[utoipa::path(get, path = "/api/v1/stream", tag = "streaming",
+    params(
+        ("symbols" = Option<String>, Query, description = "Comma-separated symbols; all when omitted"),
+        ("channels" = Option<String>, Query, description = "Comma-separated channels, e.g. `trades,rsi`"),
+        ("Last-Event-ID" = Option<String>, Header, description = "Resume after this event id"),
+    ),
+    responses(
+        (status = 200, description = "Server-Sent Events", content_type = "text/event-stream"),
+        (status = 400, description = "Unknown channel", body = ApiError),
+    ))]
+pub async fn get_stream(
+    query: HashMap<String, String>,
+    last_event_id: Option<String>,
+    state: Arc<ApiState>,
+) -> Result<Box<dyn Reply>, warp::Rejection> {
+    let mut subscription = Subscription::default();
+    let symbols: Vec<String> = split_list(query.get("symbols"));
+    let channels = match split_list(query.get("channels"))
+        .iter()
+        .map(|channel| channel.parse::<Channel>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(channels) => channels,
+        Err(message) => return Ok(error_reply(StatusCode::BAD_REQUEST, message)),
+    };
+    subscription.subscribe(symbols, channels);
+
+    let last_event_id = last_event_id.and_then(|id| id.trim().parse::<u64>().ok());
+    let processor = state.data_processor.read().await.clone();
+    let stream = client_stream(processor, subscription, last_event_id);
+
+    Ok(Box::new(warp::sse::reply(
+        warp::sse::keep_alive().interval(KEEP_ALIVE_INTERVAL).stream(stream),
+    )))
+}
+
+fn to_sse(event: &StreamEvent) -> Event {
+    let payload = serde_json::to_value(&event.event)
+        .ok()
+        .and_then(|mut value| value.get_mut("data").map(serde_json::Value::take))
+        .unwrap_or_default();
+    Event::default()
+        .id(event.id.to_string())
+        .event(event.event.channel().to_string())
+        .data(payload.to_string())
+}
+
+/// Spawns the task feeding one client and returns the receiving end.
+fn client_stream(
+    processor: DataProcessor,
+    subscription: Subscription,
+    last_event_id: Option<u64>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    let (sender, receiver) = mpsc::channel::<Event>(CLIENT_BUFFER);
+
+    tokio::spawn(async move {
+        // Subscribe before reading the replay buffer so nothing falls in between
+        let mut events = processor.subscribe();
+        let mut feed = ClientFeed::new(sender.clone(), subscription);
+
+        if let Some(last_id) = last_event_id {
+            match processor.replay_since(last_id) {
+                Some(missed) => {
+                    feed.last_id = last_id;
+                    for event in missed {
+                        if !feed.push(event) {
+                            return;
+                        }
+                    }
+                }
+                None => feed.reset("requested events are no longer available"),
+            }
+        }
+
+        loop {
+            if !feed.is_behind() {
+                tokio::select! {
+                    event = events.recv() => if !feed.receive(event, &processor) { break },
+                    _ = sender.closed() => break,
+                }
+            } else {
+                tokio::select! {
+                    event = events.recv() => if !feed.receive(event, &processor) { break },
+                    permit = sender.reserve() => match permit {
+                        Ok(permit) => {
+                            if let Some(event) = feed.next_pending() {
+                                permit.send(event);
+                            }
+                        }
+                        Err(_) => break,
+                    },
+                }
+            }
+        }
+    });
+
+    futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (Ok(event), receiver))
+    })
+}
+
+/// Per-client delivery state.
+struct ClientFeed {
+    sender: mpsc::Sender<Event>,
+    subscription: Subscription,
+    /// Latest undelivered state update per (symbol, channel) while the
+    /// client is behind.
+    coalesced: BTreeMap<(String, Channel), StreamEvent>,
+    /// Undelivered discrete events, oldest first.
+    queued: VecDeque<StreamEvent>,
+    /// Why the client must reload state, sent before anything pending.
+    reset: Option<&'static str>,
+    last_id: u64,
+}
+
+impl ClientFeed {
+    fn new(sender: mpsc::Sender<Event>, subscription: Subscription) -> Self {
+        Self {
+            sender,
+            subscription,
+            coalesced: BTreeMap::new(),
+            queued: VecDeque::new(),
+            reset: None,
+            last_id: 0,
+        }
+    }
+
+    fn is_behind(&self) -> bool {
+        self.reset.is_some() || !self.coalesced.is_empty() || !self.queued.is_empty()
+    }
+
+    /// Handles a broadcast result; returns false once the client is gone.
+    fn receive(&mut self, event: Result<StreamEvent, RecvError>, processor: &DataProcessor) -> bool {
+        match event {
+            Ok(event) => self.push(event),
+            Err(RecvError::Lagged(_)) => match processor.replay_since(self.last_id) {
+                Some(missed) => missed.into_iter().all(|event| self.push(event)),
+                None => {
+                    self.reset("events were dropped while the client was behind");
+                    true
+                }
+            },
+            Err(RecvError::Closed) => false,
+        }
+    }
+
+    /// Sends `event` directly if the client keeps up, otherwise holds it back.
+    fn push(&mut self, event: StreamEvent) -> bool {
+        if event.id <= self.last_id {
+            return true;
+        }
+        self.last_id = event.id;
+        if !self.subscription.matches(&event.event) {
+            return true;
+        }
+
+        if !self.is_behind() {
+            match self.sender.try_send(to_sse(&event)) {
+                Ok(()) => return true,
+                Err(mpsc::error::TrySendError::Full(_)) => {}
+                Err(mpsc::error::TrySendError::Closed(_)) => return false,
+            }
+        }
+        let channel = event.event.channel();
+        if channel.is_state() {
+            self.coalesced.insert((event.event.symbol().to_string(), channel), event);
+        } else if self.queued.len() < MAX_QUEUED_EVENTS {
+            self.queued.push_back(event);
+        } else {
+            self.reset("too many undelivered events");
+        }
+        true
+    }
+
+    /// Drops everything pending; the client is sent a `reset` event next.
+    fn reset(&mut self, reason: &'static str) {
+        self.coalesced.clear();
+        self.queued.clear();
+        self.reset = Some(reason);
+    }
+
+    /// The next event to deliver: a reset first, then pending events by id.
+    fn next_pending(&mut self) -> Option<Event> {
+        if let Some(reason) = self.reset.take() {
+            let data = serde_json::json!({ "reason": reason, "last_id": self.last_id });
+            return Some(Event::default().event("reset").data(data.to_string()));
+        }
+        let oldest_coalesced = self
+            .coalesced
+            .iter()
+            .min_by_key(|(_, event)| event.id)
+            .map(|(key, event)| (key.clone(), event.id));
+        let event = match (oldest_coalesced, self.queued.front()) {
+            (Some((_, coalesced_id)), Some(queued)) if queued.id < coalesced_id => self.queued.pop_front(),
+            (Some((key, _)), _) => self.coalesced.remove(&key),
+            (None, _) => self.queued.pop_front(),
+        };
+        event.as_ref().map(to_sse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consumer::{MarketEvent, PriceUpdate};
+    use crate::models::{Decimal, TradeData, TradeSide};
+    use chrono::Utc;
+
+    fn trade(id: u64) -> StreamEvent {
+        let trade = TradeData::new("AAPL".to_string(), Decimal::new(150, 0), 100, TradeSide::Buy, "NASDAQ".to_string());
+        StreamEvent { id, event: MarketEvent::Trade(trade) }
+    }
+
+    fn price(id: u64) -> StreamEvent {
+        let update = PriceUpdate { symbol: "AAPL".to_string(), price: Decimal::new(150, 0), timestamp: Utc::now() };
+        StreamEvent { id, event: MarketEvent::Price(update) }
+    }
+
+    /// A feed for every symbol and channel whose client holds `capacity` events.
+    fn client(capacity: usize) -> (ClientFeed, mpsc::Receiver<Event>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let mut subscription = Subscription::default();
+        subscription.subscribe(Vec::new(), Vec::new());
+        (ClientFeed::new(sender, subscription), receiver)
+    }
+
+    fn drain(feed: &mut ClientFeed) -> Vec<String> {
+        std::iter::from_fn(|| feed.next_pending()).map(|event| event.to_string()).collect()
+    }
+
+    #[test]
+    fn behind_clients_get_latest_state_but_every_discrete_event() {
+        let (mut feed, mut receiver) = client(1);
+        for event in [trade(1), price(2), trade(3), price(4), trade(5), price(6)] {
+            assert!(feed.push(event));
+        }
+        assert!(receiver.try_recv().unwrap().to_string().contains("id:1\n"));
+
+        let pending = drain(&mut feed);
+        let ids: Vec<&str> = pending.iter().map(|event| event.lines().find(|line| line.starts_with("id:")).unwrap()).collect();
+        assert_eq!(ids, ["id:3", "id:5", "id:6"]);
+        assert!(pending[0].starts_with("event:trades\n"));
+        assert!(pending[2].starts_with("event:prices\n"));
+        assert!(!feed.is_behind());
+    }
+
+    #[test]
+    fn overflowing_queue_resets_the_client() {
+        let (mut feed, _receiver) = client(1);
+        for id in 1..=(MAX_QUEUED_EVENTS as u64 + 2) {
+            assert!(feed.push(trade(id)));
+        }
+        assert!(feed.push(trade(MAX_QUEUED_EVENTS as u64 + 3)));
+
+        let pending = drain(&mut feed);
+        assert!(pending[0].starts_with("event:reset\n"));
+        assert!(pending[0].contains("too many undelivered events"));
+        assert_eq!(pending.len(), 2);
+    }
+
+    #[test]
+    fn duplicate_and_unsubscribed_events_are_skipped() {
+        let (sender, mut receiver) = mpsc::channel(8);
+        let mut subscription = Subscription::default();
+        subscription.subscribe(vec!["MSFT".to_string()], vec![Channel::Trades]);
+        let mut feed = ClientFeed::new(sender, subscription);
+        feed.last_id = 2;
+
+        assert!(feed.push(trade(2)));
+        assert!(feed.push(trade(3)));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(feed.last_id, 3);
+    }
+
+    #[tokio::test]
+    async fn lag_past_the_replay_buffer_sends_reset() {
+        let processor = DataProcessor::new();
+        for _ in 0..800 {
+            let trade = TradeData::new("AAPL".to_string(), Decimal::new(150, 0), 100, TradeSide::Buy, "NASDAQ".to_string());
+            processor.process_trade_data(trade).await;
+        }
+        assert!(processor.replay_since(1).is_none());
+
+        let (mut feed, _receiver) = client(1);
+        feed.last_id = 1;
+        assert!(feed.receive(Err(RecvError::Lagged(100)), &processor));
+        let pending = drain(&mut feed);
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].starts_with("event:reset\n"));
+
+        // A lag the replay buffer still covers is filled in from it
+        let (mut feed, mut receiver) = client(4096);
+        let recent = processor.replay_since(2000).unwrap();
+        feed.last_id = 2000;
+        assert!(feed.receive(Err(RecvError::Lagged(1)), &processor));
+        assert_eq!(std::iter::from_fn(|| receiver.try_recv().ok()).count(), recent.len());
+    }
+}
@@ -0,0 +1,333 @@
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::timeout;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::Reply;
+
+use crate::api::handlers::ApiState;
+use crate::consumer::{Channel, MarketEvent, StreamEvent};
+
+/// How often the server pings each client.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Clients silent for longer than this (no pong or message) are dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+/// A single send taking longer than this marks the client as too slow.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Messages accepted from clients, e.g.
+/// `{"action": "subscribe", "symbols": ["AAPL"], "channels": ["trades", "rsi"]}`.
+/// Empty `symbols` means every symbol and empty `channels` every channel.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum ClientCommand {
+    Subscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+        #[serde(default)]
+        channels: Vec<Channel>,
+    },
+    Unsubscribe {
+        #[serde(default)]
+        symbols: Vec<String>,
+        #[serde(default)]
+        channels: Vec<Channel>,
+    },
+    Ping,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscribed {
+        all_symbols: bool,
+        symbols: &'a BTreeSet<String>,
+        channels: &'a BTreeSet<Channel>,
+    },
+    Update {
+        symbol: &'a str,
+        #[serde(flatten)]
+        event: &'a MarketEvent,
+    },
+    Pong,
+    Error {
+        message: String,
+    },
+}
+
+/// What a single client wants to receive.
+#[derive(Debug, Default)]
+pub(crate) struct Subscription {
+    all_symbols: bool,
+    symbols: BTreeSet<String>,
+    channels: BTreeSet<Channel>,
+}
+
+impl Subscription {
+    pub(crate) fn subscribe(&mut self, symbols: Vec<String>, channels: Vec<Channel>) {
+        if symbols.is_empty() || symbols.iter().any(|symbol| symbol == "*") {
+            self.all_symbols = true;
+        } else {
+            self.symbols.extend(symbols.into_iter().map(|symbol| symbol.to_uppercase()));
+        }
+        if channels.is_empty() {
+            self.channels.extend(Channel::ALL);
+        } else {
+            self.channels.extend(channels);
+        }
+    }
+
+    fn unsubscribe(&mut self, symbols: Vec<String>, channels: Vec<Channel>) {
+        match (symbols.is_empty(), channels.is_empty()) {
+            (true, true) => *self = Subscription::default(),
+            (true, false) => {
+                for channel in channels {
+                    self.channels.remove(&channel);
+                }
+            }
+            (false, _) => {
+                if symbols.iter().any(|symbol| symbol == "*") {
+                    self.all_symbols = false;
+                }
+                for symbol in symbols {
+                    self.symbols.remove(&symbol.to_uppercase());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn matches(&self, event: &MarketEvent) -> bool {
+        self.channels.contains(&event.channel())
+            && (self.all_symbols || self.symbols.contains(event.symbol()))
+    }
+
+    fn describe(&self) -> ServerMessage<'_> {
+        ServerMessage::Subscribed {
+            all_symbols: self.all_symbols,
+            symbols: &self.symbols,
+            channels: &self.channels,
+        }
+    }
+}
+
+fn to_message(message: &ServerMessage) -> Message {
+    Message::text(serde_json::to_string(message).unwrap_or_default())
+}
+
+/// `GET /ws`: upgrades to a WebSocket streaming the subscribed channels.
+#[utoipa::path(get, path = "/api/v1/ws", tag = "streaming",
+    responses((status = 101, description = "Switching to the WebSocket protocol")))]
+pub fn ws_upgrade(ws: Ws, state: Arc<ApiState>) -> impl Reply {
+    ws.on_upgrade(move |socket| client_session(socket, state))
+}
+
+/// Runs one client connection until it closes, times out or falls behind.
+pub async fn client_session(socket: WebSocket, state: Arc<ApiState>) {
+    let mut events = state.data_processor.read().await.subscribe();
+    let (mut sink, mut stream) = socket.split();
+    let mut subscription = Subscription::default();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    tracing::info!("websocket client connected");
+
+    let reason = loop {
+        let outgoing = tokio::select! {
+            incoming = stream.next() => {
+                let message = match incoming {
+                    Some(Ok(message)) => message,
+                    Some(Err(e)) => break format!("receive error: {}", e),
+                    None => break "client disconnected".to_string(),
+                };
+                last_seen = Instant::now();
+                if message.is_close() {
+                    break "client closed connection".to_string();
+                }
+                let Ok(text) = message.to_str() else {
+                    // Pongs and binary frames only count as liveness
+                    continue;
+                };
+                match serde_json::from_str::<ClientCommand>(text) {
+                    Ok(ClientCommand::Subscribe { symbols, channels }) => {
+                        subscription.subscribe(symbols, channels);
+                        to_message(&subscription.describe())
+                    }
+                    Ok(ClientCommand::Unsubscribe { symbols, channels }) => {
+                        subscription.unsubscribe(symbols, channels);
+                        to_message(&subscription.describe())
+                    }
+                    Ok(ClientCommand::Ping) => to_message(&ServerMessage::Pong),
+                    Err(e) => to_message(&ServerMessage::Error {
+                        message: format!("invalid command: {}", e),
+                    }),
+                }
+            }
+            event = events.recv() => match event {
+                Ok(StreamEvent { event, .. }) if subscription.matches(&event) => to_message(&ServerMessage::Update {
+                    symbol: event.symbol(),
+                    event: &event,
+                }),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => {
+                    let reason = format!("slow client, {} updates dropped", skipped);
+                    let _ = sink.send(Message::close_with(1008u16, reason.clone())).await;
+                    break reason;
+                }
+                Err(RecvError::Closed) => break "server shutting down".to_string(),
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    break "heartbeat timeout".to_string();
+                }
+                Message::ping(Vec::new())
+            }
+        };
+
+        match timeout(SEND_TIMEOUT, sink.send(outgoing)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => break format!("send error: {}", e),
+            Err(_) => break "slow client, send timed out".to_string(),
+        }
+    };
+
+    let _ = sink.close().await;
+    tracing::info!(%reason, "websocket client disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ApiConfig, AuthMode, RateLimitConfig};
+    use crate::consumer::{DataProcessor, PriceUpdate};
+    use crate::models::{Decimal, TradeData, TradeSide};
+    use warp::Filter;
+
+    fn trade(symbol: &str) -> MarketEvent {
+        MarketEvent::Trade(TradeData::new(symbol.to_string(), Decimal::from(100), 10, TradeSide::Buy, "NASDAQ".to_string()))
+    }
+
+    fn price(symbol: &str) -> MarketEvent {
+        MarketEvent::Price(PriceUpdate { symbol: symbol.to_string(), price: Decimal::from(100), timestamp: chrono::Utc::now() })
+    }
+
+    fn state() -> Arc<ApiState> {
+        let config = ApiConfig {
+            auth: AuthMode::Disabled,
+            keys: Vec::new(),
+            cors_origins: Vec::new(),
+            rate_limit: RateLimitConfig::default(),
+        };
+        Arc::new(ApiState::new(DataProcessor::new(), &config))
+    }
+
+    /// Next message other than a heartbeat ping.
+    async fn recv(client: &mut warp::test::WsClient) -> Message {
+        loop {
+            let message = client.recv().await.unwrap();
+            if !message.is_ping() {
+                return message;
+            }
+        }
+    }
+
+    async fn recv_json(client: &mut warp::test::WsClient) -> serde_json::Value {
+        serde_json::from_str(recv(client).await.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn subscriptions_filter_by_symbol_and_channel() {
+        let mut subscription = Subscription::default();
+        assert!(!subscription.matches(&trade("AAPL")));
+
+        subscription.subscribe(vec!["aapl".to_string()], vec![Channel::Trades]);
+        assert!(subscription.matches(&trade("AAPL")));
+        assert!(!subscription.matches(&trade("MSFT")));
+        assert!(!subscription.matches(&price("AAPL")));
+
+        // No channels means all of them; `*` means every symbol
+        subscription.subscribe(vec!["*".to_string()], Vec::new());
+        assert!(subscription.matches(&price("MSFT")));
+    }
+
+    #[test]
+    fn unsubscribing_removes_symbols_channels_or_everything() {
+        let mut subscription = Subscription::default();
+        subscription.subscribe(vec!["AAPL".to_string(), "MSFT".to_string()], Vec::new());
+
+        subscription.unsubscribe(vec!["msft".to_string()], Vec::new());
+        assert!(subscription.matches(&trade("AAPL")));
+        assert!(!subscription.matches(&trade("MSFT")));
+
+        subscription.unsubscribe(Vec::new(), vec![Channel::Trades]);
+        assert!(!subscription.matches(&trade("AAPL")));
+        assert!(subscription.matches(&price("AAPL")));
+
+        subscription.unsubscribe(Vec::new(), Vec::new());
+        assert!(!subscription.matches(&price("AAPL")));
+    }
+
+    #[tokio::test]
+    async fn clients_get_only_what_they_subscribed_to() {
+        let state = state();
+        let processor = state.data_processor.read().await.clone();
+        let route = warp::ws().and(warp::any().map(move || state.clone())).map(ws_upgrade);
+        let mut client = warp::test::ws().handshake(route).await.unwrap();
+
+        client.send_text(r#"{"action": "subscribe", "symbols": ["MSFT"], "channels": ["trades"]}"#).await;
+        let subscribed = recv_json(&mut client).await;
+        assert_eq!(subscribed["type"], "subscribed");
+        assert_eq!(subscribed["symbols"], serde_json::json!(["MSFT"]));
+
+        for symbol in ["AAPL", "MSFT"] {
+            processor.process_trade_data(TradeData::new(symbol.to_string(), Decimal::from(100), 10, TradeSide::Buy, "NASDAQ".to_string())).await;
+        }
+        let update = recv_json(&mut client).await;
+        assert_eq!((update["type"].as_str(), update["symbol"].as_str()), (Some("update"), Some("MSFT")));
+        assert_eq!(update["channel"], "trades");
+
+        client.send_text(r#"{"action": "subscribe", "channels": ["nonsense"]}"#).await;
+        assert_eq!(recv_json(&mut client).await["type"], "error");
+        client.send_text(r#"{"action": "ping"}"#).await;
+        assert_eq!(recv_json(&mut client).await["type"], "pong");
+    }
+
+    #[tokio::test]
+    async fn lagging_clients_are_closed_with_policy_violation() {
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let state = state();
+        let processor = state.data_processor.read().await.clone();
+        let route = warp::ws().and(warp::any().map(move || state.clone())).map(ws_upgrade);
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}", address)).await.unwrap();
+        client.send(ClientMessage::text(r#"{"action": "subscribe"}"#)).await.unwrap();
+        while !client.next().await.unwrap().unwrap().is_text() {}
+
+        // Publish far more than the event channel holds while the session,
+        // on this thread's runtime, cannot run
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            runtime.block_on(async {
+                for _ in 0..2000 {
+                    processor.process_trade_data(TradeData::new("AAPL".to_string(), Decimal::from(100), 10, TradeSide::Buy, "NASDAQ".to_string())).await;
+                }
+            });
+        })
+        .join()
+        .unwrap();
+
+        let close = loop {
+            match client.next().await.unwrap().unwrap() {
+                ClientMessage::Close(frame) => break frame.expect("expected a close code"),
+                _ => continue,
+            }
+        };
+        assert_eq!(close.code, CloseCode::Policy);
+        assert!(close.reason.starts_with("slow client"), "{}", close.reason);
+    }
+}
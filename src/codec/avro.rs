@@ -0,0 +1,484 @@
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::codec::wire::{read_zigzag, write_zigzag};
+
+/// Registered for trade topics; field names follow `TradeData`'s serde names.
+pub const TRADE_AVRO: &str = r#"{
+  "type": "record",
+  "name": "TradeData",
+  "namespace": "trading.v1",
+  "fields": [
+    {"name": "id", "type": "string"},
+    {"name": "symbol", "type": "string"},
+    {"name": "price", "type": {"type": "bytes", "logicalType": "decimal", "precision": 28, "scale": 8}},
+    {"name": "volume", "type": "long"},
+    {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+    {"name": "side", "type": {"type": "enum", "name": "TradeSide", "symbols": ["Buy", "Sell"]}},
+    {"name": "exchange", "type": "string"}
+  ]
+}"#;
+
+/// Registered for RSI topics; field names follow `RsiData`'s serde names.
+pub const RSI_AVRO: &str = r#"{
+  "type": "record",
+  "name": "RsiData",
+  "namespace": "trading.v1",
+  "fields": [
+    {"name": "id", "type": "string"},
+    {"name": "symbol", "type": "string"},
+    {"name": "rsi_value", "type": "double"},
+    {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+    {"name": "period", "type": "int"},
+    {"name": "signal", "type": {"type": "enum", "name": "RsiSignal", "symbols": ["Overbought", "Oversold", "Neutral"]}}
+  ]
+}"#;
+
+/// The subset of Avro schemas needed to read and write the models; named
+/// types may be referenced again by name after their definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvroSchema {
+    Null,
+    Boolean,
+    Int,
+    Long {
+        /// `timestamp-millis` and `timestamp-micros` map to RFC 3339 strings.
+        logical_type: Option<String>,
+    },
+    Float,
+    Double,
+    Bytes,
+    String,
+    /// `bytes` with the `decimal` logical type; maps to a decimal string.
+    Decimal {
+        scale: u32,
+    },
+    Record {
+        name: String,
+        fields: Vec<(String, AvroSchema)>,
+    },
+    Enum {
+        name: String,
+        symbols: Vec<String>,
+    },
+    Array(Box<AvroSchema>),
+    Map(Box<AvroSchema>),
+    Union(Vec<AvroSchema>),
+    Fixed {
+        name: String,
+        size: usize,
+    },
+}
+
+impl AvroSchema {
+    pub fn parse(schema: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(schema).map_err(|e| format!("invalid Avro schema: {}", e))?;
+        Self::from_value(&value, &mut HashMap::new())
+    }
+
+    fn from_value(value: &Value, named: &mut HashMap<String, AvroSchema>) -> Result<Self, String> {
+        let schema = match value {
+            Value::String(name) => match name.as_str() {
+                "null" => AvroSchema::Null,
+                "boolean" => AvroSchema::Boolean,
+                "int" => AvroSchema::Int,
+                "long" => AvroSchema::Long { logical_type: None },
+                "float" => AvroSchema::Float,
+                "double" => AvroSchema::Double,
+                "bytes" => AvroSchema::Bytes,
+                "string" => AvroSchema::String,
+                other => named
+                    .get(other)
+                    .or_else(|| named.get(other.rsplit('.').next().unwrap_or(other)))
+                    .cloned()
+                    .ok_or_else(|| format!("unknown Avro type '{}'", other))?,
+            },
+            Value::Array(branches) => AvroSchema::Union(
+                branches.iter().map(|branch| Self::from_value(branch, named)).collect::<Result<_, _>>()?,
+            ),
+            Value::Object(object) => {
+                let kind = object.get("type").ok_or("Avro schema object without 'type'")?;
+                let name = || object.get("name").and_then(Value::as_str).map(str::to_string).ok_or("named Avro type without 'name'");
+                let schema = match kind.as_str() {
+                    Some("record") | Some("error") => {
+                        let fields = object.get("fields").and_then(Value::as_array).ok_or("Avro record without 'fields'")?;
+                        let fields = fields
+                            .iter()
+                            .map(|field| {
+                                let field_name = field.get("name").and_then(Value::as_str).ok_or("Avro field without 'name'")?;
+                                let field_type = field.get("type").ok_or("Avro field without 'type'")?;
+                                Ok((field_name.to_string(), Self::from_value(field_type, named)?))
+                            })
+                            .collect::<Result<_, String>>()?;
+                        AvroSchema::Record { name: name()?, fields }
+                    }
+                    Some("enum") => {
+                        let symbols = object.get("symbols").and_then(Value::as_array).ok_or("Avro enum without 'symbols'")?;
+                        let symbols = symbols.iter().filter_map(Value::as_str).map(str::to_string).collect();
+                        AvroSchema::Enum { name: name()?, symbols }
+                    }
+                    Some("fixed") => {
+                        let size = object.get("size").and_then(Value::as_u64).ok_or("Avro fixed without 'size'")?;
+                        AvroSchema::Fixed { name: name()?, size: size as usize }
+                    }
+                    Some("array") => AvroSchema::Array(Box::new(Self::from_value(object.get("items").ok_or("Avro array without 'items'")?, named)?)),
+                    Some("map") => AvroSchema::Map(Box::new(Self::from_value(object.get("values").ok_or("Avro map without 'values'")?, named)?)),
+                    Some("long") => AvroSchema::Long {
+                        logical_type: object.get("logicalType").and_then(Value::as_str).map(str::to_string),
+                    },
+                    Some("bytes") if object.get("logicalType").and_then(Value::as_str) == Some("decimal") => AvroSchema::Decimal {
+                        scale: object.get("scale").and_then(Value::as_u64).unwrap_or(0) as u32,
+                    },
+                    _ => Self::from_value(kind, named)?,
+                };
+                if let AvroSchema::Record { name, .. } | AvroSchema::Enum { name, .. } | AvroSchema::Fixed { name, .. } = &schema {
+                    named.insert(name.clone(), schema.clone());
+                }
+                schema
+            }
+            other => return Err(format!("invalid Avro schema {}", other)),
+        };
+        Ok(schema)
+    }
+
+    /// Whether `value` can be written with this schema; picks union branches.
+    fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (AvroSchema::Null, Value::Null) => true,
+            (AvroSchema::Boolean, Value::Bool(_)) => true,
+            (AvroSchema::Int | AvroSchema::Long { .. }, Value::Number(number)) => number.is_i64() || number.is_u64(),
+            (AvroSchema::Long { logical_type: Some(_) }, Value::String(_)) => true,
+            (AvroSchema::Float | AvroSchema::Double, Value::Number(_)) => true,
+            (AvroSchema::String | AvroSchema::Bytes, Value::String(_)) => true,
+            (AvroSchema::Decimal { .. }, Value::String(_) | Value::Number(_)) => true,
+            (AvroSchema::Enum { symbols, .. }, Value::String(symbol)) => symbols.contains(symbol),
+            (AvroSchema::Record { .. } | AvroSchema::Map(_), Value::Object(_)) => true,
+            (AvroSchema::Array(_), Value::Array(_)) => true,
+            (AvroSchema::Union(branches), value) => branches.iter().any(|branch| branch.accepts(value)),
+            _ => false,
+        }
+    }
+}
+
+/// Serializes `record` through serde and writes it as Avro binary.
+pub fn encode<T: Serialize>(schema: &AvroSchema, record: &T) -> Result<Vec<u8>, String> {
+    let value = serde_json::to_value(record).map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    write_value(schema, &value, &mut out)?;
+    Ok(out)
+}
+
+/// Reads Avro binary written with `schema` and deserializes it through serde,
+/// so fields the reader does not know are ignored.
+pub fn decode<T: DeserializeOwned>(schema: &AvroSchema, input: &[u8]) -> Result<T, String> {
+    serde_json::from_value(decode_value(schema, input)?).map_err(|e| e.to_string())
+}
+
+/// Reads Avro binary written with `schema` into its JSON form.
+pub fn decode_value(schema: &AvroSchema, mut input: &[u8]) -> Result<Value, String> {
+    read_value(schema, &mut input)
+}
+
+fn write_value(schema: &AvroSchema, value: &Value, out: &mut Vec<u8>) -> Result<(), String> {
+    let mismatch = || format!("cannot write {} as {:?}", value, schema);
+    match schema {
+        AvroSchema::Null => {}
+        AvroSchema::Boolean => out.push(value.as_bool().ok_or_else(mismatch)? as u8),
+        AvroSchema::Int => write_zigzag(out, value.as_i64().ok_or_else(mismatch)?),
+        AvroSchema::Long { logical_type } => {
+            let number = match (value, logical_type.as_deref()) {
+                (Value::String(time), Some(logical_type)) => {
+                    let time = DateTime::parse_from_rfc3339(time).map_err(|e| e.to_string())?.with_timezone(&Utc);
+                    match logical_type {
+                        "timestamp-millis" => time.timestamp_millis(),
+                        "timestamp-micros" => time.timestamp_micros(),
+                        _ => return Err(mismatch()),
+                    }
+                }
+                _ => value.as_i64().or_else(|| value.as_u64().map(|number| number as i64)).ok_or_else(mismatch)?,
+            };
+            write_zigzag(out, number);
+        }
+        AvroSchema::Float => out.extend_from_slice(&(value.as_f64().ok_or_else(mismatch)? as f32).to_le_bytes()),
+        AvroSchema::Double => out.extend_from_slice(&value.as_f64().ok_or_else(mismatch)?.to_le_bytes()),
+        AvroSchema::Bytes | AvroSchema::String => {
+            let text = value.as_str().ok_or_else(mismatch)?;
+            write_zigzag(out, text.len() as i64);
+            out.extend_from_slice(text.as_bytes());
+        }
+        AvroSchema::Decimal { scale } => {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                Value::Number(number) => number.to_string(),
+                _ => return Err(mismatch()),
+            };
+            let mut decimal = Decimal::from_str(&text).map_err(|e| format!("invalid decimal '{}': {}", text, e))?;
+            // Rescaling would round away digits beyond the scale
+            let exact = decimal.normalize().scale() <= *scale;
+            decimal.rescale(*scale);
+            if !exact || decimal.scale() != *scale {
+                return Err(format!("decimal {} does not fit scale {}", text, scale));
+            }
+            // Minimal big-endian two's complement of the unscaled value
+            let bytes = decimal.mantissa().to_be_bytes();
+            let sign = if decimal.mantissa() < 0 { 0xff } else { 0x00 };
+            let mut start = 0;
+            while start < bytes.len() - 1 && bytes[start] == sign && (bytes[start + 1] & 0x80) == (sign & 0x80) {
+                start += 1;
+            }
+            write_zigzag(out, (bytes.len() - start) as i64);
+            out.extend_from_slice(&bytes[start..]);
+        }
+        AvroSchema::Record { fields, .. } => {
+            let object = value.as_object().ok_or_else(mismatch)?;
+            for (name, field) in fields {
+                write_value(field, object.get(name).unwrap_or(&Value::Null), out)
+                    .map_err(|e| format!("field '{}': {}", name, e))?;
+            }
+        }
+        AvroSchema::Enum { symbols, .. } => {
+            let symbol = value.as_str().ok_or_else(mismatch)?;
+            let index = symbols.iter().position(|known| known == symbol).ok_or_else(mismatch)?;
+            write_zigzag(out, index as i64);
+        }
+        AvroSchema::Array(items) => {
+            let values = value.as_array().ok_or_else(mismatch)?;
+            if !values.is_empty() {
+                write_zigzag(out, values.len() as i64);
+                for item in values {
+                    write_value(items, item, out)?;
+                }
+            }
+            write_zigzag(out, 0);
+        }
+        AvroSchema::Map(values) => {
+            let entries = value.as_object().ok_or_else(mismatch)?;
+            if !entries.is_empty() {
+                write_zigzag(out, entries.len() as i64);
+                for (key, item) in entries {
+                    write_zigzag(out, key.len() as i64);
+                    out.extend_from_slice(key.as_bytes());
+                    write_value(values, item, out)?;
+                }
+            }
+            write_zigzag(out, 0);
+        }
+        AvroSchema::Union(branches) => {
+            let index = branches.iter().position(|branch| branch.accepts(value)).ok_or_else(mismatch)?;
+            write_zigzag(out, index as i64);
+            write_value(&branches[index], value, out)?;
+        }
+        AvroSchema::Fixed { .. } => return Err(mismatch()),
+    }
+    Ok(())
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if input.len() < len {
+        return Err("truncated Avro data".to_string());
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn read_len(input: &mut &[u8]) -> Result<usize, String> {
+    usize::try_from(read_zigzag(input)?).map_err(|_| "negative Avro length".to_string())
+}
+
+/// Items in the next array or map block; a negative count is followed by
+/// the block's size in bytes.
+fn read_block_count(input: &mut &[u8]) -> Result<usize, String> {
+    let count = read_zigzag(input)?;
+    if count < 0 {
+        read_zigzag(input)?;
+    }
+    Ok(count.unsigned_abs() as usize)
+}
+
+fn read_value(schema: &AvroSchema, input: &mut &[u8]) -> Result<Value, String> {
+    Ok(match schema {
+        AvroSchema::Null => Value::Null,
+        AvroSchema::Boolean => Value::Bool(take(input, 1)?[0] != 0),
+        AvroSchema::Int => Value::from(read_zigzag(input)?),
+        AvroSchema::Long { logical_type } => {
+            let number = read_zigzag(input)?;
+            let time = match logical_type.as_deref() {
+                Some("timestamp-millis") => DateTime::from_timestamp_millis(number),
+                Some("timestamp-micros") => DateTime::from_timestamp_micros(number),
+                _ => None,
+            };
+            time.map(|time| Value::String(time.to_rfc3339())).unwrap_or(Value::from(number))
+        }
+        AvroSchema::Float => Value::from(f32::from_le_bytes(take(input, 4)?.try_into().unwrap_or_default()) as f64),
+        AvroSchema::Double => Value::from(f64::from_le_bytes(take(input, 8)?.try_into().unwrap_or_default())),
+        AvroSchema::Bytes | AvroSchema::String => {
+            let len = read_len(input)?;
+            Value::String(String::from_utf8_lossy(take(input, len)?).into_owned())
+        }
+        AvroSchema::Decimal { scale } => {
+            let len = read_len(input)?;
+            let bytes = take(input, len)?;
+            if bytes.len() > 16 {
+                return Err(format!("decimal of {} bytes is too large", bytes.len()));
+            }
+            let sign = if bytes.first().is_some_and(|byte| byte & 0x80 != 0) { 0xff } else { 0x00 };
+            let mut unscaled = [sign; 16];
+            unscaled[16 - bytes.len()..].copy_from_slice(bytes);
+            let decimal = Decimal::try_from_i128_with_scale(i128::from_be_bytes(unscaled), *scale).map_err(|e| e.to_string())?;
+            Value::String(decimal.to_string())
+        }
+        AvroSchema::Record { fields, .. } => {
+            let mut object = Map::new();
+            for (name, field) in fields {
+                object.insert(name.clone(), read_value(field, input)?);
+            }
+            Value::Object(object)
+        }
+        AvroSchema::Enum { symbols, .. } => {
+            let index = read_len(input)?;
+            Value::String(symbols.get(index).ok_or_else(|| format!("enum index {} out of range", index))?.clone())
+        }
+        AvroSchema::Array(items) => {
+            let mut values = Vec::new();
+            loop {
+                let count = read_block_count(input)?;
+                if count == 0 {
+                    break;
+                }
+                for _ in 0..count {
+                    values.push(read_value(items, input)?);
+                }
+            }
+            Value::Array(values)
+        }
+        AvroSchema::Map(values) => {
+            let mut object = Map::new();
+            loop {
+                let count = read_block_count(input)?;
+                if count == 0 {
+                    break;
+                }
+                for _ in 0..count {
+                    let len = read_len(input)?;
+                    let key = String::from_utf8_lossy(take(input, len)?).into_owned();
+                    object.insert(key, read_value(values, input)?);
+                }
+            }
+            Value::Object(object)
+        }
+        AvroSchema::Union(branches) => {
+            let index = read_len(input)?;
+            let branch = branches.get(index).ok_or_else(|| format!("union index {} out of range", index))?;
+            read_value(branch, input)?
+        }
+        AvroSchema::Fixed { size, .. } => Value::String(hex::encode(take(input, *size)?)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{RsiData, RsiSignal, TradeData, TradeSide};
+    use chrono::TimeZone;
+
+    fn decimal_bytes(scale: u32, value: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_value(&AvroSchema::Decimal { scale }, &Value::String(value.to_string()), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn trades_round_trip() {
+        let schema = AvroSchema::parse(TRADE_AVRO).unwrap();
+        let mut trade = TradeData::new("AAPL".to_string(), "150.25".parse().unwrap(), 300, TradeSide::Sell, "NASDAQ".to_string());
+        trade.timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 14, 30, 0).unwrap() + chrono::TimeDelta::microseconds(123_456);
+
+        let decoded: TradeData = decode(&schema, &encode(&schema, &trade).unwrap()).unwrap();
+        assert_eq!((decoded.id, decoded.symbol, decoded.exchange), (trade.id, trade.symbol, trade.exchange));
+        assert_eq!((decoded.price, decoded.volume, decoded.side), (trade.price, trade.volume, trade.side));
+        assert_eq!(decoded.timestamp, trade.timestamp);
+    }
+
+    #[test]
+    fn rsi_values_round_trip() {
+        let schema = AvroSchema::parse(RSI_AVRO).unwrap();
+        let mut rsi = RsiData::new("TSLA".to_string(), 72.5, 14);
+        rsi.timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 14, 30, 0).unwrap();
+
+        let decoded: RsiData = decode(&schema, &encode(&schema, &rsi).unwrap()).unwrap();
+        assert_eq!((decoded.id, decoded.symbol), (rsi.id, rsi.symbol));
+        assert_eq!((decoded.rsi_value, decoded.period, decoded.signal), (72.5, 14, RsiSignal::Overbought));
+        assert_eq!(decoded.timestamp, rsi.timestamp);
+    }
+
+    #[test]
+    fn decimals_are_minimal_twos_complement_at_the_schema_scale() {
+        // Length prefix, then the unscaled value
+        assert_eq!(decimal_bytes(2, "1.27"), [2, 0x7f]);
+        assert_eq!(decimal_bytes(2, "1.28"), [4, 0x00, 0x80]);
+        assert_eq!(decimal_bytes(2, "-1.28"), [2, 0x80]);
+        assert_eq!(decimal_bytes(2, "-1.29"), [4, 0xff, 0x7f]);
+        assert_eq!(decimal_bytes(2, "0"), [2, 0x00]);
+        // Padded up to the scale
+        assert_eq!(decimal_bytes(2, "1"), [2, 0x64]);
+
+        for value in ["-0.00000001", "-98765.4321", "150.25", "79228162514.26433759"] {
+            let schema = AvroSchema::Decimal { scale: 8 };
+            let bytes = decimal_bytes(8, value);
+            let decoded = decode_value(&schema, &bytes).unwrap();
+            assert_eq!(decoded.as_str().unwrap().parse::<Decimal>().unwrap(), value.parse::<Decimal>().unwrap());
+        }
+
+        let mut out = Vec::new();
+        let too_precise = write_value(&AvroSchema::Decimal { scale: 2 }, &Value::String("1.005".to_string()), &mut out);
+        assert!(too_precise.is_err());
+    }
+
+    #[test]
+    fn unions_pick_the_branch_matching_the_value() {
+        let schema = AvroSchema::parse(r#"{"type": "record", "name": "Note", "fields": [{"name": "text", "type": ["null", "string"]}]}"#).unwrap();
+        for (value, bytes) in [(Value::Null, vec![0]), (Value::from("hi"), vec![2, 4, b'h', b'i'])] {
+            let record = serde_json::json!({ "text": value });
+            let mut out = Vec::new();
+            write_value(&schema, &record, &mut out).unwrap();
+            assert_eq!(out, bytes);
+            assert_eq!(decode_value(&schema, &out).unwrap(), record);
+        }
+        // A missing field is written as null
+        let mut out = Vec::new();
+        write_value(&schema, &serde_json::json!({}), &mut out).unwrap();
+        assert_eq!(out, [0]);
+        assert!(decode_value(&schema, &[4]).is_err());
+    }
+
+    #[test]
+    fn named_types_can_be_referenced_again() {
+        let schema = AvroSchema::parse(
+            r#"{"type": "record", "name": "Pair", "fields": [
+                {"name": "a", "type": {"type": "enum", "name": "Side", "symbols": ["Buy", "Sell"]}},
+                {"name": "b", "type": "Side"}
+            ]}"#,
+        )
+        .unwrap();
+        let record = serde_json::json!({ "a": "Sell", "b": "Buy" });
+        let mut out = Vec::new();
+        write_value(&schema, &record, &mut out).unwrap();
+        assert_eq!(out, [2, 0]);
+        assert_eq!(decode_value(&schema, &out).unwrap(), record);
+        assert!(AvroSchema::parse(r#"{"type": "record", "name": "R", "fields": [{"name": "x", "type": "Missing"}]}"#).is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let schema = AvroSchema::parse(TRADE_AVRO).unwrap();
+        let trade = TradeData::new("AAPL".to_string(), "150.25".parse().unwrap(), 300, TradeSide::Buy, "NASDAQ".to_string());
+        let bytes = encode(&schema, &trade).unwrap();
+        assert!(decode_value(&schema, &bytes[..bytes.len() - 3]).is_err());
+    }
+}
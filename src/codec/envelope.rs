@@ -0,0 +1,193 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+pub const SCHEMA_VERSION_HEADER: &str = "schema-version";
+pub const MESSAGE_TYPE_HEADER: &str = "message-type";
+pub const PRODUCER_ID_HEADER: &str = "producer-id";
+pub const SEQUENCE_HEADER: &str = "sequence";
+pub const PRODUCED_AT_HEADER: &str = "produced-at";
+
+/// Bare payloads written before envelopes existed.
+pub const LEGACY_SCHEMA_VERSION: u32 = 1;
+/// Version written by this build. Bump it together with a step in
+/// [`upgrade`] whenever a payload changes shape.
+pub const CURRENT_SCHEMA_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    Trade,
+    Rsi,
+    Quote,
+    Depth,
+}
+
+impl MessageType {
+    const ALL: [MessageType; 4] = [MessageType::Trade, MessageType::Rsi, MessageType::Quote, MessageType::Depth];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MessageType::Trade => "trade",
+            MessageType::Rsi => "rsi",
+            MessageType::Quote => "quote",
+            MessageType::Depth => "depth",
+        }
+    }
+}
+
+impl std::str::FromStr for MessageType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        MessageType::ALL
+            .into_iter()
+            .find(|message_type| message_type.as_str() == value)
+            .ok_or_else(|| format!("unknown message type '{}'", value))
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Metadata carried in Kafka headers next to every payload, so the payload
+/// itself keeps the shape registered for its wire format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    pub schema_version: u32,
+    pub message_type: MessageType,
+    pub producer_id: String,
+    /// Per producer and message type, starting at 1.
+    pub sequence: u64,
+    pub produced_at: DateTime<Utc>,
+}
+
+impl Envelope {
+    /// Stand-in for a payload without envelope headers. Legacy producers had
+    /// no id or sequence, so those are empty and zero.
+    pub fn legacy(message_type: MessageType, produced_at: DateTime<Utc>) -> Self {
+        Self {
+            schema_version: LEGACY_SCHEMA_VERSION,
+            message_type,
+            producer_id: String::new(),
+            sequence: 0,
+            produced_at,
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.schema_version == LEGACY_SCHEMA_VERSION
+    }
+
+    pub fn to_headers(&self) -> [(&'static str, String); 5] {
+        [
+            (SCHEMA_VERSION_HEADER, self.schema_version.to_string()),
+            (MESSAGE_TYPE_HEADER, self.message_type.to_string()),
+            (PRODUCER_ID_HEADER, self.producer_id.clone()),
+            (SEQUENCE_HEADER, self.sequence.to_string()),
+            (PRODUCED_AT_HEADER, self.produced_at.to_rfc3339()),
+        ]
+    }
+
+    /// Reads the envelope from record headers. `None` means the record has
+    /// no envelope at all and was written by a legacy producer; a partial or
+    /// unreadable envelope is an error.
+    pub fn from_headers(headers: &HashMap<String, String>) -> Result<Option<Self>, String> {
+        let Some(version) = headers.get(SCHEMA_VERSION_HEADER) else {
+            return Ok(None);
+        };
+        let header = |name: &str| headers.get(name).ok_or_else(|| format!("envelope without '{}' header", name));
+        Ok(Some(Self {
+            schema_version: version.parse().map_err(|_| format!("invalid schema version '{}'", version))?,
+            message_type: header(MESSAGE_TYPE_HEADER)?.parse()?,
+            producer_id: header(PRODUCER_ID_HEADER)?.clone(),
+            sequence: header(SEQUENCE_HEADER)?.parse().map_err(|e| format!("invalid sequence: {}", e))?,
+            produced_at: DateTime::parse_from_rfc3339(header(PRODUCED_AT_HEADER)?)
+                .map_err(|e| format!("invalid produced-at time: {}", e))?
+                .with_timezone(&Utc),
+        }))
+    }
+}
+
+/// Stamps envelopes for one producer, counting sequences per message type.
+#[derive(Clone)]
+pub struct EnvelopeStamper {
+    producer_id: Arc<str>,
+    sequences: Arc<[AtomicU64; 4]>,
+}
+
+impl EnvelopeStamper {
+    pub fn new(producer_id: &str) -> Self {
+        Self { producer_id: producer_id.into(), sequences: Arc::default() }
+    }
+
+    pub fn producer_id(&self) -> &str {
+        &self.producer_id
+    }
+
+    pub fn next(&self, message_type: MessageType) -> Envelope {
+        let sequence = self.sequences[message_type as usize].fetch_add(1, Ordering::Relaxed) + 1;
+        Envelope {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            message_type,
+            producer_id: self.producer_id.to_string(),
+            sequence,
+            produced_at: Utc::now(),
+        }
+    }
+}
+
+/// Rewrites a payload written at `version` into the current shape, one
+/// version at a time. Newer versions are rejected rather than guessed at.
+pub fn upgrade(message_type: MessageType, version: u32, mut body: Value) -> Result<Value, String> {
+    check_version(message_type, version)?;
+    for from in version..CURRENT_SCHEMA_VERSION {
+        body = upgrade_step(message_type, from, body)?;
+    }
+    Ok(body)
+}
+
+pub fn check_version(message_type: MessageType, version: u32) -> Result<(), String> {
+    if !(LEGACY_SCHEMA_VERSION..=CURRENT_SCHEMA_VERSION).contains(&version) {
+        return Err(format!(
+            "unsupported {} schema version {} (this build reads up to {})",
+            message_type, version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+    Ok(())
+}
+
+fn upgrade_step(message_type: MessageType, from: u32, mut body: Value) -> Result<Value, String> {
+    match (message_type, from) {
+        // Version 2 only added the envelope headers; bodies are unchanged
+        (_, 1) => {}
+        // Version 3 writes prices as decimal strings instead of numbers
+        (MessageType::Trade, 2) => price_to_string(body.get_mut("price")),
+        (MessageType::Quote, 2) => {
+            price_to_string(body.get_mut("bid_price"));
+            price_to_string(body.get_mut("ask_price"));
+        }
+        (MessageType::Depth, 2) => {
+            for side in ["bids", "asks"] {
+                if let Some(levels) = body.get_mut(side).and_then(Value::as_array_mut) {
+                    levels.iter_mut().for_each(|level| price_to_string(level.get_mut("price")));
+                }
+            }
+        }
+        (MessageType::Rsi, 2) => {}
+        _ => return Err(format!("no upgrade for {} schema version {}", message_type, from)),
+    }
+    Ok(body)
+}
+
+/// Uses the number's shortest round-trip text, so `123.45` stays `"123.45"`.
+fn price_to_string(price: Option<&mut Value>) {
+    if let Some(price) = price.filter(|price| price.is_number()) {
+        *price = Value::String(price.to_string());
+    }
+}
@@ -0,0 +1,10 @@
+pub mod wire;
+pub mod avro;
+pub mod protobuf;
+pub mod registry;
+pub mod records;
+pub mod envelope;
+
+pub use registry::*;
+pub use records::*;
+pub use envelope::*;
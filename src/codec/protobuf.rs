@@ -0,0 +1,232 @@
+use chrono::DateTime;
+use std::str::FromStr;
+
+use crate::models::{price_to_f64, Decimal, RsiData, RsiSignal, TradeData, TradeSide};
+
+/// Registered for trade topics; must stay in sync with [`TradeMessage`].
+pub const TRADE_PROTO: &str = r#"syntax = "proto3";
+
+package trading.v1;
+
+message TradeData {
+  enum TradeSide {
+    TRADE_SIDE_UNSPECIFIED = 0;
+    BUY = 1;
+    SELL = 2;
+  }
+
+  string id = 1;
+  string symbol = 2;
+  // Approximate; kept for readers that predate decimal_price
+  double price = 3 [deprecated = true];
+  uint64 volume = 4;
+  int64 timestamp_micros = 5;
+  TradeSide side = 6;
+  string exchange = 7;
+  // Exact price as a decimal string
+  string decimal_price = 8;
+}
+"#;
+
+/// Registered for RSI topics; must stay in sync with [`RsiMessage`].
+pub const RSI_PROTO: &str = r#"syntax = "proto3";
+
+package trading.v1;
+
+message RsiData {
+  enum RsiSignal {
+    RSI_SIGNAL_UNSPECIFIED = 0;
+    OVERBOUGHT = 1;
+    OVERSOLD = 2;
+    NEUTRAL = 3;
+  }
+
+  string id = 1;
+  string symbol = 2;
+  double rsi_value = 3;
+  int64 timestamp_micros = 4;
+  uint32 period = 5;
+  RsiSignal signal = 6;
+}
+"#;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TradeMessage {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub symbol: String,
+    #[prost(double, tag = "3")]
+    pub price: f64,
+    #[prost(uint64, tag = "4")]
+    pub volume: u64,
+    #[prost(int64, tag = "5")]
+    pub timestamp_micros: i64,
+    #[prost(enumeration = "ProtoTradeSide", tag = "6")]
+    pub side: i32,
+    #[prost(string, tag = "7")]
+    pub exchange: String,
+    #[prost(string, tag = "8")]
+    pub decimal_price: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ProtoTradeSide {
+    Unspecified = 0,
+    Buy = 1,
+    Sell = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RsiMessage {
+    #[prost(string, tag = "1")]
+    pub id: String,
+    #[prost(string, tag = "2")]
+    pub symbol: String,
+    #[prost(double, tag = "3")]
+    pub rsi_value: f64,
+    #[prost(int64, tag = "4")]
+    pub timestamp_micros: i64,
+    #[prost(uint32, tag = "5")]
+    pub period: u32,
+    #[prost(enumeration = "ProtoRsiSignal", tag = "6")]
+    pub signal: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ProtoRsiSignal {
+    Unspecified = 0,
+    Overbought = 1,
+    Oversold = 2,
+    Neutral = 3,
+}
+
+impl From<&TradeData> for TradeMessage {
+    fn from(trade: &TradeData) -> Self {
+        let side = match trade.side {
+            TradeSide::Buy => ProtoTradeSide::Buy,
+            TradeSide::Sell => ProtoTradeSide::Sell,
+        };
+        Self {
+            id: trade.id.clone(),
+            symbol: trade.symbol.clone(),
+            price: price_to_f64(trade.price),
+            volume: trade.volume,
+            timestamp_micros: trade.timestamp.timestamp_micros(),
+            side: side as i32,
+            exchange: trade.exchange.clone(),
+            decimal_price: trade.price.to_string(),
+        }
+    }
+}
+
+impl TryFrom<TradeMessage> for TradeData {
+    type Error = String;
+
+    fn try_from(message: TradeMessage) -> Result<Self, Self::Error> {
+        let side = match ProtoTradeSide::try_from(message.side) {
+            Ok(ProtoTradeSide::Buy) => TradeSide::Buy,
+            Ok(ProtoTradeSide::Sell) => TradeSide::Sell,
+            _ => return Err(format!("invalid trade side {}", message.side)),
+        };
+        // Messages from older writers only carry the double
+        let price = if message.decimal_price.is_empty() {
+            Decimal::from_str(&message.price.to_string())
+        } else {
+            Decimal::from_str(&message.decimal_price)
+        }
+        .map_err(|e| format!("invalid price: {}", e))?;
+        Ok(Self {
+            id: message.id,
+            symbol: message.symbol,
+            price,
+            volume: message.volume,
+            timestamp: DateTime::from_timestamp_micros(message.timestamp_micros).ok_or("timestamp out of range")?,
+            side,
+            exchange: message.exchange,
+            outlier: None,
+        })
+    }
+}
+
+impl From<&RsiData> for RsiMessage {
+    fn from(rsi: &RsiData) -> Self {
+        let signal = match rsi.signal {
+            RsiSignal::Overbought => ProtoRsiSignal::Overbought,
+            RsiSignal::Oversold => ProtoRsiSignal::Oversold,
+            RsiSignal::Neutral => ProtoRsiSignal::Neutral,
+        };
+        Self {
+            id: rsi.id.clone(),
+            symbol: rsi.symbol.clone(),
+            rsi_value: rsi.rsi_value,
+            timestamp_micros: rsi.timestamp.timestamp_micros(),
+            period: rsi.period,
+            signal: signal as i32,
+        }
+    }
+}
+
+impl TryFrom<RsiMessage> for RsiData {
+    type Error = String;
+
+    fn try_from(message: RsiMessage) -> Result<Self, Self::Error> {
+        let signal = match ProtoRsiSignal::try_from(message.signal) {
+            Ok(ProtoRsiSignal::Overbought) => RsiSignal::Overbought,
+            Ok(ProtoRsiSignal::Oversold) => RsiSignal::Oversold,
+            Ok(ProtoRsiSignal::Neutral) => RsiSignal::Neutral,
+            _ => return Err(format!("invalid RSI signal {}", message.signal)),
+        };
+        Ok(Self {
+            id: message.id,
+            symbol: message.symbol,
+            rsi_value: message.rsi_value,
+            timestamp: DateTime::from_timestamp_micros(message.timestamp_micros).ok_or("timestamp out of range")?,
+            period: message.period,
+            signal,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn trades_round_trip_with_an_exact_price() {
+        let trade = TradeData::new("AAPL".to_string(), "-0.00000001".parse().unwrap(), 300, TradeSide::Sell, "NASDAQ".to_string());
+        let bytes = TradeMessage::from(&trade).encode_to_vec();
+        let decoded = TradeData::try_from(TradeMessage::decode(&bytes[..]).unwrap()).unwrap();
+        assert_eq!((decoded.id, decoded.symbol, decoded.exchange), (trade.id, trade.symbol, trade.exchange));
+        assert_eq!((decoded.price, decoded.volume, decoded.side), (trade.price, trade.volume, trade.side));
+        assert_eq!(decoded.timestamp.timestamp_micros(), trade.timestamp.timestamp_micros());
+    }
+
+    #[test]
+    fn older_writers_without_a_decimal_price_fall_back_to_the_double() {
+        let trade = TradeData::new("AAPL".to_string(), "150.25".parse().unwrap(), 300, TradeSide::Buy, "NASDAQ".to_string());
+        let message = TradeMessage { decimal_price: String::new(), ..TradeMessage::from(&trade) };
+        assert_eq!(TradeData::try_from(message).unwrap().price, trade.price);
+    }
+
+    #[test]
+    fn rsi_values_round_trip() {
+        let rsi = RsiData::new("TSLA".to_string(), 25.0, 14);
+        let bytes = RsiMessage::from(&rsi).encode_to_vec();
+        let decoded = RsiData::try_from(RsiMessage::decode(&bytes[..]).unwrap()).unwrap();
+        assert_eq!((decoded.id, decoded.symbol, decoded.rsi_value), (rsi.id, rsi.symbol, 25.0));
+        assert_eq!((decoded.period, decoded.signal), (14, RsiSignal::Oversold));
+    }
+
+    #[test]
+    fn unspecified_enums_are_rejected() {
+        let trade = TradeData::new("AAPL".to_string(), "1".parse().unwrap(), 1, TradeSide::Buy, "NASDAQ".to_string());
+        let unspecified = TradeMessage { side: ProtoTradeSide::Unspecified as i32, ..TradeMessage::from(&trade) };
+        assert!(TradeData::try_from(unspecified).is_err());
+        let rsi = RsiMessage { signal: 9, ..RsiMessage::from(&RsiData::new("TSLA".to_string(), 50.0, 14)) };
+        assert!(RsiData::try_from(rsi).is_err());
+    }
+}
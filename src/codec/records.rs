@@ -0,0 +1,357 @@
+use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::codec::envelope::{check_version, upgrade, Envelope, MessageType};
+use crate::codec::avro::{self, AvroSchema, RSI_AVRO, TRADE_AVRO};
+use crate::codec::protobuf::{RsiMessage, TradeMessage, RSI_PROTO, TRADE_PROTO};
+use crate::codec::registry::{RegisteredSchema, SchemaRegistry, SchemaType};
+use crate::codec::wire::{frame, read_message_indexes, unframe};
+use crate::config::{SerializationConfig, WireFormat};
+use crate::models::{RsiData, TradeData};
+
+/// How long a schema the registry failed to return is not asked for again,
+/// so an unknown schema id costs one registry request rather than one per
+/// message.
+const SCHEMA_RETRY_AFTER: Duration = Duration::from_secs(10);
+
+/// A model that can travel in every wire format.
+pub trait WireRecord: Serialize + DeserializeOwned + Sized {
+    const MESSAGE_TYPE: MessageType;
+    const AVRO_SCHEMA: &'static str;
+    const PROTO_SCHEMA: &'static str;
+
+    fn to_protobuf(&self) -> Vec<u8>;
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, String>;
+}
+
+impl WireRecord for TradeData {
+    const MESSAGE_TYPE: MessageType = MessageType::Trade;
+    const AVRO_SCHEMA: &'static str = TRADE_AVRO;
+    const PROTO_SCHEMA: &'static str = TRADE_PROTO;
+
+    fn to_protobuf(&self) -> Vec<u8> {
+        TradeMessage::from(self).encode_to_vec()
+    }
+
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, String> {
+        TradeMessage::decode(bytes).map_err(|e| e.to_string())?.try_into()
+    }
+}
+
+impl WireRecord for RsiData {
+    const MESSAGE_TYPE: MessageType = MessageType::Rsi;
+    const AVRO_SCHEMA: &'static str = RSI_AVRO;
+    const PROTO_SCHEMA: &'static str = RSI_PROTO;
+
+    fn to_protobuf(&self) -> Vec<u8> {
+        RsiMessage::from(self).encode_to_vec()
+    }
+
+    fn from_protobuf(bytes: &[u8]) -> Result<Self, String> {
+        RsiMessage::decode(bytes).map_err(|e| e.to_string())?.try_into()
+    }
+}
+
+struct WriterSchema {
+    id: u32,
+    avro: Option<AvroSchema>,
+}
+
+/// Encodes records in the configured wire format. Schemas are registered or
+/// looked up once per subject (`<topic>-value`) and cached.
+#[derive(Clone)]
+pub struct RecordEncoder {
+    format: WireFormat,
+    registry: Option<SchemaRegistry>,
+    auto_register: bool,
+    subjects: Arc<Mutex<HashMap<String, Arc<WriterSchema>>>>,
+}
+
+impl Default for RecordEncoder {
+    /// Plain JSON, without a registry.
+    fn default() -> Self {
+        Self {
+            format: WireFormat::Json,
+            registry: None,
+            auto_register: false,
+            subjects: Arc::default(),
+        }
+    }
+}
+
+impl RecordEncoder {
+    pub fn new(config: &SerializationConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let registry = match &config.schema_registry_url {
+            Some(url) if config.format != WireFormat::Json => Some(SchemaRegistry::new(url)?),
+            _ => None,
+        };
+        Ok(Self {
+            format: config.format,
+            registry,
+            auto_register: config.auto_register,
+            subjects: Arc::default(),
+        })
+    }
+
+    pub fn format(&self) -> WireFormat {
+        self.format
+    }
+
+    pub async fn encode<T: WireRecord>(&self, topic: &str, record: &T) -> Result<Vec<u8>, String> {
+        match self.format {
+            WireFormat::Json => serde_json::to_vec(record).map_err(|e| e.to_string()),
+            WireFormat::Avro => {
+                let writer = self.writer_schema(topic, SchemaType::Avro, T::AVRO_SCHEMA).await?;
+                let schema = writer.avro.as_ref().ok_or("missing Avro schema")?;
+                Ok(frame(writer.id, None, &avro::encode(schema, record)?))
+            }
+            WireFormat::Protobuf => {
+                let writer = self.writer_schema(topic, SchemaType::Protobuf, T::PROTO_SCHEMA).await?;
+                Ok(frame(writer.id, Some(&[0]), &record.to_protobuf()))
+            }
+        }
+    }
+
+    async fn writer_schema(&self, topic: &str, schema_type: SchemaType, schema: &str) -> Result<Arc<WriterSchema>, String> {
+        let subject = format!("{}-value", topic);
+        if let Some(writer) = self.subjects.lock().unwrap().get(&subject) {
+            return Ok(writer.clone());
+        }
+
+        let registry = self.registry.as_ref().ok_or("no schema registry configured")?;
+        let registered = RegisteredSchema { schema_type, schema: schema.to_string() };
+        let id = if self.auto_register {
+            registry.register(&subject, &registered).await?
+        } else {
+            registry.lookup(&subject, &registered).await?
+        };
+        tracing::info!(%subject, id, %schema_type, "using registered schema");
+
+        let avro = match schema_type {
+            SchemaType::Avro => Some(AvroSchema::parse(schema)?),
+            _ => None,
+        };
+        let writer = Arc::new(WriterSchema { id, avro });
+        self.subjects.lock().unwrap().insert(subject, writer.clone());
+        Ok(writer)
+    }
+}
+
+enum ReaderSchema {
+    Avro(AvroSchema),
+    Protobuf,
+    Json,
+}
+
+/// Decodes records in any wire format. Payloads without the Confluent magic
+/// byte are read as JSON, so older producers keep working, and JSON and Avro
+/// bodies are upgraded from the schema version in their envelope.
+#[derive(Clone, Default)]
+pub struct RecordDecoder {
+    registry: Option<SchemaRegistry>,
+    schemas: Arc<Mutex<HashMap<u32, Arc<ReaderSchema>>>>,
+    /// Recent lookup failures per schema id, with when they happened.
+    failures: Arc<Mutex<HashMap<u32, (Instant, String)>>>,
+}
+
+impl RecordDecoder {
+    pub fn new(config: &SerializationConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let registry = match &config.schema_registry_url {
+            Some(url) => Some(SchemaRegistry::new(url)?),
+            None => None,
+        };
+        Ok(Self { registry, schemas: Arc::default(), failures: Arc::default() })
+    }
+
+    pub async fn decode<T: WireRecord>(&self, payload: &[u8], envelope: &Envelope) -> Result<T, DecodeError> {
+        if envelope.message_type != T::MESSAGE_TYPE {
+            return Err(DecodeError::Envelope(format!(
+                "expected a {} message, got {}",
+                T::MESSAGE_TYPE,
+                envelope.message_type
+            )));
+        }
+        check_version(envelope.message_type, envelope.schema_version).map_err(DecodeError::Envelope)?;
+        let value = match unframe(payload) {
+            None => serde_json::from_slice::<Value>(payload).map_err(|e| e.to_string()),
+            Some((id, mut body)) => match &*self.schema(id).await? {
+                ReaderSchema::Avro(schema) => avro::decode_value(schema, body),
+                // Protobuf evolves through field numbers and maps straight to the model
+                ReaderSchema::Protobuf => {
+                    let indexes = read_message_indexes(&mut body).map_err(DecodeError::Malformed)?;
+                    if indexes != [0] {
+                        return Err(DecodeError::Schema(format!("unsupported message index {:?} in schema {}", indexes, id)));
+                    }
+                    return T::from_protobuf(body).map_err(DecodeError::Malformed);
+                }
+                ReaderSchema::Json => serde_json::from_slice::<Value>(body).map_err(|e| e.to_string()),
+            },
+        }
+        .map_err(DecodeError::Malformed)?;
+        let value = upgrade(envelope.message_type, envelope.schema_version, value).map_err(DecodeError::Envelope)?;
+        serde_json::from_value(value).map_err(|e| DecodeError::Malformed(e.to_string()))
+    }
+
+    async fn schema(&self, id: u32) -> Result<Arc<ReaderSchema>, DecodeError> {
+        if let Some(schema) = self.schemas.lock().unwrap().get(&id) {
+            return Ok(schema.clone());
+        }
+
+        if let Some((_, reason)) = self.failures.lock().unwrap().get(&id).filter(|(at, _)| at.elapsed() < SCHEMA_RETRY_AFTER) {
+            return Err(DecodeError::Schema(reason.clone()));
+        }
+
+        let registry = self
+            .registry
+            .as_ref()
+            .ok_or_else(|| DecodeError::Schema(format!("schema {} referenced but no schema registry configured", id)))?;
+        let schema = match fetch_schema(registry, id).await {
+            Ok(schema) => Arc::new(schema),
+            Err(reason) => {
+                let mut failures = self.failures.lock().unwrap();
+                failures.retain(|_, (at, _)| at.elapsed() < SCHEMA_RETRY_AFTER);
+                failures.insert(id, (Instant::now(), reason.clone()));
+                return Err(DecodeError::Schema(reason));
+            }
+        };
+        self.failures.lock().unwrap().remove(&id);
+        self.schemas.lock().unwrap().insert(id, schema.clone());
+        Ok(schema)
+    }
+}
+
+async fn fetch_schema(registry: &SchemaRegistry, id: u32) -> Result<ReaderSchema, String> {
+    let registered = registry.schema(id).await?;
+    Ok(match registered.schema_type {
+        SchemaType::Avro => ReaderSchema::Avro(AvroSchema::parse(&registered.schema)?),
+        SchemaType::Protobuf => ReaderSchema::Protobuf,
+        SchemaType::Json => ReaderSchema::Json,
+    })
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The payload does not match its format or schema.
+    Malformed(String),
+    /// The writer schema could not be resolved.
+    Schema(String),
+    /// The envelope is unreadable, names another message type or a schema
+    /// version this build cannot upgrade.
+    Envelope(String),
+}
+
+impl DecodeError {
+    /// Label for the consumer's failure metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            DecodeError::Malformed(_) => "parse_error",
+            DecodeError::Schema(_) => "schema_error",
+            DecodeError::Envelope(_) => "envelope_error",
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Malformed(reason) | DecodeError::Schema(reason) | DecodeError::Envelope(reason) => f.write_str(reason),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::envelope::EnvelopeStamper;
+    use crate::codec::wire::MAGIC_BYTE;
+    use crate::models::{Decimal, TradeSide};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use warp::Filter;
+
+    const SCHEMA_ID: u32 = 42;
+
+    /// An encoder and decoder that already know schema `SCHEMA_ID`, as if
+    /// the registry had been asked once.
+    fn with_known_schema(format: WireFormat) -> (RecordEncoder, RecordDecoder) {
+        let encoder = RecordEncoder { format, ..RecordEncoder::default() };
+        let decoder = RecordDecoder::default();
+        let (avro, reader) = match format {
+            WireFormat::Avro => {
+                let schema = AvroSchema::parse(TRADE_AVRO).unwrap();
+                (Some(schema.clone()), ReaderSchema::Avro(schema))
+            }
+            WireFormat::Protobuf => (None, ReaderSchema::Protobuf),
+            WireFormat::Json => (None, ReaderSchema::Json),
+        };
+        encoder.subjects.lock().unwrap().insert("trades-value".to_string(), Arc::new(WriterSchema { id: SCHEMA_ID, avro }));
+        decoder.schemas.lock().unwrap().insert(SCHEMA_ID, Arc::new(reader));
+        (encoder, decoder)
+    }
+
+    fn trade() -> TradeData {
+        TradeData::new("AAPL".to_string(), Decimal::new(-15025, 3), 300, TradeSide::Sell, "NASDAQ".to_string())
+    }
+
+    #[tokio::test]
+    async fn registry_formats_are_framed_and_round_trip() {
+        let envelope = EnvelopeStamper::new("producer-1").next(MessageType::Trade);
+        for (format, indexes) in [(WireFormat::Avro, &[][..]), (WireFormat::Protobuf, &[0][..])] {
+            let (encoder, decoder) = with_known_schema(format);
+            let trade = trade();
+            let payload = encoder.encode("trades", &trade).await.unwrap();
+            assert_eq!(payload[..5], [MAGIC_BYTE, 0, 0, 0, SCHEMA_ID as u8]);
+            assert_eq!(payload[5..5 + indexes.len()], *indexes);
+
+            let decoded: TradeData = decoder.decode(&payload, &envelope).await.unwrap();
+            assert_eq!((decoded.id, decoded.price, decoded.side), (trade.id, trade.price, trade.side));
+        }
+    }
+
+    #[tokio::test]
+    async fn protobuf_messages_other_than_the_first_are_rejected() {
+        let (_, decoder) = with_known_schema(WireFormat::Protobuf);
+        let envelope = EnvelopeStamper::new("producer-1").next(MessageType::Trade);
+        let payload = frame(SCHEMA_ID, Some(&[1]), &trade().to_protobuf());
+        let result = decoder.decode::<TradeData>(&payload, &envelope).await;
+        assert!(matches!(result, Err(DecodeError::Schema(_))), "{:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn registry_failures_are_cached_briefly() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counted = requests.clone();
+        let missing = warp::any().map(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+            warp::http::StatusCode::NOT_FOUND
+        });
+        let (address, server) = warp::serve(missing).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let decoder = RecordDecoder::new(&SerializationConfig {
+            format: WireFormat::Json,
+            schema_registry_url: Some(format!("http://{}", address)),
+            auto_register: false,
+        })
+        .unwrap();
+        let envelope = EnvelopeStamper::new("producer-1").next(MessageType::Trade);
+        let payload = frame(7, None, b"{}");
+        for _ in 0..3 {
+            let result = decoder.decode::<TradeData>(&payload, &envelope).await;
+            assert!(matches!(result, Err(DecodeError::Schema(reason)) if reason.contains("schema 7 not found")));
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Once the failure is old enough the registry is asked again
+        decoder.failures.lock().unwrap().get_mut(&7).unwrap().0 -= SCHEMA_RETRY_AFTER;
+        assert!(decoder.decode::<TradeData>(&payload, &envelope).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
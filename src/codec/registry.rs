@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+const CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    Avro,
+    Protobuf,
+    Json,
+}
+
+impl fmt::Display for SchemaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SchemaType::Avro => "AVRO",
+            SchemaType::Protobuf => "PROTOBUF",
+            SchemaType::Json => "JSON",
+        })
+    }
+}
+
+/// A schema as stored in the registry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisteredSchema {
+    /// Omitted by the registry for Avro.
+    #[serde(default = "default_schema_type")]
+    pub schema_type: SchemaType,
+    pub schema: String,
+}
+
+fn default_schema_type() -> SchemaType {
+    SchemaType::Avro
+}
+
+#[derive(Deserialize)]
+struct SchemaId {
+    id: u32,
+}
+
+/// Client for a Confluent-compatible Schema Registry, such as the one
+/// Redpanda serves.
+#[derive(Clone)]
+pub struct SchemaRegistry {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl SchemaRegistry {
+    pub fn new(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Self { client, url: url.trim_end_matches('/').to_string() })
+    }
+
+    /// Registers `schema` under `subject`, or finds it if it already is, and
+    /// returns its id.
+    pub async fn register(&self, subject: &str, schema: &RegisteredSchema) -> Result<u32, String> {
+        self.post_schema(&format!("{}/subjects/{}/versions", self.url, subject), schema).await
+    }
+
+    /// Id of `schema` if it is already registered under `subject`.
+    pub async fn lookup(&self, subject: &str, schema: &RegisteredSchema) -> Result<u32, String> {
+        self.post_schema(&format!("{}/subjects/{}", self.url, subject), schema).await
+    }
+
+    async fn post_schema(&self, url: &str, schema: &RegisteredSchema) -> Result<u32, String> {
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE)
+            .body(serde_json::to_vec(schema).map_err(|e| e.to_string())?)
+            .send()
+            .await
+            .map_err(|e| format!("schema registry request failed: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("schema registry returned {}: {}", status, body.trim()));
+        }
+        let id: SchemaId = parse_response(response).await?;
+        Ok(id.id)
+    }
+
+    pub async fn schema(&self, id: u32) -> Result<RegisteredSchema, String> {
+        let response = self
+            .client
+            .get(format!("{}/schemas/ids/{}", self.url, id))
+            .header(reqwest::header::ACCEPT, CONTENT_TYPE)
+            .send()
+            .await
+            .map_err(|e| format!("schema registry request failed: {}", e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("schema {} not found in registry ({})", id, status));
+        }
+        parse_response(response).await
+    }
+}
+
+async fn parse_response<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T, String> {
+    let body = response.bytes().await.map_err(|e| format!("schema registry request failed: {}", e))?;
+    serde_json::from_slice(&body).map_err(|e| format!("invalid schema registry response: {}", e))
+}
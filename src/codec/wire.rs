@@ -0,0 +1,119 @@
+/// First byte of every Confluent-framed payload; JSON documents start with `{`.
+pub const MAGIC_BYTE: u8 = 0;
+
+/// Prefixes `body` with the magic byte and the big-endian schema id.
+/// Protobuf payloads also carry the index path of their message type.
+pub fn frame(schema_id: u32, message_indexes: Option<&[i64]>, body: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(body.len() + 6);
+    payload.push(MAGIC_BYTE);
+    payload.extend_from_slice(&schema_id.to_be_bytes());
+    match message_indexes {
+        // The first message of the schema is written as a single zero
+        Some([0]) => payload.push(0),
+        Some(indexes) => {
+            write_zigzag(&mut payload, indexes.len() as i64);
+            for index in indexes {
+                write_zigzag(&mut payload, *index);
+            }
+        }
+        None => {}
+    }
+    payload.extend_from_slice(body);
+    payload
+}
+
+/// Schema id and remaining bytes of a framed payload, or `None` when the
+/// payload is not framed.
+pub fn unframe(payload: &[u8]) -> Option<(u32, &[u8])> {
+    match payload {
+        [MAGIC_BYTE, a, b, c, d, body @ ..] => Some((u32::from_be_bytes([*a, *b, *c, *d]), body)),
+        _ => None,
+    }
+}
+
+/// Reads the Protobuf message index path that follows the schema id.
+pub fn read_message_indexes(body: &mut &[u8]) -> Result<Vec<i64>, String> {
+    let count = read_zigzag(body)?;
+    if count == 0 {
+        return Ok(vec![0]);
+    }
+    if count < 0 || count as usize > body.len() {
+        return Err(format!("invalid message index count {}", count));
+    }
+    (0..count).map(|_| read_zigzag(body)).collect()
+}
+
+/// Zig-zag varint, as used by Avro `int`/`long` and the message indexes.
+pub fn write_zigzag(out: &mut Vec<u8>, value: i64) {
+    let mut value = ((value << 1) ^ (value >> 63)) as u64;
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+pub fn read_zigzag(input: &mut &[u8]) -> Result<i64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = input.split_first().ok_or("truncated varint")?;
+        *input = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Err("varint is too long".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_carry_the_magic_byte_and_big_endian_schema_id() {
+        let payload = frame(0x0102_0304, None, b"body");
+        assert_eq!(payload, [0, 1, 2, 3, 4, b'b', b'o', b'd', b'y']);
+        assert_eq!(unframe(&payload), Some((0x0102_0304, &b"body"[..])));
+        // JSON, or too short to hold a schema id
+        assert_eq!(unframe(br#"{"id":1}"#), None);
+        assert_eq!(unframe(&[0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn message_indexes_follow_the_schema_id() {
+        // The first message is a single zero
+        let first = frame(7, Some(&[0]), b"x");
+        assert_eq!(first, [0, 0, 0, 0, 7, 0, b'x']);
+        let (_, mut body) = unframe(&first).unwrap();
+        assert_eq!(read_message_indexes(&mut body), Ok(vec![0]));
+        assert_eq!(body, b"x");
+
+        // Otherwise a count, then each index, all zig-zag encoded
+        let nested = frame(7, Some(&[1, 2]), b"x");
+        assert_eq!(&nested[5..], [4, 2, 4, b'x']);
+        let (_, mut body) = unframe(&nested).unwrap();
+        assert_eq!(read_message_indexes(&mut body), Ok(vec![1, 2]));
+        assert_eq!(body, b"x");
+
+        assert!(read_message_indexes(&mut &[1u8][..]).is_err());
+        assert!(read_message_indexes(&mut &[20u8, 2][..]).is_err());
+    }
+
+    #[test]
+    fn zigzag_round_trips_and_rejects_bad_input() {
+        let mut out = Vec::new();
+        for value in [0, -1, 1, -64, 64, i64::MIN, i64::MAX] {
+            out.clear();
+            write_zigzag(&mut out, value);
+            let mut input = &out[..];
+            assert_eq!(read_zigzag(&mut input), Ok(value));
+            assert!(input.is_empty());
+        }
+        out.clear();
+        write_zigzag(&mut out, -3);
+        assert_eq!(out, [5]);
+        assert!(read_zigzag(&mut &[0x80u8][..]).is_err());
+        assert!(read_zigzag(&mut &[0xffu8; 11][..]).is_err());
+    }
+}
@@ -0,0 +1,1548 @@
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use utoipa::ToSchema;
+
+use crate::config::{KafkaClientBuilder, KafkaConfigError, SecurityConfig};
+use crate::consumer::{HISTORY_CAPACITY, PRICE_CAPACITY};
+use crate::models::{Decimal, PriceSpec, PriceSpecs};
+
+/// Command line flags shared by the producer and consumer binaries.
+///
+/// Every flag can also be set through the listed environment variable.
+/// Precedence is CLI flag, then environment, then config file, then defaults.
+#[derive(Debug, Clone, Default, Parser)]
+#[command(about = "Trading data pipeline")]
+pub struct CliArgs {
+    /// Path to a TOML config file
+    #[arg(long, env = "CONFIG_FILE")]
+    pub config: Option<String>,
+
+    /// Comma-separated list of Kafka bootstrap servers
+    #[arg(long, env = "KAFKA_BROKERS")]
+    pub brokers: Option<String>,
+
+    #[arg(long, env = "KAFKA_TRADE_TOPIC")]
+    pub trade_topic: Option<String>,
+
+    #[arg(long, env = "KAFKA_RSI_TOPIC")]
+    pub rsi_topic: Option<String>,
+
+    #[arg(long, env = "KAFKA_QUOTE_TOPIC")]
+    pub quote_topic: Option<String>,
+
+    #[arg(long, env = "KAFKA_DEPTH_TOPIC")]
+    pub depth_topic: Option<String>,
+
+    /// Topic receiving trades rejected by validation; rejects are only counted when unset
+    #[arg(long, env = "KAFKA_REJECTS_TOPIC")]
+    pub rejects_topic: Option<String>,
+
+    #[arg(long, env = "KAFKA_SECURITY_PROTOCOL")]
+    pub security_protocol: Option<String>,
+
+    #[arg(long, env = "KAFKA_SASL_MECHANISM")]
+    pub sasl_mechanism: Option<String>,
+
+    #[arg(long, env = "KAFKA_SASL_USERNAME")]
+    pub sasl_username: Option<String>,
+
+    #[arg(long, env = "KAFKA_SASL_PASSWORD", hide_env_values = true)]
+    pub sasl_password: Option<String>,
+
+    /// File containing the SASL password
+    #[arg(long, env = "KAFKA_SASL_PASSWORD_FILE")]
+    pub sasl_password_file: Option<String>,
+
+    /// CA certificate used to verify the brokers
+    #[arg(long, env = "KAFKA_SSL_CA_LOCATION")]
+    pub ssl_ca_location: Option<String>,
+
+    /// Client certificate for mutual TLS
+    #[arg(long, env = "KAFKA_SSL_CERTIFICATE_LOCATION")]
+    pub ssl_certificate_location: Option<String>,
+
+    /// Private key for the client certificate
+    #[arg(long, env = "KAFKA_SSL_KEY_LOCATION")]
+    pub ssl_key_location: Option<String>,
+
+    #[arg(long, env = "KAFKA_SSL_KEY_PASSWORD", hide_env_values = true)]
+    pub ssl_key_password: Option<String>,
+
+    /// File containing the private key password
+    #[arg(long, env = "KAFKA_SSL_KEY_PASSWORD_FILE")]
+    pub ssl_key_password_file: Option<String>,
+
+    /// Extra librdkafka property, e.g. `-X linger.ms=10` (repeatable).
+    /// Environment variables named `KAFKA_CLIENT_<PROPERTY>` are passed through
+    /// too, with `_` mapped to `.`, e.g. `KAFKA_CLIENT_LINGER_MS=10`.
+    #[arg(short = 'X', long = "client-property", value_name = "KEY=VALUE")]
+    pub client_properties: Vec<String>,
+
+    /// Consumer group id
+    #[arg(long, env = "KAFKA_GROUP_ID")]
+    pub group_id: Option<String>,
+
+    /// Port for the consumer's HTTP API
+    #[arg(long, env = "PORT")]
+    pub port: Option<u16>,
+
+    /// Largest partition lag at which the consumer still reports ready
+    #[arg(long, env = "CONSUMER_READY_MAX_LAG")]
+    pub ready_max_lag: Option<i64>,
+
+    /// Seconds without a message after which the consumer reports not ready
+    #[arg(long, env = "CONSUMER_READY_MAX_IDLE_SECS")]
+    pub ready_max_idle_secs: Option<u64>,
+
+    /// Delay between generated trades in milliseconds
+    #[arg(long, env = "PRODUCER_INTERVAL_MS")]
+    pub interval_ms: Option<u64>,
+
+    /// How random data is generated: `random` (default) or `order-book`
+    #[arg(long, env = "PRODUCER_SIMULATION")]
+    pub simulation: Option<String>,
+
+    /// Maximum number of messages awaiting a delivery report
+    #[arg(long, env = "PRODUCER_MAX_IN_FLIGHT")]
+    pub max_in_flight: Option<u32>,
+
+    /// Port for the producer's metrics endpoint; 0 disables it
+    #[arg(long, env = "PRODUCER_METRICS_PORT")]
+    pub metrics_port: Option<u16>,
+
+    /// Address the producer's metrics endpoint listens on
+    #[arg(long, env = "PRODUCER_METRICS_ADDRESS")]
+    pub metrics_address: Option<IpAddr>,
+
+    /// Identifies this producer in message envelopes
+    #[arg(long, env = "PRODUCER_ID")]
+    pub producer_id: Option<String>,
+
+    /// Scenario file to play instead of random data
+    #[arg(long, env = "SCENARIO_FILE")]
+    pub scenario: Option<String>,
+
+    /// Webhook notified of RSI signal changes and fired alerts (repeatable)
+    #[arg(long = "webhook-url", env = "WEBHOOK_URLS", value_delimiter = ',')]
+    pub webhook_urls: Vec<String>,
+
+    /// Secret used to sign requests to `--webhook-url` endpoints
+    #[arg(long, env = "WEBHOOK_SECRET", hide_env_values = true)]
+    pub webhook_secret: Option<String>,
+
+    /// File keeping undelivered webhook notifications across restarts
+    #[arg(long, env = "WEBHOOK_OUTBOX")]
+    pub webhook_outbox: Option<String>,
+
+    /// TOML file with `[[keys]]` entries, added to the keys in `[api]`
+    #[arg(long, env = "API_KEYS_FILE")]
+    pub api_keys_file: Option<String>,
+
+    /// `required` (default) or `disabled` to serve the API without keys
+    #[arg(long, env = "API_AUTH")]
+    pub api_auth: Option<String>,
+
+    /// Origin allowed to call the API from a browser (repeatable; `*` for any)
+    #[arg(long = "cors-origin", env = "CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Vec<String>,
+
+    /// Sustained requests per second allowed per client and route
+    #[arg(long, env = "API_RATE_LIMIT_PER_SECOND")]
+    pub rate_limit_per_second: Option<f64>,
+
+    /// Requests a client may burst above the sustained rate
+    #[arg(long, env = "API_RATE_LIMIT_BURST")]
+    pub rate_limit_burst: Option<u32>,
+
+    /// SQLite database keeping trades, candles and RSI across restarts
+    #[arg(long, env = "STORAGE_PATH")]
+    pub storage_path: Option<String>,
+
+    /// Directory to archive trades and RSI to as Parquet files
+    #[arg(long, env = "ARCHIVE_PATH")]
+    pub archive_path: Option<String>,
+
+    /// Encoding of produced trades and RSI values: `json`, `avro` or `protobuf`
+    #[arg(long, env = "WIRE_FORMAT")]
+    pub wire_format: Option<String>,
+
+    /// Confluent-compatible Schema Registry, e.g. `http://localhost:18081`
+    #[arg(long, env = "SCHEMA_REGISTRY_URL")]
+    pub schema_registry_url: Option<String>,
+
+    /// Log filter in `tracing` env-filter syntax, e.g. `info,trading_system=debug`
+    #[arg(long, env = "RUST_LOG")]
+    pub log_filter: Option<String>,
+
+    /// Log output: `text` or `json`
+    #[arg(long, env = "LOG_FORMAT")]
+    pub log_format: Option<String>,
+
+    /// OTLP/gRPC collector to export spans to, e.g. `http://localhost:4317`
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+}
+
+/// Binary a configuration is loaded for; each only validates what it uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Producer,
+    Consumer,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    pub kafka: KafkaConfig,
+    pub topics: TopicsConfig,
+    pub producer: ProducerConfig,
+    pub consumer: ConsumerConfig,
+    pub webhooks: WebhooksConfig,
+    pub api: ApiConfig,
+    pub storage: StorageConfig,
+    pub archive: ArchiveConfig,
+    pub serialization: SerializationConfig,
+    /// Tick size and precision of each symbol's prices.
+    pub prices: PriceSpecs,
+    pub validation: ValidationConfig,
+    pub outliers: OutlierConfig,
+    pub logging: LoggingConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    pub brokers: String,
+    pub security: SecurityConfig,
+    /// Raw librdkafka properties applied after everything else.
+    pub client_overrides: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TopicsConfig {
+    pub trade: String,
+    pub rsi: String,
+    pub quote: String,
+    pub depth: String,
+    pub rejects: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProducerConfig {
+    pub interval_ms: u64,
+    pub simulation: Simulation,
+    pub max_in_flight: u32,
+    /// The metrics endpoint is not served when 0.
+    pub metrics_port: u16,
+    pub metrics_address: IpAddr,
+    pub scenario: Option<String>,
+    /// Stamped on every message envelope; random per process by default.
+    pub id: String,
+}
+
+/// Source of the producer's random market data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Simulation {
+    /// Independent random trades and RSI values.
+    Random,
+    /// Trades matched from simulated limit order books, plus quotes and depth.
+    /// Publishes no RSI values, leaving the consumer's RSI views empty.
+    OrderBook,
+}
+
+impl std::str::FromStr for Simulation {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "order-book" | "order_book" | "orderbook" => Ok(Simulation::OrderBook),
+            "random" => Ok(Simulation::Random),
+            _ => Err(format!("unknown simulation '{}', expected order-book or random", value)),
+        }
+    }
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Simulation::OrderBook => "order-book",
+            Simulation::Random => "random",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsumerConfig {
+    pub group_id: String,
+    pub api_port: u16,
+    /// Readiness thresholds for `/health/ready`.
+    pub ready_max_lag: i64,
+    pub ready_max_idle_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    /// History is kept in memory only when unset; requires the `sqlite` feature.
+    pub path: Option<String>,
+    /// Records written per transaction.
+    pub batch_size: usize,
+    /// Longest a record waits for its batch to fill up.
+    pub flush_interval_ms: u64,
+    pub retention: RetentionConfig,
+}
+
+/// Days each kind of record is kept; 0 keeps it forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub trades_days: u32,
+    pub candles_days: u32,
+    pub rsi_days: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self { trades_days: 7, candles_days: 90, rsi_days: 30 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    /// Nothing is archived when unset; requires the `parquet` feature.
+    pub path: Option<String>,
+    /// A file is finalised once it reaches this size...
+    pub max_file_bytes: u64,
+    /// ...or has been open this long.
+    pub max_file_age_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SerializationConfig {
+    /// How the producer encodes trades and RSI values; the consumer accepts
+    /// every format, as well as JSON from older producers.
+    pub format: WireFormat,
+    /// Required for Avro and Protobuf, which carry a registry schema id.
+    pub schema_registry_url: Option<String>,
+    /// Register missing schemas instead of requiring them to exist.
+    pub auto_register: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Bare JSON documents.
+    Json,
+    /// Avro binary in the Confluent wire format.
+    Avro,
+    /// Protobuf in the Confluent wire format.
+    Protobuf,
+}
+
+impl std::str::FromStr for WireFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "json" => Ok(WireFormat::Json),
+            "avro" => Ok(WireFormat::Avro),
+            "protobuf" | "proto" => Ok(WireFormat::Protobuf),
+            _ => Err(format!("unknown wire format '{}', expected json, avro or protobuf", value)),
+        }
+    }
+}
+
+impl fmt::Display for WireFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WireFormat::Json => "json",
+            WireFormat::Avro => "avro",
+            WireFormat::Protobuf => "protobuf",
+        })
+    }
+}
+
+/// Rules every trade must pass before it is produced, and again before it is
+/// processed. Unset limits are not checked.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationConfig {
+    pub enabled: bool,
+    /// Inclusive band every price must fall in.
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+    /// Largest move from the symbol's last accepted price, in percent. A run
+    /// of consistent trades beyond it moves the reference to the new level.
+    pub max_deviation_pct: Option<f64>,
+    pub max_volume: Option<u64>,
+    /// Accepted symbols and exchanges; empty accepts any.
+    pub symbols: BTreeSet<String>,
+    pub exchanges: BTreeSet<String>,
+    /// How far a trade may be stamped ahead of this host's clock...
+    pub max_future_skew_ms: u64,
+    /// ...or behind it. Unset accepts trades of any age, e.g. when a consumer
+    /// replays a topic from the start.
+    pub max_age_secs: Option<u64>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_price: None,
+            max_price: None,
+            max_deviation_pct: None,
+            max_volume: None,
+            symbols: BTreeSet::new(),
+            exchanges: BTreeSet::new(),
+            max_future_skew_ms: 5000,
+            max_age_secs: None,
+        }
+    }
+}
+
+/// Consumer-side filter holding statistically unlikely prices out of candles,
+/// RSI and alerts until later trades confirm them.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutlierConfig {
+    pub enabled: bool,
+    pub method: OutlierMethod,
+    /// Modified z-score against recent prices above which a price is suspect (`mad`).
+    pub mad_threshold: f64,
+    /// Distance from the rolling VWAP above which a price is suspect, in percent (`vwap`).
+    pub vwap_deviation_pct: f64,
+    /// Accepted trades the statistics are taken over, at most the 100 prices
+    /// kept for RSI with `mad` or the 1000 recent trades with `vwap`...
+    pub window: usize,
+    /// ...and how many are needed before anything is flagged.
+    pub min_samples: usize,
+    /// Subsequent trades near a suspect price that confirm the move.
+    pub confirm_after: usize,
+}
+
+impl Default for OutlierConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            method: OutlierMethod::Mad,
+            mad_threshold: 10.0,
+            vwap_deviation_pct: 5.0,
+            window: 50,
+            min_samples: 20,
+            confirm_after: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierMethod {
+    /// Median absolute deviation of recent prices.
+    Mad,
+    /// Percent from the volume-weighted average price of recent trades.
+    Vwap,
+}
+
+impl fmt::Display for OutlierMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutlierMethod::Mad => "mad",
+            OutlierMethod::Vwap => "vwap",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoggingConfig {
+    pub filter: String,
+    pub format: LogFormat,
+    /// Spans are exported over OTLP when set; requires the `otlp` feature.
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line, including the fields of the active spans.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "text" | "pretty" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format '{}', expected text or json", value)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiConfig {
+    pub auth: AuthMode,
+    /// Accepted keys; at least one is needed unless auth is disabled.
+    pub keys: Vec<ApiKey>,
+    /// Browser origins allowed by CORS; `*` allows any.
+    pub cors_origins: Vec<String>,
+    pub rate_limit: RateLimitConfig,
+}
+
+/// Whether API requests must present a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    /// Data endpoints need a configured key.
+    Required,
+    /// The API is open to anyone who can reach it; must be chosen explicitly.
+    Disabled,
+}
+
+impl std::str::FromStr for AuthMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "required" => Ok(AuthMode::Required),
+            "disabled" => Ok(AuthMode::Disabled),
+            _ => Err(format!("unknown API auth mode '{}', expected required or disabled", value)),
+        }
+    }
+}
+
+impl fmt::Display for AuthMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AuthMode::Required => "required",
+            AuthMode::Disabled => "disabled",
+        })
+    }
+}
+
+/// Token buckets per client (API key, or IP without one) and route.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub default: RateLimit,
+    /// Overrides keyed by route pattern, e.g. `/api/v1/symbols/{symbol}/trades`.
+    pub routes: BTreeMap<String, RateLimit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default: RateLimit { requests_per_second: 20.0, burst: 40 },
+            routes: BTreeMap::new(),
+        }
+    }
+}
+
+/// An API key, stored only as the hex SHA-256 of the key itself.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKey {
+    pub name: String,
+    /// e.g. `printf %s "$KEY" | sha256sum`
+    pub sha256: String,
+    #[serde(default = "ApiScope::read_only")]
+    pub scopes: Vec<ApiScope>,
+}
+
+/// What an API key may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Market data, history, fired alerts and streams.
+    Read,
+    /// Everything `read` allows plus alert rule management and webhook status.
+    Admin,
+}
+
+impl ApiScope {
+    pub fn read_only() -> Vec<ApiScope> {
+        vec![ApiScope::Read]
+    }
+
+    /// Whether a key with this scope may use endpoints requiring `required`.
+    pub fn grants(self, required: ApiScope) -> bool {
+        self == ApiScope::Admin || self == required
+    }
+}
+
+impl fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ApiScope::Read => "read",
+            ApiScope::Admin => "admin",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhooksConfig {
+    pub endpoints: Vec<WebhookEndpoint>,
+    pub outbox_path: String,
+    /// Attempts per notification before it is given up on.
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookEndpoint {
+    /// Identifies the endpoint in status output; defaults to the URL.
+    #[serde(default)]
+    pub name: String,
+    pub url: String,
+    /// HMAC-SHA256 key for the `X-Webhook-Signature` header.
+    pub secret: Option<String>,
+    pub secret_file: Option<String>,
+    #[serde(default = "WebhookEvent::all")]
+    pub events: Vec<WebhookEvent>,
+}
+
+/// Notifications a webhook can receive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// An RSI signal changed, e.g. from Neutral to Overbought.
+    Signals,
+    /// An alert rule fired.
+    Alerts,
+}
+
+impl WebhookEvent {
+    pub fn all() -> Vec<WebhookEvent> {
+        vec![WebhookEvent::Signals, WebhookEvent::Alerts]
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            WebhookEvent::Signals => "signals",
+            WebhookEvent::Alerts => "alerts",
+        })
+    }
+}
+
+impl WebhookEndpoint {
+    /// The signing secret, read from `secret_file` if set.
+    pub fn secret(&self) -> Result<Option<String>, String> {
+        match (&self.secret, &self.secret_file) {
+            (Some(_), Some(_)) => Err(format!("webhook '{}' sets both secret and secret_file", self.name)),
+            (Some(secret), None) => Ok(Some(secret.clone())),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map(|secret| Some(secret.trim().to_string()))
+                .map_err(|e| format!("failed to read webhook secret file '{}': {}", path, e)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// On-disk layout of the config file; every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    #[serde(default)]
+    kafka: FileKafkaConfig,
+    #[serde(default)]
+    topics: FileTopicsConfig,
+    #[serde(default)]
+    producer: FileProducerConfig,
+    #[serde(default)]
+    consumer: FileConsumerConfig,
+    #[serde(default)]
+    webhooks: FileWebhooksConfig,
+    #[serde(default)]
+    api: FileApiConfig,
+    #[serde(default)]
+    storage: FileStorageConfig,
+    #[serde(default)]
+    archive: FileArchiveConfig,
+    #[serde(default)]
+    serialization: FileSerializationConfig,
+    #[serde(default)]
+    prices: FilePricesConfig,
+    #[serde(default)]
+    validation: ValidationConfig,
+    #[serde(default)]
+    outliers: OutlierConfig,
+    #[serde(default)]
+    logging: FileLoggingConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileKafkaConfig {
+    brokers: Option<String>,
+    #[serde(default)]
+    security: SecurityConfig,
+    #[serde(default)]
+    client: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileTopicsConfig {
+    trade: Option<String>,
+    rsi: Option<String>,
+    quote: Option<String>,
+    depth: Option<String>,
+    rejects: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProducerConfig {
+    interval_ms: Option<u64>,
+    simulation: Option<String>,
+    max_in_flight: Option<u32>,
+    metrics_port: Option<u16>,
+    metrics_address: Option<IpAddr>,
+    scenario: Option<String>,
+    id: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConsumerConfig {
+    group_id: Option<String>,
+    api_port: Option<u16>,
+    ready_max_lag: Option<i64>,
+    ready_max_idle_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileWebhooksConfig {
+    #[serde(default)]
+    endpoints: Vec<WebhookEndpoint>,
+    outbox_path: Option<String>,
+    max_attempts: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    max_backoff_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileApiConfig {
+    auth: Option<String>,
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+    keys_file: Option<String>,
+    cors_origins: Option<Vec<String>>,
+    #[serde(default)]
+    rate_limit: FileRateLimitConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRateLimitConfig {
+    enabled: Option<bool>,
+    requests_per_second: Option<f64>,
+    burst: Option<u32>,
+    #[serde(default)]
+    routes: BTreeMap<String, RateLimit>,
+}
+
+/// Layout of `api.keys_file`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ApiKeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileStorageConfig {
+    path: Option<String>,
+    batch_size: Option<usize>,
+    flush_interval_ms: Option<u64>,
+    #[serde(default)]
+    retention: RetentionConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileArchiveConfig {
+    path: Option<String>,
+    max_file_bytes: Option<u64>,
+    max_file_age_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileSerializationConfig {
+    format: Option<String>,
+    schema_registry_url: Option<String>,
+    auto_register: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilePricesConfig {
+    tick_size: Option<Decimal>,
+    precision: Option<u32>,
+    #[serde(default)]
+    symbols: BTreeMap<String, FilePriceSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilePriceSpec {
+    tick_size: Decimal,
+    precision: Option<u32>,
+}
+
+impl FilePricesConfig {
+    /// Precision defaults to the decimal places of the tick size.
+    fn into_specs(self) -> Result<PriceSpecs, String> {
+        let spec = |tick_size: Decimal, precision: Option<u32>| {
+            PriceSpec::new(tick_size, precision.unwrap_or_else(|| tick_size.normalize().scale()))
+        };
+        let default = match self.tick_size {
+            Some(tick_size) => spec(tick_size, self.precision)?,
+            None if self.precision.is_some() => return Err("prices.precision needs prices.tick_size".to_string()),
+            None => PriceSpec::default(),
+        };
+        let symbols = self
+            .symbols
+            .into_iter()
+            .map(|(symbol, file)| Ok((symbol.clone(), spec(file.tick_size, file.precision).map_err(|e| format!("prices.symbols.{}: {}", symbol, e))?)))
+            .collect::<Result<_, String>>()?;
+        Ok(PriceSpecs { default, symbols })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileLoggingConfig {
+    filter: Option<String>,
+    format: Option<String>,
+    otlp_endpoint: Option<String>,
+}
+
+impl AppConfig {
+    /// Parses the process arguments and environment, then loads the config file if any.
+    pub fn from_env(component: Component) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load(CliArgs::parse(), component)
+    }
+
+    /// Loads the configuration and validates the shared sections plus those
+    /// `component` uses.
+    pub fn load(args: CliArgs, component: Component) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = match &args.config {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read config file '{}': {}", path, e))?;
+                toml::from_str(&contents)
+                    .map_err(|e| format!("invalid config file '{}': {}", path, e))?
+            }
+            None => FileConfig::default(),
+        };
+
+        let mut client_overrides = file.kafka.client;
+        for (name, value) in std::env::vars() {
+            if let Some(property) = name.strip_prefix("KAFKA_CLIENT_") {
+                client_overrides.insert(property.to_lowercase().replace('_', "."), value);
+            }
+        }
+        for property in &args.client_properties {
+            let (key, value) = property
+                .split_once('=')
+                .ok_or_else(|| format!("client property '{}' must be KEY=VALUE", property))?;
+            client_overrides.insert(key.trim().to_string(), value.trim().to_string());
+        }
+
+        let mut api_keys = file.api.keys;
+        if let Some(path) = args.api_keys_file.as_ref().or(file.api.keys_file.as_ref()) {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("failed to read API keys file '{}': {}", path, e))?;
+            let keys_file: ApiKeysFile = toml::from_str(&contents)
+                .map_err(|e| format!("invalid API keys file '{}': {}", path, e))?;
+            api_keys.extend(keys_file.keys);
+        }
+        for key in &mut api_keys {
+            key.sha256 = key.sha256.trim().to_lowercase();
+        }
+        let cors_origins = if args.cors_origins.is_empty() {
+            file.api.cors_origins.unwrap_or_else(|| vec!["http://localhost:3000".to_string()])
+        } else {
+            args.cors_origins
+        };
+
+        let mut endpoints = file.webhooks.endpoints;
+        endpoints.extend(args.webhook_urls.iter().map(|url| WebhookEndpoint {
+            name: String::new(),
+            url: url.trim().to_string(),
+            secret: args.webhook_secret.clone(),
+            secret_file: None,
+            events: WebhookEvent::all(),
+        }));
+        for endpoint in &mut endpoints {
+            if endpoint.name.is_empty() {
+                endpoint.name = endpoint.url.clone();
+            }
+        }
+
+        let file_security = file.kafka.security;
+        let config = Self {
+            kafka: KafkaConfig {
+                brokers: args.brokers
+                    .or(file.kafka.brokers)
+                    .unwrap_or_else(|| "localhost:19092".to_string()),
+                security: SecurityConfig {
+                    protocol: args.security_protocol.or(file_security.protocol),
+                    sasl_mechanism: args.sasl_mechanism.or(file_security.sasl_mechanism),
+                    sasl_username: args.sasl_username.or(file_security.sasl_username),
+                    sasl_password: args.sasl_password.or(file_security.sasl_password),
+                    sasl_password_file: args.sasl_password_file.or(file_security.sasl_password_file),
+                    ssl_ca_location: args.ssl_ca_location.or(file_security.ssl_ca_location),
+                    ssl_certificate_location: args.ssl_certificate_location
+                        .or(file_security.ssl_certificate_location),
+                    ssl_key_location: args.ssl_key_location.or(file_security.ssl_key_location),
+                    ssl_key_password: args.ssl_key_password.or(file_security.ssl_key_password),
+                    ssl_key_password_file: args.ssl_key_password_file
+                        .or(file_security.ssl_key_password_file),
+                },
+                client_overrides,
+            },
+            topics: TopicsConfig {
+                trade: args.trade_topic
+                    .or(file.topics.trade)
+                    .unwrap_or_else(|| "trade-data".to_string()),
+                rsi: args.rsi_topic
+                    .or(file.topics.rsi)
+                    .unwrap_or_else(|| "rsi-data".to_string()),
+                quote: args.quote_topic
+                    .or(file.topics.quote)
+                    .unwrap_or_else(|| "quote-data".to_string()),
+                depth: args.depth_topic
+                    .or(file.topics.depth)
+                    .unwrap_or_else(|| "depth-data".to_string()),
+                rejects: args.rejects_topic.or(file.topics.rejects),
+            },
+            producer: ProducerConfig {
+                interval_ms: args.interval_ms.or(file.producer.interval_ms).unwrap_or(500),
+                simulation: match args.simulation.or(file.producer.simulation) {
+                    Some(simulation) => simulation.parse()?,
+                    None => Simulation::Random,
+                },
+                max_in_flight: args.max_in_flight.or(file.producer.max_in_flight).unwrap_or(1000),
+                metrics_port: args.metrics_port.or(file.producer.metrics_port).unwrap_or(9091),
+                metrics_address: args.metrics_address
+                    .or(file.producer.metrics_address)
+                    .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                scenario: args.scenario.or(file.producer.scenario),
+                id: args.producer_id
+                    .or(file.producer.id)
+                    .unwrap_or_else(|| format!("producer-{}", uuid::Uuid::new_v4().simple())),
+            },
+            consumer: ConsumerConfig {
+                group_id: args.group_id
+                    .or(file.consumer.group_id)
+                    .unwrap_or_else(|| "trading-consumer-group".to_string()),
+                api_port: args.port.or(file.consumer.api_port).unwrap_or(3001),
+                ready_max_lag: args.ready_max_lag.or(file.consumer.ready_max_lag).unwrap_or(1000),
+                ready_max_idle_secs: args.ready_max_idle_secs
+                    .or(file.consumer.ready_max_idle_secs)
+                    .unwrap_or(60),
+            },
+            webhooks: WebhooksConfig {
+                endpoints,
+                outbox_path: args.webhook_outbox
+                    .or(file.webhooks.outbox_path)
+                    .unwrap_or_else(|| "webhook-outbox.json".to_string()),
+                max_attempts: file.webhooks.max_attempts.unwrap_or(8),
+                initial_backoff_ms: file.webhooks.initial_backoff_ms.unwrap_or(500),
+                max_backoff_ms: file.webhooks.max_backoff_ms.unwrap_or(60_000),
+                timeout_ms: file.webhooks.timeout_ms.unwrap_or(5000),
+            },
+            api: ApiConfig {
+                auth: match args.api_auth.or(file.api.auth) {
+                    Some(auth) => auth.parse()?,
+                    None => AuthMode::Required,
+                },
+                keys: api_keys,
+                cors_origins: cors_origins.iter().map(|origin| origin.trim().to_string()).collect(),
+                rate_limit: RateLimitConfig {
+                    enabled: file.api.rate_limit.enabled.unwrap_or(true),
+                    default: RateLimit {
+                        requests_per_second: args.rate_limit_per_second
+                            .or(file.api.rate_limit.requests_per_second)
+                            .unwrap_or(20.0),
+                        burst: args.rate_limit_burst.or(file.api.rate_limit.burst).unwrap_or(40),
+                    },
+                    routes: file.api.rate_limit.routes,
+                },
+            },
+            storage: StorageConfig {
+                path: args.storage_path.or(file.storage.path),
+                batch_size: file.storage.batch_size.unwrap_or(500),
+                flush_interval_ms: file.storage.flush_interval_ms.unwrap_or(1000),
+                retention: file.storage.retention,
+            },
+            archive: ArchiveConfig {
+                path: args.archive_path.or(file.archive.path),
+                max_file_bytes: file.archive.max_file_bytes.unwrap_or(128 * 1024 * 1024),
+                max_file_age_secs: file.archive.max_file_age_secs.unwrap_or(300),
+            },
+            serialization: SerializationConfig {
+                format: match args.wire_format.or(file.serialization.format) {
+                    Some(format) => format.parse()?,
+                    None => WireFormat::Json,
+                },
+                schema_registry_url: args.schema_registry_url
+                    .or(file.serialization.schema_registry_url)
+                    .map(|url| url.trim().trim_end_matches('/').to_string()),
+                auto_register: file.serialization.auto_register.unwrap_or(true),
+            },
+            prices: file.prices.into_specs()?,
+            validation: file.validation,
+            outliers: file.outliers,
+            logging: LoggingConfig {
+                filter: args.log_filter
+                    .or(file.logging.filter)
+                    .unwrap_or_else(|| "info".to_string()),
+                format: match args.log_format.or(file.logging.format) {
+                    Some(format) => format.parse()?,
+                    None => LogFormat::Text,
+                },
+                otlp_endpoint: args.otlp_endpoint.or(file.logging.otlp_endpoint),
+            },
+        };
+
+        config.validate(component)?;
+        Ok(config)
+    }
+
+    /// Symbols named in `[validation]` or `[prices.symbols]`.
+    pub fn known_symbols(&self) -> BTreeSet<String> {
+        self.validation.symbols.iter().chain(self.prices.symbols.keys()).cloned().collect()
+    }
+
+    pub fn validate(&self, component: Component) -> Result<(), Box<dyn std::error::Error>> {
+        self.kafka.validate()?;
+        validate_topic("trade", &self.topics.trade)?;
+        validate_topic("rsi", &self.topics.rsi)?;
+        validate_topic("quote", &self.topics.quote)?;
+        validate_topic("depth", &self.topics.depth)?;
+        if let Some(rejects) = &self.topics.rejects {
+            validate_topic("rejects", rejects)?;
+            if *rejects == self.topics.trade {
+                return Err("the rejects topic must differ from the trade topic".into());
+            }
+        }
+        self.serialization.validate()?;
+        self.validation.validate()?;
+        if self.logging.otlp_endpoint.is_some() && !cfg!(feature = "otlp") {
+            return Err("an OTLP endpoint is configured but this build lacks the `otlp` feature".into());
+        }
+        match component {
+            Component::Producer => self.validate_producer(),
+            Component::Consumer => self.validate_consumer(),
+        }
+    }
+
+    fn validate_producer(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.producer.interval_ms == 0 {
+            return Err("producer interval must be greater than zero".into());
+        }
+        if self.producer.max_in_flight == 0 {
+            return Err("producer max in-flight must be greater than zero".into());
+        }
+        if self.producer.id.trim().is_empty() {
+            return Err("producer id must not be empty".into());
+        }
+        Ok(())
+    }
+
+    fn validate_consumer(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.consumer.group_id.trim().is_empty() {
+            return Err("consumer group id must not be empty".into());
+        }
+        if self.consumer.api_port == 0 {
+            return Err("API port must not be 0".into());
+        }
+        if self.consumer.ready_max_lag < 0 || self.consumer.ready_max_idle_secs == 0 {
+            return Err("readiness thresholds must be positive".into());
+        }
+        self.webhooks.validate()?;
+        self.api.validate()?;
+        if self.storage.path.is_some() && !cfg!(feature = "sqlite") {
+            return Err("a storage path is configured but this build lacks the `sqlite` feature".into());
+        }
+        if self.storage.batch_size == 0 || self.storage.flush_interval_ms == 0 {
+            return Err("storage batch size and flush interval must be greater than zero".into());
+        }
+        if self.archive.path.is_some() && !cfg!(feature = "parquet") {
+            return Err("an archive path is configured but this build lacks the `parquet` feature".into());
+        }
+        if self.archive.max_file_bytes == 0 || self.archive.max_file_age_secs == 0 {
+            return Err("archive file size and age limits must be greater than zero".into());
+        }
+        self.outliers.validate()?;
+        Ok(())
+    }
+}
+
+impl WebhooksConfig {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut names = std::collections::BTreeSet::new();
+        for endpoint in &self.endpoints {
+            if !(endpoint.url.starts_with("http://") || endpoint.url.starts_with("https://")) {
+                return Err(format!("invalid webhook URL '{}', expected http(s)://", endpoint.url).into());
+            }
+            if !names.insert(endpoint.name.as_str()) {
+                return Err(format!("duplicate webhook name '{}'", endpoint.name).into());
+            }
+            if endpoint.events.is_empty() {
+                return Err(format!("webhook '{}' has no events", endpoint.name).into());
+            }
+            endpoint.secret()?;
+        }
+        if self.max_attempts == 0 {
+            return Err("webhook max attempts must be greater than zero".into());
+        }
+        if self.initial_backoff_ms == 0 || self.max_backoff_ms < self.initial_backoff_ms {
+            return Err("webhook backoff must be positive and max_backoff_ms at least initial_backoff_ms".into());
+        }
+        if self.timeout_ms == 0 {
+            return Err("webhook timeout must be greater than zero".into());
+        }
+        Ok(())
+    }
+}
+
+impl SerializationConfig {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.schema_registry_url {
+            Some(url) if !(url.starts_with("http://") || url.starts_with("https://")) => {
+                Err(format!("invalid schema registry URL '{}', expected http(s)://", url).into())
+            }
+            None if self.format != WireFormat::Json => {
+                Err(format!("the {} wire format needs a schema registry URL", self.format).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ValidationConfig {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.min_price.is_some_and(|min| min < Decimal::ZERO) {
+            return Err("validation min_price must not be negative".into());
+        }
+        if let (Some(min), Some(max)) = (self.min_price, self.max_price) {
+            if max < min {
+                return Err(format!("validation max_price {} is below min_price {}", max, min).into());
+            }
+        }
+        if self.max_deviation_pct.is_some_and(|pct| !(pct.is_finite() && pct > 0.0)) {
+            return Err("validation max_deviation_pct must be positive".into());
+        }
+        if self.max_volume == Some(0) || self.max_age_secs == Some(0) {
+            return Err("validation max_volume and max_age_secs must be greater than zero".into());
+        }
+        if self.symbols.iter().chain(&self.exchanges).any(|name| name.trim().is_empty()) {
+            return Err("validation symbols and exchanges must not be empty".into());
+        }
+        Ok(())
+    }
+
+    /// One line per configured rule, for the effective configuration.
+    fn rules(&self) -> Vec<String> {
+        let mut rules = Vec::new();
+        match (self.min_price, self.max_price) {
+            (Some(min), Some(max)) => rules.push(format!("price {}..={}", min, max)),
+            (Some(min), None) => rules.push(format!("price >= {}", min)),
+            (None, Some(max)) => rules.push(format!("price <= {}", max)),
+            (None, None) => {}
+        }
+        if let Some(pct) = self.max_deviation_pct {
+            rules.push(format!("deviation <= {}%", pct));
+        }
+        if let Some(volume) = self.max_volume {
+            rules.push(format!("volume <= {}", volume));
+        }
+        if !self.symbols.is_empty() {
+            rules.push(format!("symbols {}", self.symbols.iter().cloned().collect::<Vec<_>>().join(",")));
+        }
+        if !self.exchanges.is_empty() {
+            rules.push(format!("exchanges {}", self.exchanges.iter().cloned().collect::<Vec<_>>().join(",")));
+        }
+        rules.push(format!("skew <= {}ms", self.max_future_skew_ms));
+        if let Some(age) = self.max_age_secs {
+            rules.push(format!("age <= {}s", age));
+        }
+        rules
+    }
+}
+
+impl OutlierConfig {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(self.mad_threshold) || !positive(self.vwap_deviation_pct) {
+            return Err("outlier thresholds must be positive".into());
+        }
+        if self.min_samples < 2 || self.window < self.min_samples {
+            return Err("outlier min_samples must be at least 2 and no larger than window".into());
+        }
+        let kept = match self.method {
+            OutlierMethod::Mad => PRICE_CAPACITY,
+            OutlierMethod::Vwap => HISTORY_CAPACITY,
+        };
+        if self.window > kept {
+            return Err(format!("outlier window must be at most {} with method {}", kept, self.method).into());
+        }
+        if self.confirm_after == 0 {
+            return Err("outlier confirm_after must be greater than zero".into());
+        }
+        Ok(())
+    }
+}
+
+impl ApiConfig {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self.auth {
+            AuthMode::Required if self.keys.is_empty() => {
+                return Err("no API keys configured; add [[api.keys]] or set api.auth = \"disabled\" to serve the API without authentication".into());
+            }
+            AuthMode::Disabled if !self.keys.is_empty() => {
+                return Err("API keys are configured but api.auth is \"disabled\"".into());
+            }
+            _ => {}
+        }
+        let mut names = std::collections::BTreeSet::new();
+        for key in &self.keys {
+            if key.name.trim().is_empty() {
+                return Err("API key names must not be empty".into());
+            }
+            if !names.insert(key.name.as_str()) {
+                return Err(format!("duplicate API key name '{}'", key.name).into());
+            }
+            if key.sha256.len() != 64 || !key.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("API key '{}' must be a hex SHA-256 digest", key.name).into());
+            }
+            if key.scopes.is_empty() {
+                return Err(format!("API key '{}' has no scopes", key.name).into());
+            }
+        }
+        let limits = std::iter::once(("default", &self.rate_limit.default))
+            .chain(self.rate_limit.routes.iter().map(|(route, limit)| (route.as_str(), limit)));
+        for (route, limit) in limits {
+            if !(limit.requests_per_second.is_finite() && limit.requests_per_second > 0.0) || limit.burst == 0 {
+                return Err(format!("rate limit for {} must have a positive rate and burst", route).into());
+            }
+        }
+        for origin in &self.cors_origins {
+            let valid = origin == "*" || origin.starts_with("http://") || origin.starts_with("https://");
+            if !valid {
+                return Err(format!("invalid CORS origin '{}', expected * or http(s)://host", origin).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KafkaConfig {
+    pub fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.brokers.trim().is_empty() {
+            return Err("no Kafka brokers configured".into());
+        }
+        for broker in self.brokers.split(',') {
+            let broker = broker.trim();
+            let valid = broker
+                .rsplit_once(':')
+                .map(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+                .unwrap_or(false);
+            if !valid {
+                return Err(format!("invalid broker address '{}', expected host:port", broker).into());
+            }
+        }
+        if let Some(key) = self.client_overrides.keys().find(|key| key.is_empty()) {
+            return Err(format!("client property with empty key: '{}'", key).into());
+        }
+        self.security
+            .resolve()
+            .map_err(|e| format!("invalid Kafka security settings: {}", e))?;
+        Ok(())
+    }
+
+    /// Starts a client config with brokers, security and passthrough properties.
+    pub fn client_builder(&self) -> Result<KafkaClientBuilder, KafkaConfigError> {
+        Ok(KafkaClientBuilder::new(&self.brokers, &self.security)?.overrides(&self.client_overrides))
+    }
+}
+
+fn validate_topic(name: &str, topic: &str) -> Result<(), String> {
+    let valid_chars = topic
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if topic.is_empty() || topic.len() > 249 || !valid_chars {
+        return Err(format!("invalid {} topic name '{}'", name, topic));
+    }
+    Ok(())
+}
+
+/// Hides values of properties that look like credentials.
+fn redact<'a>(key: &str, value: &'a str) -> &'a str {
+    let key = key.to_lowercase();
+    if ["password", "secret", "key", "token"].iter().any(|word| key.contains(word)) {
+        "****"
+    } else {
+        value
+    }
+}
+
+impl fmt::Display for AppConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let security = &self.kafka.security;
+        writeln!(f, "Effective configuration:")?;
+        writeln!(f, "   kafka.brokers            = {}", self.kafka.brokers)?;
+        let protocol = security.resolve()
+            .map(|resolved| resolved.protocol.to_string())
+            .unwrap_or_else(|_| security.protocol.clone().unwrap_or_default());
+        writeln!(f, "   kafka.security.protocol  = {}", protocol)?;
+        if let Some(mechanism) = &security.sasl_mechanism {
+            writeln!(f, "   kafka.security.mechanism = {}", mechanism)?;
+        }
+        if let Some(username) = &security.sasl_username {
+            writeln!(f, "   kafka.security.username  = {}", username)?;
+        }
+        if security.sasl_password.is_some() {
+            writeln!(f, "   kafka.security.password  = ****")?;
+        }
+        if let Some(path) = &security.sasl_password_file {
+            writeln!(f, "   kafka.security.password  = **** (from {})", path)?;
+        }
+        let ssl_locations = [
+            ("ssl_ca_location", &security.ssl_ca_location),
+            ("ssl_certificate_location", &security.ssl_certificate_location),
+            ("ssl_key_location", &security.ssl_key_location),
+        ];
+        for (name, location) in ssl_locations {
+            if let Some(location) = location {
+                writeln!(f, "   kafka.security.{} = {}", name, location)?;
+            }
+        }
+        if security.ssl_key_password.is_some() || security.ssl_key_password_file.is_some() {
+            writeln!(f, "   kafka.security.ssl_key_password = ****")?;
+        }
+        for (key, value) in &self.kafka.client_overrides {
+            writeln!(f, "   kafka.client.{} = {}", key, redact(key, value))?;
+        }
+        writeln!(f, "   topics.trade             = {}", self.topics.trade)?;
+        writeln!(f, "   topics.rsi               = {}", self.topics.rsi)?;
+        writeln!(f, "   topics.quote             = {}", self.topics.quote)?;
+        writeln!(f, "   topics.depth             = {}", self.topics.depth)?;
+        if let Some(rejects) = &self.topics.rejects {
+            writeln!(f, "   topics.rejects           = {}", rejects)?;
+        }
+        writeln!(f, "   producer.interval_ms     = {}", self.producer.interval_ms)?;
+        writeln!(f, "   producer.simulation      = {}", self.producer.simulation)?;
+        writeln!(f, "   producer.max_in_flight   = {}", self.producer.max_in_flight)?;
+        if self.producer.metrics_port == 0 {
+            writeln!(f, "   producer.metrics_port    = 0 (disabled)")?;
+        } else {
+            writeln!(f, "   producer.metrics_address = {}:{}", self.producer.metrics_address, self.producer.metrics_port)?;
+        }
+        writeln!(f, "   producer.id              = {}", self.producer.id)?;
+        if let Some(scenario) = &self.producer.scenario {
+            writeln!(f, "   producer.scenario        = {}", scenario)?;
+        }
+        writeln!(f, "   consumer.group_id        = {}", self.consumer.group_id)?;
+        writeln!(f, "   consumer.api_port        = {}", self.consumer.api_port)?;
+        writeln!(f, "   consumer.ready_max_lag   = {}", self.consumer.ready_max_lag)?;
+        write!(f, "   consumer.ready_max_idle_secs = {}", self.consumer.ready_max_idle_secs)?;
+        for endpoint in &self.webhooks.endpoints {
+            let events: Vec<String> = endpoint.events.iter().map(ToString::to_string).collect();
+            let signed = if endpoint.secret.is_some() || endpoint.secret_file.is_some() { ", signed" } else { "" };
+            write!(f, "\n   webhooks.{} = {} ({}{})", endpoint.name, endpoint.url, events.join(","), signed)?;
+        }
+        if !self.webhooks.endpoints.is_empty() {
+            write!(f, "\n   webhooks.outbox_path     = {}", self.webhooks.outbox_path)?;
+        }
+        for key in &self.api.keys {
+            let scopes: Vec<String> = key.scopes.iter().map(ToString::to_string).collect();
+            write!(f, "\n   api.keys.{} = {}", key.name, scopes.join(","))?;
+        }
+        if self.api.auth == AuthMode::Disabled {
+            write!(f, "\n   api.auth                 = disabled")?;
+        }
+        write!(f, "\n   api.cors_origins         = {}", self.api.cors_origins.join(","))?;
+        let rate_limit = &self.api.rate_limit;
+        if rate_limit.enabled {
+            write!(f, "\n   api.rate_limit           = {}/s, burst {}",
+                rate_limit.default.requests_per_second, rate_limit.default.burst)?;
+            for (route, limit) in &rate_limit.routes {
+                write!(f, "\n   api.rate_limit.{} = {}/s, burst {}", route, limit.requests_per_second, limit.burst)?;
+            }
+        } else {
+            write!(f, "\n   api.rate_limit           = disabled")?;
+        }
+        match &self.storage.path {
+            Some(path) => {
+                let retention = &self.storage.retention;
+                write!(f, "\n   storage.path             = {}", path)?;
+                write!(f, "\n   storage.retention_days   = trades {}, candles {}, rsi {}",
+                    retention.trades_days, retention.candles_days, retention.rsi_days)?;
+            }
+            None => write!(f, "\n   storage.path             = (none, history kept in memory)")?,
+        }
+        if let Some(path) = &self.archive.path {
+            write!(f, "\n   archive.path             = {} (roll at {} bytes or {}s)",
+                path, self.archive.max_file_bytes, self.archive.max_file_age_secs)?;
+        }
+        write!(f, "\n   serialization.format     = {}", self.serialization.format)?;
+        if let Some(url) = &self.serialization.schema_registry_url {
+            let registration = if self.serialization.auto_register { "auto-register" } else { "pre-registered" };
+            write!(f, "\n   serialization.schema_registry_url = {} ({})", url, registration)?;
+        }
+        write!(f, "\n   prices.default           = {}", self.prices.default)?;
+        let mut symbols: Vec<_> = self.prices.symbols.iter().collect();
+        symbols.sort_by(|a, b| a.0.cmp(b.0));
+        for (symbol, spec) in symbols {
+            write!(f, "\n   prices.{:<17} = {}", symbol, spec)?;
+        }
+        if self.validation.enabled {
+            write!(f, "\n   validation               = {}", self.validation.rules().join(", "))?;
+        } else {
+            write!(f, "\n   validation               = disabled")?;
+        }
+        if self.outliers.enabled {
+            let threshold = match self.outliers.method {
+                OutlierMethod::Mad => format!("z > {}", self.outliers.mad_threshold),
+                OutlierMethod::Vwap => format!("> {}% from VWAP", self.outliers.vwap_deviation_pct),
+            };
+            write!(f, "\n   outliers                 = {} {} over {} trades, confirmed by {}",
+                self.outliers.method, threshold, self.outliers.window, self.outliers.confirm_after)?;
+        } else {
+            write!(f, "\n   outliers                 = disabled")?;
+        }
+        write!(f, "\n   logging.filter           = {}", self.logging.filter)?;
+        write!(f, "\n   logging.format           = {}", self.logging.format)?;
+        if let Some(endpoint) = &self.logging.otlp_endpoint {
+            write!(f, "\n   logging.otlp_endpoint    = {}", endpoint)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_binary_only_validates_its_own_sections() {
+        let args = CliArgs { interval_ms: Some(0), port: Some(0), api_auth: Some("disabled".to_string()), ..Default::default() };
+        assert!(AppConfig::load(args.clone(), Component::Producer).is_err());
+        assert!(AppConfig::load(args, Component::Consumer).is_err());
+
+        let producer_only = CliArgs { port: Some(0), ..Default::default() };
+        assert!(AppConfig::load(producer_only, Component::Producer).is_ok());
+        let consumer_only = CliArgs { interval_ms: Some(0), api_auth: Some("disabled".to_string()), ..Default::default() };
+        assert!(AppConfig::load(consumer_only, Component::Consumer).is_ok());
+    }
+
+    #[test]
+    fn metrics_endpoint_can_share_the_api_port_or_be_disabled() {
+        let shared = CliArgs {
+            metrics_port: Some(3001),
+            port: Some(3001),
+            api_auth: Some("disabled".to_string()),
+            ..Default::default()
+        };
+        assert!(AppConfig::load(shared.clone(), Component::Producer).is_ok());
+        assert!(AppConfig::load(shared, Component::Consumer).is_ok());
+
+        let disabled = CliArgs {
+            metrics_port: Some(0),
+            metrics_address: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            ..Default::default()
+        };
+        let config = AppConfig::load(disabled, Component::Producer).unwrap();
+        assert_eq!(config.producer.metrics_port, 0);
+        assert_eq!(config.producer.metrics_address, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert!(config.to_string().contains("metrics_port    = 0 (disabled)"));
+    }
+
+    #[test]
+    fn consumer_refuses_to_start_without_keys_unless_auth_is_disabled() {
+        let error = AppConfig::load(CliArgs::default(), Component::Consumer).unwrap_err();
+        assert!(error.to_string().contains("no API keys configured"));
+
+        let disabled = CliArgs { api_auth: Some("disabled".to_string()), ..Default::default() };
+        let config = AppConfig::load(disabled, Component::Consumer).unwrap();
+        assert_eq!(config.api.auth, AuthMode::Disabled);
+        assert!(config.to_string().contains("api.auth                 = disabled"));
+
+        let unknown = CliArgs { api_auth: Some("off".to_string()), ..Default::default() };
+        assert!(AppConfig::load(unknown, Component::Consumer).is_err());
+        // The producer serves no API
+        assert!(AppConfig::load(CliArgs::default(), Component::Producer).is_ok());
+    }
+
+    #[test]
+    fn keys_and_disabled_auth_are_exclusive() {
+        let mut api = ApiConfig {
+            auth: AuthMode::Required,
+            keys: vec![ApiKey { name: "dashboard".to_string(), sha256: "ab".repeat(32), scopes: ApiScope::read_only() }],
+            cors_origins: vec!["http://localhost:3000".to_string()],
+            rate_limit: RateLimitConfig {
+                enabled: true,
+                default: RateLimit { requests_per_second: 1.0, burst: 1 },
+                routes: BTreeMap::new(),
+            },
+        };
+        assert!(api.validate().is_ok());
+        api.auth = AuthMode::Disabled;
+        assert!(api.validate().is_err());
+        api.keys.clear();
+        assert!(api.validate().is_ok());
+    }
+
+    #[test]
+    fn order_book_simulation_is_opt_in() {
+        let config = AppConfig::load(CliArgs::default(), Component::Producer).unwrap();
+        assert_eq!(config.producer.simulation, Simulation::Random);
+
+        let args = CliArgs { simulation: Some("order-book".to_string()), ..Default::default() };
+        let config = AppConfig::load(args, Component::Producer).unwrap();
+        assert_eq!(config.producer.simulation, Simulation::OrderBook);
+    }
+
+    #[test]
+    fn outlier_window_is_bounded_by_the_history_kept() {
+        let mad = OutlierConfig { window: PRICE_CAPACITY, ..OutlierConfig::default() };
+        assert!(mad.validate().is_ok());
+        assert!(OutlierConfig { window: PRICE_CAPACITY + 1, ..mad.clone() }.validate().is_err());
+
+        let vwap = OutlierConfig { method: OutlierMethod::Vwap, window: HISTORY_CAPACITY, ..mad };
+        assert!(vwap.validate().is_ok());
+        assert!(OutlierConfig { window: HISTORY_CAPACITY + 1, ..vwap }.validate().is_err());
+    }
+}
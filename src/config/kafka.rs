@@ -0,0 +1,578 @@
+use rdkafka::config::ClientConfig;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Security settings as they come from CLI flags, env vars or the config file.
+/// Nothing here is validated until it is resolved into a [`KafkaSecurity`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityConfig {
+    pub protocol: Option<String>,
+    pub sasl_mechanism: Option<String>,
+    pub sasl_username: Option<String>,
+    pub sasl_password: Option<String>,
+    pub sasl_password_file: Option<String>,
+    pub ssl_ca_location: Option<String>,
+    pub ssl_certificate_location: Option<String>,
+    pub ssl_key_location: Option<String>,
+    pub ssl_key_password: Option<String>,
+    pub ssl_key_password_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityProtocol {
+    Plaintext,
+    Ssl,
+    SaslPlaintext,
+    SaslSsl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaslMechanism {
+    Plain,
+    ScramSha256,
+    ScramSha512,
+}
+
+#[derive(Clone)]
+pub struct SaslCredentials {
+    pub mechanism: SaslMechanism,
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for SaslCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SaslCredentials")
+            .field("mechanism", &self.mechanism)
+            .field("username", &self.username)
+            .field("password", &"****")
+            .finish()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct SslSettings {
+    pub ca_location: Option<String>,
+    pub certificate_location: Option<String>,
+    pub key_location: Option<String>,
+    pub key_password: Option<String>,
+}
+
+impl fmt::Debug for SslSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SslSettings")
+            .field("ca_location", &self.ca_location)
+            .field("certificate_location", &self.certificate_location)
+            .field("key_location", &self.key_location)
+            .field("key_password", &self.key_password.as_ref().map(|_| "****"))
+            .finish()
+    }
+}
+
+/// Fully validated security configuration.
+#[derive(Debug, Clone)]
+pub struct KafkaSecurity {
+    pub protocol: SecurityProtocol,
+    pub sasl: Option<SaslCredentials>,
+    pub ssl: SslSettings,
+}
+
+#[derive(Debug)]
+pub enum KafkaConfigError {
+    UnknownProtocol(String),
+    UnknownMechanism(String),
+    /// Some settings of a group were provided but required ones are missing.
+    Incomplete {
+        context: String,
+        missing: Vec<&'static str>,
+    },
+    /// Settings were provided that the chosen protocol does not use.
+    Unused {
+        protocol: SecurityProtocol,
+        settings: Vec<&'static str>,
+    },
+    Conflict(&'static str, &'static str),
+    /// A certificate or key location that does not exist.
+    MissingFile {
+        setting: &'static str,
+        path: String,
+    },
+    SecretFile {
+        path: String,
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for KafkaConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KafkaConfigError::UnknownProtocol(value) => write!(
+                f,
+                "unknown security protocol '{}', expected one of PLAINTEXT, SSL, SASL_PLAINTEXT, SASL_SSL",
+                value
+            ),
+            KafkaConfigError::UnknownMechanism(value) => write!(
+                f,
+                "unknown SASL mechanism '{}', expected one of PLAIN, SCRAM-SHA-256, SCRAM-SHA-512",
+                value
+            ),
+            KafkaConfigError::Incomplete { context, missing } => {
+                write!(f, "{}, but missing {}", context, missing.join(", "))
+            }
+            KafkaConfigError::Unused { protocol, settings } => write!(
+                f,
+                "{} set but security protocol is {}",
+                settings.join(", "),
+                protocol
+            ),
+            KafkaConfigError::Conflict(a, b) => write!(f, "set either {} or {}, not both", a, b),
+            KafkaConfigError::MissingFile { setting, path } => write!(f, "{} '{}' does not exist", setting, path),
+            KafkaConfigError::SecretFile { path, error } => {
+                write!(f, "failed to read secret file '{}': {}", path, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for KafkaConfigError {}
+
+impl FromStr for SecurityProtocol {
+    type Err = KafkaConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_uppercase().as_str() {
+            "PLAINTEXT" => Ok(SecurityProtocol::Plaintext),
+            "SSL" => Ok(SecurityProtocol::Ssl),
+            "SASL_PLAINTEXT" => Ok(SecurityProtocol::SaslPlaintext),
+            "SASL_SSL" => Ok(SecurityProtocol::SaslSsl),
+            _ => Err(KafkaConfigError::UnknownProtocol(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SecurityProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SecurityProtocol::Plaintext => "PLAINTEXT",
+            SecurityProtocol::Ssl => "SSL",
+            SecurityProtocol::SaslPlaintext => "SASL_PLAINTEXT",
+            SecurityProtocol::SaslSsl => "SASL_SSL",
+        })
+    }
+}
+
+impl SecurityProtocol {
+    pub fn uses_sasl(self) -> bool {
+        matches!(self, SecurityProtocol::SaslPlaintext | SecurityProtocol::SaslSsl)
+    }
+
+    pub fn uses_ssl(self) -> bool {
+        matches!(self, SecurityProtocol::Ssl | SecurityProtocol::SaslSsl)
+    }
+}
+
+impl FromStr for SaslMechanism {
+    type Err = KafkaConfigError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_uppercase().replace('_', "-").as_str() {
+            "PLAIN" => Ok(SaslMechanism::Plain),
+            "SCRAM-SHA-256" => Ok(SaslMechanism::ScramSha256),
+            "SCRAM-SHA-512" => Ok(SaslMechanism::ScramSha512),
+            _ => Err(KafkaConfigError::UnknownMechanism(value.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SaslMechanism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SaslMechanism::Plain => "PLAIN",
+            SaslMechanism::ScramSha256 => "SCRAM-SHA-256",
+            SaslMechanism::ScramSha512 => "SCRAM-SHA-512",
+        })
+    }
+}
+
+/// Returns the inline value or the trimmed contents of `file`, rejecting both at once.
+fn read_secret(
+    value: &Option<String>,
+    file: &Option<String>,
+    names: (&'static str, &'static str),
+) -> Result<Option<String>, KafkaConfigError> {
+    match (value, file) {
+        (Some(_), Some(_)) => Err(KafkaConfigError::Conflict(names.0, names.1)),
+        (Some(value), None) => Ok(Some(value.clone())),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map(|contents| Some(contents.trim_end_matches(['\r', '\n']).to_string()))
+            .map_err(|error| KafkaConfigError::SecretFile { path: path.clone(), error }),
+        (None, None) => Ok(None),
+    }
+}
+
+impl SecurityConfig {
+    /// Validates the combination of settings and reads any secret files.
+    pub fn resolve(&self) -> Result<KafkaSecurity, KafkaConfigError> {
+        let sasl_password = read_secret(
+            &self.sasl_password,
+            &self.sasl_password_file,
+            ("KAFKA_SASL_PASSWORD", "KAFKA_SASL_PASSWORD_FILE"),
+        )?;
+        let ssl_key_password = read_secret(
+            &self.ssl_key_password,
+            &self.ssl_key_password_file,
+            ("KAFKA_SSL_KEY_PASSWORD", "KAFKA_SSL_KEY_PASSWORD_FILE"),
+        )?;
+
+        let sasl_given: Vec<&'static str> = [
+            ("KAFKA_SASL_MECHANISM", self.sasl_mechanism.is_some()),
+            ("KAFKA_SASL_USERNAME", self.sasl_username.is_some()),
+            ("KAFKA_SASL_PASSWORD", sasl_password.is_some()),
+        ]
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(name, _)| *name)
+        .collect();
+        let ssl_given: Vec<&'static str> = [
+            ("KAFKA_SSL_CA_LOCATION", self.ssl_ca_location.is_some()),
+            ("KAFKA_SSL_CERTIFICATE_LOCATION", self.ssl_certificate_location.is_some()),
+            ("KAFKA_SSL_KEY_LOCATION", self.ssl_key_location.is_some()),
+            ("KAFKA_SSL_KEY_PASSWORD", ssl_key_password.is_some()),
+        ]
+        .iter()
+        .filter(|(_, given)| *given)
+        .map(|(name, _)| *name)
+        .collect();
+
+        let protocol = match &self.protocol {
+            Some(protocol) => protocol.parse()?,
+            None if !sasl_given.is_empty() => {
+                return Err(KafkaConfigError::Incomplete {
+                    context: format!("{} set", sasl_given.join(", ")),
+                    missing: vec!["KAFKA_SECURITY_PROTOCOL (SASL_PLAINTEXT or SASL_SSL)"],
+                });
+            }
+            None if !ssl_given.is_empty() => SecurityProtocol::Ssl,
+            None => SecurityProtocol::Plaintext,
+        };
+
+        if !protocol.uses_sasl() && !sasl_given.is_empty() {
+            return Err(KafkaConfigError::Unused { protocol, settings: sasl_given });
+        }
+        if !protocol.uses_ssl() && !ssl_given.is_empty() {
+            return Err(KafkaConfigError::Unused { protocol, settings: ssl_given });
+        }
+
+        let sasl = if protocol.uses_sasl() {
+            let mut missing = Vec::new();
+            if self.sasl_mechanism.is_none() {
+                missing.push("KAFKA_SASL_MECHANISM (PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512)");
+            }
+            if self.sasl_username.is_none() {
+                missing.push("KAFKA_SASL_USERNAME");
+            }
+            if sasl_password.is_none() {
+                missing.push("KAFKA_SASL_PASSWORD (or KAFKA_SASL_PASSWORD_FILE)");
+            }
+            if !missing.is_empty() {
+                return Err(KafkaConfigError::Incomplete {
+                    context: format!("security protocol is {}", protocol),
+                    missing,
+                });
+            }
+            Some(SaslCredentials {
+                mechanism: self.sasl_mechanism.as_deref().unwrap_or_default().parse()?,
+                username: self.sasl_username.clone().unwrap_or_default(),
+                password: sasl_password.unwrap_or_default(),
+            })
+        } else {
+            None
+        };
+
+        // A client certificate needs its key and vice versa
+        match (&self.ssl_certificate_location, &self.ssl_key_location) {
+            (Some(_), None) => {
+                return Err(KafkaConfigError::Incomplete {
+                    context: "KAFKA_SSL_CERTIFICATE_LOCATION set".to_string(),
+                    missing: vec!["KAFKA_SSL_KEY_LOCATION"],
+                });
+            }
+            (None, Some(_)) => {
+                return Err(KafkaConfigError::Incomplete {
+                    context: "KAFKA_SSL_KEY_LOCATION set".to_string(),
+                    missing: vec!["KAFKA_SSL_CERTIFICATE_LOCATION"],
+                });
+            }
+            _ => {}
+        }
+        if ssl_key_password.is_some() && self.ssl_key_location.is_none() {
+            return Err(KafkaConfigError::Incomplete {
+                context: "KAFKA_SSL_KEY_PASSWORD set".to_string(),
+                missing: vec!["KAFKA_SSL_KEY_LOCATION", "KAFKA_SSL_CERTIFICATE_LOCATION"],
+            });
+        }
+        // librdkafka only reports a bad path once the client is created
+        let ssl_files = [
+            ("KAFKA_SSL_CA_LOCATION", &self.ssl_ca_location),
+            ("KAFKA_SSL_CERTIFICATE_LOCATION", &self.ssl_certificate_location),
+            ("KAFKA_SSL_KEY_LOCATION", &self.ssl_key_location),
+        ];
+        for (setting, path) in ssl_files {
+            if let Some(path) = path.as_ref().filter(|path| *path != "probe" && !Path::new(path).exists()) {
+                return Err(KafkaConfigError::MissingFile { setting, path: path.clone() });
+            }
+        }
+
+        Ok(KafkaSecurity {
+            protocol,
+            sasl,
+            ssl: SslSettings {
+                ca_location: self.ssl_ca_location.clone(),
+                certificate_location: self.ssl_certificate_location.clone(),
+                key_location: self.ssl_key_location.clone(),
+                key_password: ssl_key_password,
+            },
+        })
+    }
+}
+
+impl KafkaSecurity {
+    fn apply(&self, config: &mut ClientConfig) {
+        config.set("security.protocol", self.protocol.to_string());
+
+        if let Some(sasl) = &self.sasl {
+            config
+                .set("sasl.mechanism", sasl.mechanism.to_string())
+                .set("sasl.username", &sasl.username)
+                .set("sasl.password", &sasl.password);
+        }
+
+        let ssl = &self.ssl;
+        let ssl_properties = [
+            ("ssl.ca.location", &ssl.ca_location),
+            ("ssl.certificate.location", &ssl.certificate_location),
+            ("ssl.key.location", &ssl.key_location),
+            ("ssl.key.password", &ssl.key_password),
+        ];
+        for (key, value) in ssl_properties {
+            if let Some(value) = value {
+                config.set(key, value);
+            }
+        }
+    }
+}
+
+/// Builds an rdkafka [`ClientConfig`] from shared settings.
+///
+/// Properties are applied in order: bootstrap servers, security, the
+/// client-specific defaults given with [`KafkaClientBuilder::set`], and
+/// finally the user's passthrough properties, so users can override anything.
+#[derive(Debug, Clone)]
+pub struct KafkaClientBuilder {
+    brokers: String,
+    security: KafkaSecurity,
+    defaults: BTreeMap<String, String>,
+    overrides: BTreeMap<String, String>,
+}
+
+impl KafkaClientBuilder {
+    pub fn new(brokers: &str, security: &SecurityConfig) -> Result<Self, KafkaConfigError> {
+        Ok(Self {
+            brokers: brokers.to_string(),
+            security: security.resolve()?,
+            defaults: BTreeMap::new(),
+            overrides: BTreeMap::new(),
+        })
+    }
+
+    /// Sets a client default that passthrough properties may override.
+    pub fn set(mut self, key: &str, value: &str) -> Self {
+        self.defaults.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds passthrough librdkafka properties, applied last.
+    pub fn overrides(mut self, properties: &BTreeMap<String, String>) -> Self {
+        self.overrides.extend(properties.clone());
+        self
+    }
+
+    pub fn security(&self) -> &KafkaSecurity {
+        &self.security
+    }
+
+    pub fn build(&self) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &self.brokers);
+        self.security.apply(&mut config);
+        for (key, value) in self.defaults.iter().chain(self.overrides.iter()) {
+            config.set(key, value);
+        }
+        config
+    }
+}
+
+/// Prints which security mode a client is about to connect with.
+pub fn log_security(builder: &KafkaClientBuilder) {
+    let security = builder.security();
+    match &security.sasl {
+        Some(sasl) => tracing::info!(protocol = %security.protocol, mechanism = %sasl.mechanism, username = %sasl.username, "using SASL authentication"),
+        None if security.protocol.uses_ssl() => tracing::info!(protocol = %security.protocol, "using SSL connection"),
+        None => tracing::info!("using PLAINTEXT connection (no SASL)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// A file that exists for the duration of a test.
+    fn temp_file(contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("kafka-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    fn sasl_ssl() -> SecurityConfig {
+        SecurityConfig {
+            protocol: Some("SASL_SSL".to_string()),
+            sasl_mechanism: Some("scram-sha-512".to_string()),
+            sasl_username: Some("trader".to_string()),
+            sasl_password: Some("hunter2".to_string()),
+            ..SecurityConfig::default()
+        }
+    }
+
+    fn missing(result: Result<KafkaSecurity, KafkaConfigError>) -> Vec<&'static str> {
+        match result {
+            Err(KafkaConfigError::Incomplete { missing, .. }) => missing,
+            other => panic!("expected an incomplete configuration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults_to_plaintext() {
+        let security = SecurityConfig::default().resolve().unwrap();
+        assert_eq!(security.protocol, SecurityProtocol::Plaintext);
+        assert!(security.sasl.is_none());
+    }
+
+    #[test]
+    fn unknown_protocol_or_mechanism_is_an_error() {
+        let protocol = SecurityConfig { protocol: Some("TLS".to_string()), ..SecurityConfig::default() };
+        assert!(matches!(protocol.resolve(), Err(KafkaConfigError::UnknownProtocol(value)) if value == "TLS"));
+        let mechanism = SecurityConfig { sasl_mechanism: Some("GSSAPI".to_string()), ..sasl_ssl() };
+        assert!(matches!(mechanism.resolve(), Err(KafkaConfigError::UnknownMechanism(_))));
+    }
+
+    #[test]
+    fn sasl_needs_a_mechanism_and_credentials() {
+        let resolved = sasl_ssl().resolve().unwrap();
+        let sasl = resolved.sasl.unwrap();
+        assert_eq!((sasl.mechanism, sasl.username.as_str()), (SaslMechanism::ScramSha512, "trader"));
+
+        let no_mechanism = SecurityConfig { sasl_mechanism: None, ..sasl_ssl() };
+        assert_eq!(missing(no_mechanism.resolve()), ["KAFKA_SASL_MECHANISM (PLAIN, SCRAM-SHA-256 or SCRAM-SHA-512)"]);
+        let no_credentials = SecurityConfig { sasl_username: None, sasl_password: None, ..sasl_ssl() };
+        assert_eq!(missing(no_credentials.resolve()), ["KAFKA_SASL_USERNAME", "KAFKA_SASL_PASSWORD (or KAFKA_SASL_PASSWORD_FILE)"]);
+        let no_protocol = SecurityConfig { protocol: None, ..sasl_ssl() };
+        assert_eq!(missing(no_protocol.resolve()), ["KAFKA_SECURITY_PROTOCOL (SASL_PLAINTEXT or SASL_SSL)"]);
+        let plaintext = SecurityConfig { protocol: Some("SSL".to_string()), ..sasl_ssl() };
+        assert!(matches!(plaintext.resolve(), Err(KafkaConfigError::Unused { .. })));
+    }
+
+    #[test]
+    fn passwords_come_inline_or_from_a_file() {
+        let path = temp_file("from-file\n");
+        let from_file = SecurityConfig { sasl_password: None, sasl_password_file: Some(path.clone()), ..sasl_ssl() };
+        assert_eq!(from_file.resolve().unwrap().sasl.unwrap().password, "from-file");
+        let both = SecurityConfig { sasl_password_file: Some(path.clone()), ..sasl_ssl() };
+        assert!(matches!(both.resolve(), Err(KafkaConfigError::Conflict(..))));
+        std::fs::remove_file(path).unwrap();
+
+        let unreadable = SecurityConfig { sasl_password: None, sasl_password_file: Some("/nonexistent/secret".to_string()), ..sasl_ssl() };
+        assert!(matches!(unreadable.resolve(), Err(KafkaConfigError::SecretFile { .. })));
+    }
+
+    #[test]
+    fn client_certificate_and_key_come_together() {
+        let (certificate, key) = (temp_file("cert"), temp_file("key"));
+        let ssl = |certificate: Option<&String>, key: Option<&String>| SecurityConfig {
+            ssl_certificate_location: certificate.cloned(),
+            ssl_key_location: key.cloned(),
+            ..SecurityConfig::default()
+        };
+        let both = ssl(Some(&certificate), Some(&key)).resolve().unwrap();
+        assert_eq!(both.protocol, SecurityProtocol::Ssl);
+        assert_eq!(missing(ssl(Some(&certificate), None).resolve()), ["KAFKA_SSL_KEY_LOCATION"]);
+        assert_eq!(missing(ssl(None, Some(&key)).resolve()), ["KAFKA_SSL_CERTIFICATE_LOCATION"]);
+        let password_only = SecurityConfig { ssl_key_password: Some("secret".to_string()), ..SecurityConfig::default() };
+        assert_eq!(missing(password_only.resolve()), ["KAFKA_SSL_KEY_LOCATION", "KAFKA_SSL_CERTIFICATE_LOCATION"]);
+        std::fs::remove_file(certificate).unwrap();
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    fn certificate_and_key_paths_must_exist() {
+        let ca = SecurityConfig { ssl_ca_location: Some("/nonexistent/ca.pem".to_string()), ..SecurityConfig::default() };
+        assert!(matches!(ca.resolve(), Err(KafkaConfigError::MissingFile { setting: "KAFKA_SSL_CA_LOCATION", .. })));
+        let key = temp_file("key");
+        let certificate = SecurityConfig {
+            ssl_certificate_location: Some("/nonexistent/client.pem".to_string()),
+            ssl_key_location: Some(key.clone()),
+            ..SecurityConfig::default()
+        };
+        assert!(matches!(certificate.resolve(), Err(KafkaConfigError::MissingFile { setting: "KAFKA_SSL_CERTIFICATE_LOCATION", .. })));
+        std::fs::remove_file(key).unwrap();
+        let probe = SecurityConfig { ssl_ca_location: Some("probe".to_string()), ..SecurityConfig::default() };
+        assert!(probe.resolve().is_ok());
+    }
+
+    /// Collects formatted log output.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn secrets_are_never_logged() {
+        let security = SecurityConfig { ssl_key_password: Some("key-secret".to_string()), ..sasl_ssl() };
+        let key = temp_file("key");
+        let certificate = temp_file("cert");
+        let security = SecurityConfig {
+            ssl_key_location: Some(key.clone()),
+            ssl_certificate_location: Some(certificate.clone()),
+            ..security
+        };
+        let builder = KafkaClientBuilder::new("localhost:9092", &security).unwrap();
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).finish();
+        tracing::subscriber::with_default(subscriber, || log_security(&builder));
+        let logged = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logged.contains("SCRAM-SHA-512") && logged.contains("trader"));
+
+        let debugged = format!("{:?}", builder);
+        for output in [logged, debugged] {
+            assert!(!output.contains("hunter2") && !output.contains("key-secret"), "{}", output);
+        }
+        std::fs::remove_file(key).unwrap();
+        std::fs::remove_file(certificate).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+pub mod app;
+pub mod kafka;
+
+pub use app::*;
+pub use kafka::*;
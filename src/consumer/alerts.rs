@@ -0,0 +1,429 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::consumer::PriceHistory;
+use crate::models::{price_to_f64, Decimal, TradeData};
+
+/// Number of fired alerts kept for `GET /alerts/fired`.
+const FIRED_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossDirection {
+    Above,
+    Below,
+    Either,
+}
+
+/// What an alert rule watches for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    RsiAbove { threshold: f64 },
+    RsiBelow { threshold: f64 },
+    /// Price moving through `level` between two consecutive trades.
+    PriceCross {
+        level: Decimal,
+        #[serde(default = "default_direction")]
+        direction: CrossDirection,
+    },
+    /// Price moving at least `percent` (either way) within `window_secs`.
+    PercentMove { percent: f64, window_secs: u64 },
+    /// A trade's volume reaching `multiplier` times the average of the
+    /// previous `lookback` trades.
+    VolumeSpike {
+        multiplier: f64,
+        #[serde(default = "default_lookback")]
+        lookback: usize,
+    },
+}
+
+fn default_direction() -> CrossDirection {
+    CrossDirection::Either
+}
+
+fn default_lookback() -> usize {
+    20
+}
+
+impl AlertCondition {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            AlertCondition::RsiAbove { threshold } | AlertCondition::RsiBelow { threshold } => {
+                if !(0.0..=100.0).contains(&threshold) {
+                    return Err("RSI threshold must be between 0 and 100".to_string());
+                }
+            }
+            AlertCondition::PriceCross { level, .. } => {
+                if level <= Decimal::ZERO {
+                    return Err("price level must be positive".to_string());
+                }
+            }
+            AlertCondition::PercentMove { percent, window_secs } => {
+                if !(percent.is_finite() && percent > 0.0) {
+                    return Err("percent must be positive".to_string());
+                }
+                if window_secs == 0 {
+                    return Err("window_secs must be greater than zero".to_string());
+                }
+            }
+            AlertCondition::VolumeSpike { multiplier, lookback } => {
+                if !(multiplier.is_finite() && multiplier > 0.0) {
+                    return Err("multiplier must be positive".to_string());
+                }
+                if lookback == 0 {
+                    return Err("lookback must be greater than zero".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the condition against the latest trade in `history`, returning
+    /// the observed value and a description when it holds. Earlier outliers
+    /// are left out of the comparison.
+    fn check(&self, history: &PriceHistory, rsi: Option<f64>) -> Option<(f64, String)> {
+        let trade = history.trades.last()?;
+        let previous: Vec<&TradeData> = history.trades[..history.trades.len() - 1]
+            .iter()
+            .filter(|old| !old.is_outlier())
+            .collect();
+
+        match *self {
+            AlertCondition::RsiAbove { threshold } => {
+                let rsi = rsi.filter(|rsi| *rsi > threshold)?;
+                Some((rsi, format!("RSI {:.2} above {:.2}", rsi, threshold)))
+            }
+            AlertCondition::RsiBelow { threshold } => {
+                let rsi = rsi.filter(|rsi| *rsi < threshold)?;
+                Some((rsi, format!("RSI {:.2} below {:.2}", rsi, threshold)))
+            }
+            AlertCondition::PriceCross { level, direction } => {
+                let last = previous.last()?.price;
+                let up = last < level && trade.price >= level;
+                let down = last > level && trade.price <= level;
+                let crossed = match direction {
+                    CrossDirection::Above => up,
+                    CrossDirection::Below => down,
+                    CrossDirection::Either => up || down,
+                };
+                crossed.then(|| {
+                    let way = if up { "above" } else { "below" };
+                    (price_to_f64(trade.price), format!("price {} crossed {} {}", trade.price, way, level))
+                })
+            }
+            AlertCondition::PercentMove { percent, window_secs } => {
+                let since = trade.timestamp - Duration::seconds(window_secs as i64);
+                let base = previous.iter().find(|old| old.timestamp >= since)?.price;
+                let change = price_to_f64((trade.price - base).checked_div(base)? * Decimal::ONE_HUNDRED);
+                (change.abs() >= percent).then(|| {
+                    (change, format!("price moved {:+.2}% in {}s", change, window_secs))
+                })
+            }
+            AlertCondition::VolumeSpike { multiplier, lookback } => {
+                if previous.len() < lookback {
+                    return None;
+                }
+                let window = &previous[previous.len() - lookback..];
+                let average = window.iter().map(|old| old.volume as f64).sum::<f64>() / lookback as f64;
+                let ratio = trade.volume as f64 / average;
+                (average > 0.0 && ratio >= multiplier).then(|| {
+                    (ratio, format!("volume {} is {:.1}x the {}-trade average", trade.volume, ratio, lookback))
+                })
+            }
+        }
+    }
+}
+
+/// Body of `POST /alerts` and `PUT /alerts/{id}`.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AlertRuleRequest {
+    pub symbol: String,
+    pub condition: AlertCondition,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl AlertRuleRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.symbol.trim().is_empty() {
+            return Err("symbol must not be empty".to_string());
+        }
+        self.condition.validate()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AlertRule {
+    pub id: String,
+    pub symbol: String,
+    pub condition: AlertCondition,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FiredAlert {
+    pub id: String,
+    pub rule_id: String,
+    pub symbol: String,
+    pub condition: AlertCondition,
+    pub message: String,
+    /// The value that triggered the rule (RSI, price, percent change or volume ratio).
+    pub value: f64,
+    pub price: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl fmt::Display for FiredAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.symbol, self.message)
+    }
+}
+
+struct RuleState {
+    rule: AlertRule,
+    /// Whether the condition held on the previous trade; rules fire only
+    /// when it starts holding, not on every trade while it does.
+    active: bool,
+}
+
+#[derive(Default)]
+struct AlertStore {
+    rules: BTreeMap<String, RuleState>,
+    fired: VecDeque<FiredAlert>,
+}
+
+/// Alert rules and the alerts they fired, shared between the API and the
+/// consumer.
+#[derive(Clone, Default)]
+pub struct AlertEngine {
+    store: Arc<Mutex<AlertStore>>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn list(&self) -> Vec<AlertRule> {
+        let store = self.store.lock().unwrap();
+        store.rules.values().map(|state| state.rule.clone()).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<AlertRule> {
+        let store = self.store.lock().unwrap();
+        store.rules.get(id).map(|state| state.rule.clone())
+    }
+
+    pub fn create(&self, request: AlertRuleRequest) -> Result<AlertRule, String> {
+        request.validate()?;
+        let now = Utc::now();
+        let rule = AlertRule {
+            id: Uuid::new_v4().to_string(),
+            symbol: request.symbol.trim().to_uppercase(),
+            condition: request.condition,
+            enabled: request.enabled,
+            created_at: now,
+            updated_at: now,
+        };
+        let mut store = self.store.lock().unwrap();
+        store.rules.insert(rule.id.clone(), RuleState { rule: rule.clone(), active: false });
+        Ok(rule)
+    }
+
+    /// Replaces a rule; `Ok(None)` if it does not exist.
+    pub fn update(&self, id: &str, request: AlertRuleRequest) -> Result<Option<AlertRule>, String> {
+        request.validate()?;
+        let mut store = self.store.lock().unwrap();
+        let Some(state) = store.rules.get_mut(id) else {
+            return Ok(None);
+        };
+        state.rule.symbol = request.symbol.trim().to_uppercase();
+        state.rule.condition = request.condition;
+        state.rule.enabled = request.enabled;
+        state.rule.updated_at = Utc::now();
+        state.active = false;
+        Ok(Some(state.rule.clone()))
+    }
+
+    pub fn delete(&self, id: &str) -> bool {
+        let mut store = self.store.lock().unwrap();
+        store.rules.remove(id).is_some()
+    }
+
+    /// Fired alerts, newest first.
+    pub fn fired(&self, symbol: Option<&str>, limit: usize) -> Vec<FiredAlert> {
+        let store = self.store.lock().unwrap();
+        store
+            .fired
+            .iter()
+            .rev()
+            .filter(|alert| symbol.is_none_or(|symbol| alert.symbol == symbol))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Evaluates the enabled rules for the symbol of the latest trade in
+    /// `history` and records the alerts that fired.
+    pub fn evaluate(&self, history: &PriceHistory, rsi: Option<f64>) -> Vec<FiredAlert> {
+        let Some(trade) = history.trades.last() else {
+            return Vec::new();
+        };
+
+        let mut store = self.store.lock().unwrap();
+        let mut fired = Vec::new();
+        for state in store.rules.values_mut() {
+            if !state.rule.enabled || state.rule.symbol != history.symbol {
+                continue;
+            }
+            let hit = state.rule.condition.check(history, rsi);
+            let was_active = std::mem::replace(&mut state.active, hit.is_some());
+            let Some((value, message)) = hit.filter(|_| !was_active) else {
+                continue;
+            };
+            fired.push(FiredAlert {
+                id: Uuid::new_v4().to_string(),
+                rule_id: state.rule.id.clone(),
+                symbol: history.symbol.clone(),
+                condition: state.rule.condition.clone(),
+                message,
+                value,
+                price: trade.price,
+                timestamp: trade.timestamp,
+            });
+        }
+
+        for alert in &fired {
+            if store.fired.len() == FIRED_CAPACITY {
+                store.fired.pop_front();
+            }
+            store.fired.push_back(alert.clone());
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TradeSide;
+
+    fn rule(engine: &AlertEngine, symbol: &str, condition: AlertCondition) -> AlertRule {
+        engine.create(AlertRuleRequest { symbol: symbol.to_string(), condition, enabled: true }).unwrap()
+    }
+
+    /// Adds a trade and evaluates the rules against it, returning the
+    /// messages of the alerts that fired.
+    fn trade(engine: &AlertEngine, history: &mut PriceHistory, price: &str, volume: u64) -> Vec<String> {
+        let trade = TradeData::new(history.symbol.clone(), price.parse().unwrap(), volume, TradeSide::Buy, "NASDAQ".to_string());
+        history.add_trade(trade);
+        engine.evaluate(history, None).into_iter().map(|alert| alert.message).collect()
+    }
+
+    #[test]
+    fn price_crossings_fire_in_the_configured_direction() {
+        let engine = AlertEngine::new();
+        let level = "100".parse().unwrap();
+        let up = rule(&engine, "aapl", AlertCondition::PriceCross { level, direction: CrossDirection::Above });
+        assert_eq!(up.symbol, "AAPL");
+        let mut history = PriceHistory::new("AAPL".to_string());
+
+        assert!(trade(&engine, &mut history, "99", 10).is_empty());
+        assert_eq!(trade(&engine, &mut history, "100", 10), ["price 100 crossed above 100"]);
+        assert!(trade(&engine, &mut history, "101", 10).is_empty());
+        // Crossing back down does not match an `above` rule
+        assert!(trade(&engine, &mut history, "99", 10).is_empty());
+        assert_eq!(trade(&engine, &mut history, "102", 10), ["price 102 crossed above 100"]);
+
+        // Other symbols' trades are not evaluated against the rule
+        let mut other = PriceHistory::new("MSFT".to_string());
+        trade(&engine, &mut other, "99", 10);
+        assert!(trade(&engine, &mut other, "101", 10).is_empty());
+        assert_eq!(engine.fired(Some("AAPL"), 10).len(), 2);
+    }
+
+    #[test]
+    fn rules_fire_once_per_crossing_and_re_arm_when_it_clears() {
+        let engine = AlertEngine::new();
+        rule(&engine, "AAPL", AlertCondition::RsiAbove { threshold: 70.0 });
+        let mut history = PriceHistory::new("AAPL".to_string());
+        history.add_trade(TradeData::new("AAPL".to_string(), "100".parse().unwrap(), 10, TradeSide::Buy, "NASDAQ".to_string()));
+        let fire = |rsi: f64| engine.evaluate(&history, Some(rsi)).len();
+
+        assert_eq!(fire(65.0), 0);
+        assert_eq!(fire(75.0), 1);
+        // Still above: no repeat while the condition holds
+        assert_eq!(fire(80.0), 0);
+        assert_eq!(fire(60.0), 0);
+        assert_eq!(fire(71.0), 1);
+        let fired = engine.fired(None, 10);
+        assert_eq!(fired[0].message, "RSI 71.00 above 70.00");
+        assert_eq!(fired[0].value, 71.0);
+    }
+
+    #[test]
+    fn percent_moves_and_volume_spikes() {
+        let engine = AlertEngine::new();
+        rule(&engine, "AAPL", AlertCondition::PercentMove { percent: 5.0, window_secs: 60 });
+        rule(&engine, "AAPL", AlertCondition::VolumeSpike { multiplier: 3.0, lookback: 2 });
+        let mut history = PriceHistory::new("AAPL".to_string());
+
+        assert!(trade(&engine, &mut history, "100", 100).is_empty());
+        assert!(trade(&engine, &mut history, "102", 100).is_empty());
+        assert_eq!(trade(&engine, &mut history, "106", 100), ["price moved +6.00% in 60s"]);
+        assert_eq!(trade(&engine, &mut history, "106", 300), ["volume 300 is 3.0x the 2-trade average"]);
+    }
+
+    #[test]
+    fn updates_and_disabled_rules() {
+        let engine = AlertEngine::new();
+        let created = rule(&engine, "AAPL", AlertCondition::RsiBelow { threshold: 30.0 });
+        let mut history = PriceHistory::new("AAPL".to_string());
+        history.add_trade(TradeData::new("AAPL".to_string(), "100".parse().unwrap(), 10, TradeSide::Buy, "NASDAQ".to_string()));
+        assert_eq!(engine.evaluate(&history, Some(20.0)).len(), 1);
+
+        let disable = AlertRuleRequest { symbol: "AAPL".to_string(), condition: created.condition.clone(), enabled: false };
+        let updated = engine.update(&created.id, disable).unwrap().unwrap();
+        assert!(!updated.enabled);
+        assert!(engine.evaluate(&history, Some(10.0)).is_empty());
+
+        // Re-enabling starts from scratch, so a condition already holding fires
+        let enable = AlertRuleRequest { symbol: "AAPL".to_string(), condition: created.condition, enabled: true };
+        engine.update(&created.id, enable).unwrap();
+        assert_eq!(engine.evaluate(&history, Some(10.0)).len(), 1);
+
+        assert!(engine.delete(&created.id));
+        assert!(!engine.delete(&created.id));
+        assert!(engine.get(&created.id).is_none());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let invalid = [
+            ("AAPL", AlertCondition::RsiAbove { threshold: 101.0 }, "RSI threshold must be between 0 and 100"),
+            ("AAPL", AlertCondition::PriceCross { level: Decimal::ZERO, direction: CrossDirection::Either }, "price level must be positive"),
+            ("AAPL", AlertCondition::PercentMove { percent: f64::NAN, window_secs: 60 }, "percent must be positive"),
+            ("AAPL", AlertCondition::PercentMove { percent: 1.0, window_secs: 0 }, "window_secs must be greater than zero"),
+            ("AAPL", AlertCondition::VolumeSpike { multiplier: 2.0, lookback: 0 }, "lookback must be greater than zero"),
+            (" ", AlertCondition::RsiAbove { threshold: 70.0 }, "symbol must not be empty"),
+        ];
+        let engine = AlertEngine::new();
+        for (symbol, condition, error) in invalid {
+            let request = AlertRuleRequest { symbol: symbol.to_string(), condition, enabled: true };
+            assert_eq!(engine.create(request).unwrap_err(), error);
+        }
+        assert!(engine.list().is_empty());
+    }
+}
@@ -0,0 +1,472 @@
+use arrow_array::{ArrayRef, Decimal128Array, Float64Array, RecordBatch, StringArray, TimestampNanosecondArray, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::config::ArchiveConfig;
+use crate::consumer::{DataProcessor, MarketEvent};
+use crate::models::{RsiData, TradeData, MAX_PRICE_PRECISION};
+
+/// Suffix of files still being written; together with the leading `.` it
+/// keeps them out of the readers' view until they are complete.
+const IN_PROGRESS_SUFFIX: &str = ".inprogress";
+
+/// Records the archiver may fall behind by before it starts dropping them.
+const QUEUE_CAPACITY: usize = 8192;
+
+/// Rows buffered per file before they are handed to the Parquet writer.
+const BATCH_ROWS: usize = 1024;
+
+const MAX_ROW_GROUP_ROWS: usize = 64 * 1024;
+
+/// Prices are stored as `DECIMAL(28, 8)`, enough for any `Decimal`.
+const PRICE_DIGITS: u8 = 28;
+
+/// How often buffered rows are written out and files checked for rolling.
+const ROLL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+type ArchiveError = Box<dyn std::error::Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Dataset {
+    Trades,
+    Rsi,
+}
+
+impl Dataset {
+    fn name(self) -> &'static str {
+        match self {
+            Dataset::Trades => "trades",
+            Dataset::Rsi => "rsi",
+        }
+    }
+
+    /// Columns mirror the fields of `TradeData` and `RsiData`.
+    fn schema(self) -> SchemaRef {
+        let timestamp = Field::new("timestamp", DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())), false);
+        let fields = match self {
+            Dataset::Trades => vec![
+                Field::new("id", DataType::Utf8, false),
+                Field::new("symbol", DataType::Utf8, false),
+                Field::new("price", DataType::Decimal128(PRICE_DIGITS, MAX_PRICE_PRECISION as i8), false),
+                Field::new("volume", DataType::UInt64, false),
+                timestamp,
+                Field::new("side", DataType::Utf8, false),
+                Field::new("exchange", DataType::Utf8, false),
+            ],
+            Dataset::Rsi => vec![
+                Field::new("id", DataType::Utf8, false),
+                Field::new("symbol", DataType::Utf8, false),
+                Field::new("rsi_value", DataType::Float64, false),
+                timestamp,
+                Field::new("period", DataType::UInt32, false),
+                Field::new("signal", DataType::Utf8, false),
+            ],
+        };
+        Arc::new(Schema::new(fields))
+    }
+}
+
+enum Record {
+    Trade(TradeData),
+    Rsi(RsiData),
+}
+
+impl Record {
+    fn from_event(event: MarketEvent) -> Option<Self> {
+        match event {
+            MarketEvent::Trade(trade) => Some(Record::Trade(trade)),
+            MarketEvent::Rsi(rsi) => Some(Record::Rsi(rsi)),
+            _ => None,
+        }
+    }
+
+    fn partition(&self) -> Partition {
+        let (dataset, symbol, timestamp) = match self {
+            Record::Trade(trade) => (Dataset::Trades, &trade.symbol, trade.timestamp),
+            Record::Rsi(rsi) => (Dataset::Rsi, &rsi.symbol, rsi.timestamp),
+        };
+        Partition {
+            dataset,
+            hour: timestamp.duration_trunc(TimeDelta::hours(1)).unwrap_or(timestamp),
+            symbol: symbol.clone(),
+        }
+    }
+}
+
+/// One hour of one symbol's trades or RSI values.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Partition {
+    dataset: Dataset,
+    hour: DateTime<Utc>,
+    symbol: String,
+}
+
+impl Partition {
+    /// Hive-style `<dataset>/date=YYYY-MM-DD/hour=HH/symbol=<symbol>`.
+    fn dir(&self, root: &Path) -> PathBuf {
+        let symbol: String = self
+            .symbol
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+            .collect();
+        root.join(self.dataset.name())
+            .join(self.hour.format("date=%Y-%m-%d").to_string())
+            .join(self.hour.format("hour=%H").to_string())
+            .join(format!("symbol={}", symbol))
+    }
+}
+
+fn nanos(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt().unwrap_or(i64::MAX)
+}
+
+fn trade_batch(schema: SchemaRef, trades: &[TradeData]) -> Result<RecordBatch, ArchiveError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(trades.iter().map(|trade| trade.id.as_str()))),
+        Arc::new(StringArray::from_iter_values(trades.iter().map(|trade| trade.symbol.as_str()))),
+        Arc::new(
+            Decimal128Array::from_iter_values(trades.iter().map(|trade| {
+                let mut price = trade.price;
+                price.rescale(MAX_PRICE_PRECISION);
+                price.mantissa()
+            }))
+            .with_precision_and_scale(PRICE_DIGITS, MAX_PRICE_PRECISION as i8)?,
+        ),
+        Arc::new(UInt64Array::from_iter_values(trades.iter().map(|trade| trade.volume))),
+        Arc::new(TimestampNanosecondArray::from_iter_values(trades.iter().map(|trade| nanos(trade.timestamp))).with_timezone("UTC")),
+        Arc::new(StringArray::from_iter_values(trades.iter().map(|trade| format!("{:?}", trade.side)))),
+        Arc::new(StringArray::from_iter_values(trades.iter().map(|trade| trade.exchange.as_str()))),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+fn rsi_batch(schema: SchemaRef, values: &[RsiData]) -> Result<RecordBatch, ArchiveError> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(values.iter().map(|rsi| rsi.id.as_str()))),
+        Arc::new(StringArray::from_iter_values(values.iter().map(|rsi| rsi.symbol.as_str()))),
+        Arc::new(Float64Array::from_iter_values(values.iter().map(|rsi| rsi.rsi_value))),
+        Arc::new(TimestampNanosecondArray::from_iter_values(values.iter().map(|rsi| nanos(rsi.timestamp))).with_timezone("UTC")),
+        Arc::new(UInt32Array::from_iter_values(values.iter().map(|rsi| rsi.period))),
+        Arc::new(StringArray::from_iter_values(values.iter().map(|rsi| format!("{:?}", rsi.signal)))),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// A Parquet file being written under its in-progress name.
+struct OpenFile {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    in_progress: PathBuf,
+    path: PathBuf,
+    opened: Instant,
+    trades: Vec<TradeData>,
+    rsi: Vec<RsiData>,
+}
+
+impl OpenFile {
+    fn create(partition: &Partition, root: &Path) -> Result<Self, ArchiveError> {
+        let dir = partition.dir(root);
+        fs::create_dir_all(&dir)?;
+        let name = format!("part-{}.parquet", Uuid::new_v4());
+        let in_progress = dir.join(format!(".{}{}", name, IN_PROGRESS_SUFFIX));
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(MAX_ROW_GROUP_ROWS)
+            .build();
+        let schema = partition.dataset.schema();
+        let writer = ArrowWriter::try_new(File::create(&in_progress)?, schema.clone(), Some(properties))?;
+        Ok(Self {
+            writer,
+            schema,
+            in_progress,
+            path: dir.join(name),
+            opened: Instant::now(),
+            trades: Vec::new(),
+            rsi: Vec::new(),
+        })
+    }
+
+    fn push(&mut self, record: Record) -> Result<(), ArchiveError> {
+        match record {
+            Record::Trade(trade) => self.trades.push(trade),
+            Record::Rsi(rsi) => self.rsi.push(rsi),
+        }
+        if self.trades.len() + self.rsi.len() >= BATCH_ROWS {
+            self.write_buffered()?;
+        }
+        Ok(())
+    }
+
+    fn write_buffered(&mut self) -> Result<(), ArchiveError> {
+        if !self.trades.is_empty() {
+            self.writer.write(&trade_batch(self.schema.clone(), &self.trades)?)?;
+            self.trades.clear();
+        }
+        if !self.rsi.is_empty() {
+            self.writer.write(&rsi_batch(self.schema.clone(), &self.rsi)?)?;
+            self.rsi.clear();
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        (self.writer.bytes_written() + self.writer.in_progress_size()) as u64
+    }
+
+    /// Writes the footer, syncs the file and only then renames it into view,
+    /// so a crash never leaves a truncated file under a final name.
+    fn finalise(mut self) -> Result<PathBuf, ArchiveError> {
+        self.write_buffered()?;
+        let file = self.writer.into_inner()?;
+        file.sync_all()?;
+        fs::rename(&self.in_progress, &self.path)?;
+        if let Some(dir) = self.path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(self.path)
+    }
+}
+
+/// Archives every processed trade and RSI value to Parquet files under
+/// `<path>/<dataset>/date=YYYY-MM-DD/hour=HH/symbol=<symbol>/`, partitioned
+/// by the record's own timestamp. Files are rolled by size and age.
+pub struct ParquetArchiver {
+    root: PathBuf,
+    max_file_bytes: u64,
+    max_file_age: Duration,
+    open: HashMap<Partition, OpenFile>,
+}
+
+impl ParquetArchiver {
+    /// Creates the archive directory and removes files left in progress by a
+    /// previous run, whose rows were never finalised.
+    pub fn new(config: &ArchiveConfig) -> Result<Self, ArchiveError> {
+        let root = PathBuf::from(config.path.as_deref().ok_or("no archive path configured")?);
+        fs::create_dir_all(&root).map_err(|e| format!("failed to create archive directory '{}': {}", root.display(), e))?;
+        let removed = remove_in_progress(&root)?;
+        if removed > 0 {
+            tracing::warn!(files = removed, "removed unfinished archive files from a previous run");
+        }
+        Ok(Self {
+            root,
+            max_file_bytes: config.max_file_bytes,
+            max_file_age: Duration::from_secs(config.max_file_age_secs),
+            open: HashMap::new(),
+        })
+    }
+
+    /// Archives trades and RSI values published by `processor` on a
+    /// dedicated thread, as Parquet encoding and file syncs block. Records are
+    /// fed through a bounded queue; if a slow disk lets it fill, further rows
+    /// are dropped and counted rather than holding up processing.
+    pub fn start(self, processor: &DataProcessor) -> Result<(), ArchiveError> {
+        let records = processor.tap("archive", QUEUE_CAPACITY, |event| matches!(event, MarketEvent::Trade(_) | MarketEvent::Rsi(_)));
+        let runtime = Handle::current();
+        std::thread::Builder::new()
+            .name("parquet-archiver".to_string())
+            .spawn(move || self.run(runtime, records))?;
+        Ok(())
+    }
+
+    fn run(mut self, runtime: Handle, mut records: mpsc::Receiver<MarketEvent>) {
+        let mut next_check = Instant::now() + ROLL_CHECK_INTERVAL;
+        loop {
+            let wait = next_check.saturating_duration_since(Instant::now());
+            match runtime.block_on(tokio::time::timeout(wait, records.recv())) {
+                Ok(Some(event)) => {
+                    if let Some(record) = Record::from_event(event) {
+                        self.append(record);
+                    }
+                }
+                Ok(None) => break,
+                Err(_) => {}
+            }
+            if Instant::now() >= next_check {
+                self.roll(false);
+                next_check = Instant::now() + ROLL_CHECK_INTERVAL;
+            }
+        }
+        self.roll(true);
+    }
+
+    fn append(&mut self, record: Record) {
+        let partition = record.partition();
+        if !self.open.contains_key(&partition) {
+            match OpenFile::create(&partition, &self.root) {
+                Ok(file) => {
+                    self.open.insert(partition.clone(), file);
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, dataset = partition.dataset.name(), symbol = %partition.symbol, "failed to open archive file, record dropped");
+                    return;
+                }
+            }
+        }
+        if let Some(file) = self.open.get_mut(&partition) {
+            if let Err(e) = file.push(record) {
+                tracing::error!(error = %e, path = %file.in_progress.display(), "failed to write archive rows");
+            }
+        }
+    }
+
+    /// Writes buffered rows and finalises files that are big or old enough,
+    /// or every file when `all` is set.
+    fn roll(&mut self, all: bool) {
+        let mut finished = Vec::new();
+        for (partition, file) in &mut self.open {
+            if let Err(e) = file.write_buffered() {
+                tracing::error!(error = %e, path = %file.in_progress.display(), "failed to write archive rows");
+            }
+            if all || file.size() >= self.max_file_bytes || file.opened.elapsed() >= self.max_file_age {
+                finished.push(partition.clone());
+            }
+        }
+        for partition in finished {
+            let Some(file) = self.open.remove(&partition) else { continue };
+            let in_progress = file.in_progress.clone();
+            match file.finalise() {
+                Ok(path) => tracing::debug!(path = %path.display(), "finalised archive file"),
+                Err(e) => tracing::error!(error = %e, path = %in_progress.display(), "failed to finalise archive file"),
+            }
+        }
+    }
+}
+
+fn remove_in_progress(dir: &Path) -> Result<usize, ArchiveError> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            removed += remove_in_progress(&path)?;
+        } else if path.to_string_lossy().ends_with(IN_PROGRESS_SUFFIX) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Decimal, TradeSide};
+    use chrono::TimeZone;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    fn archiver(max_file_bytes: u64, max_file_age_secs: u64) -> (ParquetArchiver, PathBuf) {
+        let root = std::env::temp_dir().join(format!("archive-{}", Uuid::new_v4()));
+        let config = ArchiveConfig { path: Some(root.display().to_string()), max_file_bytes, max_file_age_secs };
+        (ParquetArchiver::new(&config).unwrap(), root)
+    }
+
+    fn trade(symbol: &str, timestamp: DateTime<Utc>) -> Record {
+        let mut trade = TradeData::new(symbol.to_string(), Decimal::new(15025, 2), 10, TradeSide::Buy, "NASDAQ".to_string());
+        trade.timestamp = timestamp;
+        Record::Trade(trade)
+    }
+
+    /// Every file under `dir`, relative to it, sorted.
+    fn files(dir: &Path) -> Vec<String> {
+        fn walk(dir: &Path, found: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    walk(&path, found);
+                } else {
+                    found.push(path);
+                }
+            }
+        }
+        let mut found = Vec::new();
+        walk(dir, &mut found);
+        let mut names: Vec<String> = found.iter().map(|path| path.strip_prefix(dir).unwrap().display().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    fn rows(path: &Path) -> i64 {
+        SerializedFileReader::new(File::open(path).unwrap()).unwrap().metadata().file_metadata().num_rows()
+    }
+
+    #[test]
+    fn records_are_partitioned_by_dataset_hour_and_symbol() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 5, 14, 37, 0).unwrap();
+        assert_eq!(
+            trade("BRK/A", at).partition().dir(Path::new("/archive")),
+            Path::new("/archive/trades/date=2024-03-05/hour=14/symbol=BRK_A"),
+        );
+        let rsi = Record::Rsi(RsiData::new("AAPL".to_string(), 55.0, 14).with_timestamp(at));
+        assert_eq!(rsi.partition().dir(Path::new("/archive")), Path::new("/archive/rsi/date=2024-03-05/hour=14/symbol=AAPL"));
+        assert_eq!(trade("AAPL", at).partition().hour, Utc.with_ymd_and_hms(2024, 3, 5, 14, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn files_stay_in_progress_until_rolled_by_size() {
+        let (mut archiver, root) = archiver(1, 3600);
+        let at = Utc.with_ymd_and_hms(2024, 3, 5, 14, 37, 0).unwrap();
+        for _ in 0..3 {
+            archiver.append(trade("AAPL", at));
+        }
+        archiver.append(trade("AAPL", at + TimeDelta::hours(1)));
+
+        let open = files(&root);
+        assert_eq!(open.len(), 2);
+        assert!(open.iter().all(|name| name.ends_with(IN_PROGRESS_SUFFIX)));
+
+        archiver.roll(false);
+        let finalised = files(&root);
+        assert_eq!(finalised.len(), 2);
+        assert!(finalised[0].starts_with("trades/date=2024-03-05/hour=14/symbol=AAPL/part-"));
+        assert!(finalised[1].starts_with("trades/date=2024-03-05/hour=15/symbol=AAPL/part-"));
+        assert!(finalised.iter().all(|name| name.ends_with(".parquet")));
+        assert_eq!(rows(&root.join(&finalised[0])), 3);
+        assert_eq!(rows(&root.join(&finalised[1])), 1);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn files_roll_by_age_or_on_shutdown() {
+        let at = Utc.with_ymd_and_hms(2024, 3, 5, 14, 37, 0).unwrap();
+        let (mut aged, root) = archiver(u64::MAX, 0);
+        aged.append(trade("AAPL", at));
+        aged.roll(false);
+        assert!(files(&root).iter().all(|name| !name.ends_with(IN_PROGRESS_SUFFIX)));
+        fs::remove_dir_all(root).unwrap();
+
+        let (mut young, root) = archiver(u64::MAX, 3600);
+        young.append(trade("AAPL", at));
+        young.append(trade("MSFT", at));
+        young.roll(false);
+        assert!(files(&root).iter().all(|name| name.ends_with(IN_PROGRESS_SUFFIX)));
+        young.roll(true);
+        let finalised = files(&root);
+        assert_eq!(finalised.len(), 2);
+        assert!(finalised.iter().all(|name| name.ends_with(".parquet")));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn unfinished_files_from_a_previous_run_are_removed() {
+        let (_, root) = archiver(1, 3600);
+        let dir = root.join("trades/date=2024-03-05/hour=14/symbol=AAPL");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!(".part-1.parquet{}", IN_PROGRESS_SUFFIX)), b"partial").unwrap();
+        fs::write(dir.join("part-2.parquet"), b"complete").unwrap();
+
+        let config = ArchiveConfig { path: Some(root.display().to_string()), max_file_bytes: 1, max_file_age_secs: 3600 };
+        ParquetArchiver::new(&config).unwrap();
+        assert_eq!(files(&root), ["trades/date=2024-03-05/hour=14/symbol=AAPL/part-2.parquet"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,15 +1,53 @@
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use chrono::{DateTime, Utc};
 
-use crate::models::{TradeData, RsiData};
+use crate::consumer::{
+    candle_time, rsi_time, trade_time, AlertEngine, ConsumerHealth, ConsumerMetrics, EventBus, HistoryQuery, MarketEvent, OutlierFilter, Page,
+    PriceUpdate, RsiSnapshot, SignalChange, StreamEvent, SymbolSummary,
+};
+use crate::config::OutlierConfig;
+#[cfg(feature = "sqlite")]
+use crate::consumer::{HistoryStore, StoredRecord};
+#[cfg(feature = "sqlite")]
+use crate::models::OutlierStatus;
+use crate::models::{price_to_f64, Candle, Decimal, PriceSpecs, TradeData, RsiData, RsiSignal};
+
+/// Width of the candles built from incoming trades.
+pub const CANDLE_INTERVAL_SECS: u32 = 60;
+
+/// Capacity of the event channel; subscribers further behind than this lag.
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Number of recent events kept for clients resuming a stream.
+const EVENT_REPLAY_CAPACITY: usize = 2048;
+
+/// Number of trades and RSI values kept per symbol for the history endpoints.
+pub const HISTORY_CAPACITY: usize = 1000;
+
+/// Number of candles kept per symbol.
+const CANDLE_CAPACITY: usize = 500;
+
+/// Number of prices the RSI is calculated from.
+pub const PRICE_CAPACITY: usize = 100;
 
 #[derive(Debug, Clone)]
 pub struct PriceHistory {
     pub symbol: String,
-    pub prices: Vec<f64>,
+    pub prices: Vec<Decimal>,
     pub timestamps: Vec<DateTime<Utc>>,
+    /// Recent candles, oldest first; the last one may still be open.
+    pub candles: Vec<Candle>,
+    /// Recent trades and RSI values, oldest first.
+    pub trades: Vec<TradeData>,
+    pub rsi_history: Vec<RsiData>,
+    /// Suspected outliers awaiting confirmation, oldest first.
+    pub quarantine: Vec<TradeData>,
+    pub total_volume: u64,
+    pub total_notional: Decimal,
+    pub trade_count: u64,
+    pub last_signal: Option<RsiSignal>,
 }
 
 impl PriceHistory {
@@ -18,20 +56,82 @@ impl PriceHistory {
             symbol,
             prices: Vec::new(),
             timestamps: Vec::new(),
+            candles: Vec::new(),
+            trades: Vec::new(),
+            rsi_history: Vec::new(),
+            quarantine: Vec::new(),
+            total_volume: 0,
+            total_notional: Decimal::ZERO,
+            trade_count: 0,
+            last_signal: None,
+        }
+    }
+
+    pub fn add_trade(&mut self, trade: TradeData) {
+        self.total_volume = self.total_volume.saturating_add(trade.volume);
+        self.total_notional = self.total_notional.saturating_add(trade.notional().unwrap_or(Decimal::MAX));
+        self.trade_count += 1;
+        self.trades.push(trade);
+        if self.trades.len() > HISTORY_CAPACITY {
+            self.trades.remove(0);
+        }
+    }
+
+    pub fn add_rsi(&mut self, rsi: RsiData) {
+        self.rsi_history.push(rsi);
+        if self.rsi_history.len() > HISTORY_CAPACITY {
+            self.rsi_history.remove(0);
+        }
+    }
+
+    pub fn summary(&self) -> SymbolSummary {
+        SymbolSummary {
+            symbol: self.symbol.clone(),
+            last_price: self.prices.last().copied(),
+            last_trade_time: self.timestamps.last().copied(),
+            volume: self.total_volume,
+            notional: self.total_notional,
+            trade_count: self.trade_count,
+            quarantined: self.quarantine.len(),
+            rsi: self.rsi_history.last().map(RsiSnapshot::from),
         }
     }
 
-    pub fn add_price(&mut self, price: f64, timestamp: DateTime<Utc>) {
+    /// Adds a trade to the current candle, opening a new one when the trade
+    /// falls outside it. Returns the candle the trade landed in.
+    pub fn add_to_candle(&mut self, price: Decimal, volume: u64, timestamp: DateTime<Utc>) -> &Candle {
+        let in_current = self.candles.last().is_some_and(|candle| candle.contains(timestamp));
+        if in_current {
+            if let Some(candle) = self.candles.last_mut() {
+                candle.update(price, volume);
+            }
+        } else {
+            if let Some(candle) = self.candles.last_mut() {
+                candle.closed = true;
+            }
+            self.candles.push(Candle::open(self.symbol.clone(), CANDLE_INTERVAL_SECS, price, volume, timestamp));
+
+            // Keep only the last candles for memory efficiency
+            if self.candles.len() > CANDLE_CAPACITY {
+                self.candles.remove(0);
+            }
+        }
+        self.candles.last().expect("candle was just added")
+    }
+
+    pub fn add_price(&mut self, price: Decimal, timestamp: DateTime<Utc>) {
         self.prices.push(price);
         self.timestamps.push(timestamp);
         
-        // Keep only last 100 prices for memory efficiency
-        if self.prices.len() > 100 {
+        // Keep only the last prices for memory efficiency
+        if self.prices.len() > PRICE_CAPACITY {
             self.prices.remove(0);
             self.timestamps.remove(0);
         }
     }
 
+    /// Price changes are taken exactly and only then converted to `f64`,
+    /// since RSI is a ratio of averages.
     pub fn calculate_rsi(&self, period: usize) -> Option<f64> {
         if self.prices.len() < period + 1 {
             return None;
@@ -41,7 +141,7 @@ impl PriceHistory {
         let mut losses = Vec::new();
 
         for i in 1..self.prices.len() {
-            let change = self.prices[i] - self.prices[i - 1];
+            let change = price_to_f64(self.prices[i] - self.prices[i - 1]);
             if change > 0.0 {
                 gains.push(change);
                 losses.push(0.0);
@@ -70,32 +170,219 @@ impl PriceHistory {
     }
 }
 
+/// A bounded feed of the events `wants` selects, for a sink that should
+/// not miss any.
+struct EventTap {
+    /// Label for the dropped-events metric.
+    name: &'static str,
+    sender: mpsc::Sender<MarketEvent>,
+    wants: fn(&MarketEvent) -> bool,
+    /// Whether the last event was dropped, so a backlog is logged once.
+    overflowing: bool,
+}
+
 #[derive(Clone)]
 pub struct DataProcessor {
     price_histories: Arc<RwLock<HashMap<String, PriceHistory>>>,
+    events: EventBus,
+    taps: Arc<Mutex<Vec<EventTap>>>,
+    alerts: AlertEngine,
+    metrics: ConsumerMetrics,
+    health: ConsumerHealth,
+    price_specs: Arc<PriceSpecs>,
+    outliers: OutlierFilter,
+    #[cfg(feature = "sqlite")]
+    store: Option<HistoryStore>,
+}
+
+impl Default for DataProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DataProcessor {
     pub fn new() -> Self {
         Self {
             price_histories: Arc::new(RwLock::new(HashMap::new())),
+            events: EventBus::new(EVENT_CHANNEL_CAPACITY, EVENT_REPLAY_CAPACITY),
+            taps: Arc::default(),
+            alerts: AlertEngine::new(),
+            metrics: ConsumerMetrics::new(),
+            health: ConsumerHealth::new(),
+            price_specs: Arc::default(),
+            outliers: OutlierFilter::default(),
+            #[cfg(feature = "sqlite")]
+            store: None,
         }
     }
 
-    pub async fn process_trade_data(&self, trade_data: TradeData) {
-        let symbol = trade_data.symbol.clone();
-        let price = trade_data.price;
-        let timestamp = trade_data.timestamp;
+    /// Writes incoming prices with their symbol's precision.
+    pub fn with_price_specs(mut self, specs: PriceSpecs) -> Self {
+        self.price_specs = Arc::new(specs);
+        self
+    }
 
-        // Update price history
+    /// Screens trades for outliers with `config` before they reach candles,
+    /// RSI and alerts.
+    pub fn with_outliers(mut self, config: &OutlierConfig) -> Self {
+        self.outliers = OutlierFilter::new(config);
+        self
+    }
+
+    /// Rebuilds the recent history of every symbol from `store`, then persists
+    /// everything processed from now on to it and serves history from it.
+    /// Must be called before the processor is cloned.
+    #[cfg(feature = "sqlite")]
+    pub async fn with_store(mut self, store: HistoryStore) -> Result<Self, String> {
+        let restored = store.restore(HISTORY_CAPACITY).await?;
+        {
+            let mut histories = self.price_histories.write().await;
+            for (symbol, stored) in restored {
+                let mut history = PriceHistory::new(symbol.clone());
+                let accepted: Vec<&TradeData> = stored.trades.iter().filter(|trade| !trade.is_outlier()).collect();
+                for trade in &accepted[accepted.len().saturating_sub(PRICE_CAPACITY)..] {
+                    history.add_price(trade.price, trade.timestamp);
+                }
+                // Suspects after the last accepted trade are still awaiting confirmation
+                let pending = stored.trades.iter().rev().take_while(|trade| trade.outlier == Some(OutlierStatus::Suspect)).count();
+                history.quarantine = stored.trades[stored.trades.len() - pending..].to_vec();
+                let recent_candles = stored.candles.len().saturating_sub(CANDLE_CAPACITY);
+                history.candles = stored.candles[recent_candles..].to_vec();
+                history.trades = stored.trades;
+                history.last_signal = stored.rsi.last().map(|rsi| rsi.signal);
+                history.rsi_history = stored.rsi;
+                history.trade_count = stored.trade_count;
+                history.total_volume = stored.total_volume;
+                history.total_notional = stored.total_notional;
+                histories.insert(symbol, history);
+            }
+            self.metrics.set_tracked_symbols(histories.len());
+            tracing::info!(symbols = histories.len(), "restored history from the store");
+        }
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    /// Subscribes to the updates published for every processed trade.
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamEvent> {
+        self.events.subscribe()
+    }
+
+    /// Feeds the events `wants` selects to the returned receiver, which can
+    /// fall `capacity` events behind, rather than the broadcast's shared
+    /// window, before losing any. Processing never waits for it: events
+    /// that do not fit are dropped and counted under `name`.
+    pub fn tap(&self, name: &'static str, capacity: usize, wants: fn(&MarketEvent) -> bool) -> mpsc::Receiver<MarketEvent> {
+        let (sender, receiver) = mpsc::channel(capacity);
+        self.taps.lock().unwrap().push(EventTap { name, sender, wants, overflowing: false });
+        receiver
+    }
+
+    /// Recently published events after `last_id`; see [`EventBus::replay_since`].
+    pub fn replay_since(&self, last_id: u64) -> Option<Vec<StreamEvent>> {
+        self.events.replay_since(last_id)
+    }
+
+    /// Alert rules evaluated against every processed trade.
+    pub fn alerts(&self) -> &AlertEngine {
+        &self.alerts
+    }
+
+    /// Metrics shared with the Kafka consumer and the API.
+    pub fn metrics(&self) -> &ConsumerMetrics {
+        &self.metrics
+    }
+
+    /// Liveness and readiness state, updated by the Kafka consumer.
+    pub fn health(&self) -> &ConsumerHealth {
+        &self.health
+    }
+
+    fn publish(&self, event: MarketEvent) {
         {
+            let mut taps = self.taps.lock().unwrap();
+            taps.retain(|tap| !tap.sender.is_closed());
+            for tap in taps.iter_mut().filter(|tap| (tap.wants)(&event)) {
+                match tap.sender.try_send(event.clone()) {
+                    Ok(()) => tap.overflowing = false,
+                    Err(mpsc::error::TrySendError::Full(_)) => {
+                        self.metrics.record_sink_dropped(tap.name);
+                        if !tap.overflowing {
+                            tracing::warn!(sink = tap.name, "sink is falling behind, dropping events");
+                        }
+                        tap.overflowing = true;
+                    }
+                    // The receiver is gone; the tap is dropped next time
+                    Err(mpsc::error::TrySendError::Closed(_)) => {}
+                }
+            }
+        }
+        self.events.publish(event);
+    }
+
+    pub async fn process_trade_data(&self, mut trade_data: TradeData) {
+        let symbol = trade_data.symbol.clone();
+        let spec = self.price_specs.get(&symbol);
+        trade_data.price = spec.normalize(trade_data.price);
+
+        // Screen for outliers, then update price history and candles with
+        // whatever the screening released
+        let (screening, candles) = {
             let mut histories = self.price_histories.write().await;
             let history = histories.entry(symbol.clone()).or_insert_with(|| {
                 PriceHistory::new(symbol.clone())
             });
-            history.add_price(price, timestamp);
+            let screening = self.outliers.screen(history, spec.tick_size, trade_data);
+            history.add_trade(screening.trade.clone());
+            let candles: Vec<Candle> = screening
+                .released
+                .iter()
+                .map(|trade| {
+                    history.add_price(trade.price, trade.timestamp);
+                    history.add_to_candle(trade.price, trade.volume, trade.timestamp).clone()
+                })
+                .collect();
+            self.metrics.set_tracked_symbols(histories.len());
+            (screening, candles)
+        };
+
+        let flagged = screening.trade.outlier.iter().chain(screening.resolved.iter().filter_map(|trade| trade.outlier.as_ref()));
+        for status in flagged {
+            self.metrics.record_outlier(&symbol, status.as_str());
+        }
+        if screening.released.is_empty() {
+            tracing::info!(%symbol, price = %screening.trade.price, "trade quarantined as a suspected outlier");
+        }
+        for trade in &screening.resolved {
+            tracing::info!(%symbol, price = %trade.price, status = ?trade.outlier, "suspected outlier resolved");
+        }
+
+        #[cfg(feature = "sqlite")]
+        if let Some(store) = &self.store {
+            for trade in screening.resolved.iter().chain([&screening.trade]) {
+                store.save(StoredRecord::Trade(trade.clone())).await;
+            }
+            for candle in &candles {
+                store.save(StoredRecord::Candle(candle.clone())).await;
+            }
+        }
+
+        self.publish(MarketEvent::Trade(screening.trade));
+        for (trade, candle) in screening.released.iter().zip(candles) {
+            self.publish(MarketEvent::Price(PriceUpdate {
+                symbol: symbol.clone(),
+                price: trade.price,
+                timestamp: trade.timestamp,
+            }));
+            self.publish(MarketEvent::Candle(candle));
         }
 
+        // Quarantined trades leave indicators and alerts untouched
+        let Some(timestamp) = screening.released.last().map(|trade| trade.timestamp) else {
+            return;
+        };
+
         // Calculate RSI if we have enough data
         let rsi = {
             let histories = self.price_histories.read().await;
@@ -107,16 +394,49 @@ impl DataProcessor {
         };
 
         if let Some(rsi_value) = rsi {
-            let rsi_data = RsiData::new(symbol.clone(), rsi_value, 14);
-            println!("📈 RSI calculated for {}: {:.2} ({:?})", 
-                symbol, rsi_value, rsi_data.signal);
-            
-            // Here we would send RSI data to the RSI topic
-            // For now, we'll just log it
+            self.metrics.record_rsi_computation(&symbol);
+            let rsi_data = RsiData::new(symbol.clone(), rsi_value, 14).with_timestamp(timestamp);
+            tracing::debug!(%symbol, rsi = rsi_value, signal = ?rsi_data.signal, "RSI calculated");
+
+            let previous = {
+                let mut histories = self.price_histories.write().await;
+                histories.get_mut(&symbol).and_then(|history| {
+                    history.add_rsi(rsi_data.clone());
+                    history.last_signal.replace(rsi_data.signal)
+                })
+            };
+            if previous != Some(rsi_data.signal) {
+                self.publish(MarketEvent::Signal(SignalChange {
+                    symbol: symbol.clone(),
+                    previous,
+                    signal: rsi_data.signal,
+                    rsi_value,
+                    timestamp: rsi_data.timestamp,
+                }));
+            }
+
+            #[cfg(feature = "sqlite")]
+            if let Some(store) = &self.store {
+                store.save(StoredRecord::Rsi(rsi_data.clone())).await;
+            }
+
+            self.publish(MarketEvent::Rsi(rsi_data));
+        }
+
+        let fired = {
+            let histories = self.price_histories.read().await;
+            histories
+                .get(&symbol)
+                .map(|history| self.alerts.evaluate(history, rsi))
+                .unwrap_or_default()
+        };
+        for alert in fired {
+            tracing::info!(rule_id = %alert.rule_id, symbol = %alert.symbol, "alert fired: {}", alert);
+            self.publish(MarketEvent::Alert(alert));
         }
     }
 
-    pub async fn get_latest_prices(&self) -> HashMap<String, f64> {
+    pub async fn get_latest_prices(&self) -> HashMap<String, Decimal> {
         let histories = self.price_histories.read().await;
         histories.iter()
             .filter_map(|(symbol, history)| {
@@ -125,12 +445,115 @@ impl DataProcessor {
             .collect()
     }
 
-    pub async fn get_latest_rsi(&self) -> HashMap<String, f64> {
+    /// Latest RSI reading per symbol, sorted by symbol.
+    pub async fn get_latest_rsi(&self) -> Vec<RsiData> {
         let histories = self.price_histories.read().await;
-        histories.iter()
-            .filter_map(|(symbol, history)| {
-                history.calculate_rsi(14).map(|rsi| (symbol.clone(), rsi))
-            })
-            .collect()
+        let mut latest: Vec<RsiData> = histories
+            .values()
+            .filter_map(|history| history.rsi_history.last().cloned())
+            .collect();
+        latest.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        latest
+    }
+
+    /// Summaries of every tracked symbol, sorted by symbol.
+    pub async fn get_symbols(&self) -> Vec<SymbolSummary> {
+        let histories = self.price_histories.read().await;
+        let mut symbols: Vec<SymbolSummary> = histories.values().map(PriceHistory::summary).collect();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        symbols
+    }
+
+    pub async fn get_symbol(&self, symbol: &str) -> Option<SymbolSummary> {
+        let histories = self.price_histories.read().await;
+        histories.get(symbol).map(PriceHistory::summary)
+    }
+
+    /// Returns `None` for symbols that have not traded yet.
+    pub async fn get_trades(&self, symbol: &str, query: &HistoryQuery) -> Option<Page<TradeData>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(store) = &self.store {
+            match store.trades(symbol, query).await {
+                Ok(page) => return page,
+                Err(e) => tracing::error!(error = %e, "failed to read trades from the store, serving recent ones from memory"),
+            }
+        }
+        let histories = self.price_histories.read().await;
+        histories.get(symbol).map(|history| query.paginate(&history.trades, trade_time))
+    }
+
+    pub async fn get_rsi_history(&self, symbol: &str, query: &HistoryQuery) -> Option<Page<RsiData>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(store) = &self.store {
+            match store.rsi_history(symbol, query).await {
+                Ok(page) => return page,
+                Err(e) => tracing::error!(error = %e, "failed to read RSI history from the store, serving recent ones from memory"),
+            }
+        }
+        let histories = self.price_histories.read().await;
+        histories.get(symbol).map(|history| query.paginate(&history.rsi_history, rsi_time))
+    }
+
+    pub async fn get_candles(&self, symbol: &str, query: &HistoryQuery) -> Option<Page<Candle>> {
+        #[cfg(feature = "sqlite")]
+        if let Some(store) = &self.store {
+            match store.candles(symbol, query).await {
+                Ok(page) => return page,
+                Err(e) => tracing::error!(error = %e, "failed to read candles from the store, serving recent ones from memory"),
+            }
+        }
+        let histories = self.price_histories.read().await;
+        histories.get(symbol).map(|history| query.paginate(&history.candles, candle_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TradeSide;
+
+    #[tokio::test]
+    async fn a_stalled_sink_does_not_hold_up_processing() {
+        let processor = DataProcessor::new();
+        // Never read, like a sink stuck on a slow disk or endpoint
+        let mut stalled = processor.tap("archive", 4, |event| matches!(event, MarketEvent::Trade(_)));
+        let mut keeping_up = processor.tap("webhooks", 256, |event| matches!(event, MarketEvent::Trade(_)));
+
+        let feeding = async {
+            for volume in 1..=100 {
+                let trade = TradeData::new("AAPL".to_string(), Decimal::from(100), volume, TradeSide::Buy, "NASDAQ".to_string());
+                processor.process_trade_data(trade).await;
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), feeding).await.expect("processing stalled on a full sink");
+        assert_eq!(processor.get_symbol("AAPL").await.unwrap().trade_count, 100);
+
+        let mut volumes = Vec::new();
+        while let Ok(MarketEvent::Trade(trade)) = stalled.try_recv() {
+            volumes.push(trade.volume);
+        }
+        assert_eq!(volumes, [1, 2, 3, 4]);
+        let mut received = 0;
+        while keeping_up.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, 100);
+        let rendered = processor.metrics().render();
+        assert!(rendered.contains(r#"consumer_sink_dropped_total{sink="archive"} 96"#), "{}", rendered);
+        assert!(!rendered.contains(r#"sink="webhooks""#));
+    }
+
+    #[test]
+    fn totals_saturate_instead_of_overflowing() {
+        let mut history = PriceHistory::new("AAPL".to_string());
+        let huge = Decimal::MAX / Decimal::TWO;
+        for _ in 0..2 {
+            let trade = TradeData::new("AAPL".to_string(), huge, u64::MAX, TradeSide::Buy, "NASDAQ".to_string());
+            history.add_to_candle(trade.price, trade.volume, trade.timestamp);
+            history.add_trade(trade);
+        }
+        assert_eq!((history.total_volume, history.total_notional), (u64::MAX, Decimal::MAX));
+        let candle = history.candles.last().unwrap();
+        assert_eq!((candle.volume, candle.notional, candle.trade_count), (u64::MAX, Decimal::MAX, 2));
     }
 }
@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::consumer::FiredAlert;
+use crate::models::{Candle, Decimal, RsiData, RsiSignal, TradeData};
+
+/// Update published by [`DataProcessor`](crate::consumer::DataProcessor) for
+/// every processed trade, fanned out to streaming clients.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "channel", content = "data", rename_all = "snake_case")]
+pub enum MarketEvent {
+    #[serde(rename = "trades")]
+    Trade(TradeData),
+    #[serde(rename = "prices")]
+    Price(PriceUpdate),
+    Rsi(RsiData),
+    #[serde(rename = "candles")]
+    Candle(Candle),
+    #[serde(rename = "signals")]
+    Signal(SignalChange),
+    #[serde(rename = "alerts")]
+    Alert(FiredAlert),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceUpdate {
+    pub symbol: String,
+    pub price: Decimal,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Emitted when a symbol's RSI signal differs from its previous value.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignalChange {
+    pub symbol: String,
+    pub previous: Option<RsiSignal>,
+    pub signal: RsiSignal,
+    pub rsi_value: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Trades,
+    Prices,
+    Rsi,
+    Candles,
+    Signals,
+    Alerts,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 6] = [
+        Channel::Trades,
+        Channel::Prices,
+        Channel::Rsi,
+        Channel::Candles,
+        Channel::Signals,
+        Channel::Alerts,
+    ];
+
+    /// Whether each update supersedes the previous one for its symbol, so a
+    /// client that falls behind only needs the latest.
+    pub fn is_state(&self) -> bool {
+        matches!(self, Channel::Prices | Channel::Rsi | Channel::Candles)
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "trades" => Ok(Channel::Trades),
+            "prices" => Ok(Channel::Prices),
+            "rsi" => Ok(Channel::Rsi),
+            "candles" => Ok(Channel::Candles),
+            "signals" => Ok(Channel::Signals),
+            "alerts" => Ok(Channel::Alerts),
+            other => Err(format!("unknown channel '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Channel::Trades => "trades",
+            Channel::Prices => "prices",
+            Channel::Rsi => "rsi",
+            Channel::Candles => "candles",
+            Channel::Signals => "signals",
+            Channel::Alerts => "alerts",
+        })
+    }
+}
+
+impl MarketEvent {
+    pub fn channel(&self) -> Channel {
+        match self {
+            MarketEvent::Trade(_) => Channel::Trades,
+            MarketEvent::Price(_) => Channel::Prices,
+            MarketEvent::Rsi(_) => Channel::Rsi,
+            MarketEvent::Candle(_) => Channel::Candles,
+            MarketEvent::Signal(_) => Channel::Signals,
+            MarketEvent::Alert(_) => Channel::Alerts,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            MarketEvent::Trade(trade) => &trade.symbol,
+            MarketEvent::Price(price) => &price.symbol,
+            MarketEvent::Rsi(rsi) => &rsi.symbol,
+            MarketEvent::Candle(candle) => &candle.symbol,
+            MarketEvent::Signal(signal) => &signal.symbol,
+            MarketEvent::Alert(alert) => &alert.symbol,
+        }
+    }
+}
+
+/// A [`MarketEvent`] tagged with a process-wide, strictly increasing id.
+#[derive(Debug, Clone)]
+pub struct StreamEvent {
+    pub id: u64,
+    pub event: MarketEvent,
+}
+
+/// Fans events out to live subscribers and keeps the most recent ones so
+/// reconnecting clients can resume from the last id they saw.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<StreamEvent>,
+    replay: Arc<Mutex<ReplayBuffer>>,
+}
+
+struct ReplayBuffer {
+    events: VecDeque<StreamEvent>,
+    capacity: usize,
+    next_id: u64,
+}
+
+impl EventBus {
+    pub fn new(channel_capacity: usize, replay_capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(channel_capacity);
+        Self {
+            sender,
+            replay: Arc::new(Mutex::new(ReplayBuffer {
+                events: VecDeque::with_capacity(replay_capacity),
+                capacity: replay_capacity,
+                next_id: 1,
+            })),
+        }
+    }
+
+    pub fn publish(&self, event: MarketEvent) {
+        // Hold the lock while sending so ids reach subscribers in order
+        let mut replay = self.replay.lock().unwrap();
+        let event = StreamEvent { id: replay.next_id, event };
+        replay.next_id += 1;
+        if replay.events.len() == replay.capacity {
+            replay.events.pop_front();
+        }
+        replay.events.push_back(event.clone());
+
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Events published after `last_id`, or `None` if some of them have
+    /// already been evicted from the replay buffer.
+    pub fn replay_since(&self, last_id: u64) -> Option<Vec<StreamEvent>> {
+        let replay = self.replay.lock().unwrap();
+        let oldest = replay.events.front().map(|event| event.id).unwrap_or(replay.next_id);
+        if last_id + 1 < oldest {
+            return None;
+        }
+        Some(replay.events.iter().filter(|event| event.id > last_id).cloned().collect())
+    }
+}
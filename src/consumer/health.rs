@@ -0,0 +1,299 @@
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+/// The consume loop polls at least every second; this long without a poll
+/// means it is stuck.
+const LOOP_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const DEFAULT_MAX_LAG: i64 = 1000;
+pub const DEFAULT_MAX_IDLE: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ComponentStatus {
+    pub healthy: bool,
+    pub detail: String,
+}
+
+impl ComponentStatus {
+    fn new(healthy: bool, detail: String) -> Self {
+        Self { healthy, detail }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HealthReport {
+    pub status: &'static str,
+    pub timestamp: DateTime<Utc>,
+    pub components: BTreeMap<&'static str, ComponentStatus>,
+}
+
+impl HealthReport {
+    fn new(ok: &'static str, failed: &'static str, components: BTreeMap<&'static str, ComponentStatus>) -> Self {
+        let healthy = components.values().all(|component| component.healthy);
+        Self {
+            status: if healthy { ok } else { failed },
+            timestamp: Utc::now(),
+            components,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.components.values().all(|component| component.healthy)
+    }
+}
+
+#[derive(Debug)]
+struct HealthState {
+    max_lag: i64,
+    max_idle: Duration,
+    loop_stopped: Option<String>,
+    last_poll: Option<Instant>,
+    last_message: Option<Instant>,
+    /// Result of the last metadata request: broker count or error.
+    broker: Option<Result<usize, String>>,
+    assigned_partitions: Vec<(String, i32)>,
+    max_lag_seen: Option<i64>,
+    restored: bool,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        Self {
+            max_lag: DEFAULT_MAX_LAG,
+            max_idle: DEFAULT_MAX_IDLE,
+            loop_stopped: None,
+            last_poll: None,
+            last_message: None,
+            broker: None,
+            assigned_partitions: Vec::new(),
+            max_lag_seen: None,
+            restored: false,
+        }
+    }
+}
+
+/// Tracks what liveness and readiness are derived from; updated by the
+/// consume loop and read by `/health/live` and `/health/ready`.
+#[derive(Debug, Clone, Default)]
+pub struct ConsumerHealth {
+    state: Arc<Mutex<HealthState>>,
+}
+
+impl ConsumerHealth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum partition lag and message silence still considered ready.
+    pub fn set_thresholds(&self, max_lag: i64, max_idle: Duration) {
+        self.update(|state| {
+            state.max_lag = max_lag;
+            state.max_idle = max_idle;
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut HealthState)) {
+        f(&mut self.state.lock().unwrap());
+    }
+
+    pub fn record_poll(&self) {
+        self.update(|state| state.last_poll = Some(Instant::now()));
+    }
+
+    pub fn record_message(&self) {
+        self.update(|state| state.last_message = Some(Instant::now()));
+    }
+
+    pub fn record_stopped(&self, reason: String) {
+        self.update(|state| state.loop_stopped = Some(reason));
+    }
+
+    pub fn record_broker(&self, result: Result<usize, String>) {
+        self.update(|state| state.broker = Some(result));
+    }
+
+    pub fn record_assignment(&self, partitions: Vec<(String, i32)>) {
+        self.update(|state| state.assigned_partitions = partitions);
+    }
+
+    pub fn record_max_lag(&self, max_lag: Option<i64>) {
+        self.update(|state| state.max_lag_seen = max_lag);
+    }
+
+    /// Marks startup state restoration as finished.
+    pub fn mark_restored(&self) {
+        self.update(|state| state.restored = true);
+    }
+
+    fn consume_loop(state: &HealthState) -> ComponentStatus {
+        if let Some(reason) = &state.loop_stopped {
+            return ComponentStatus::new(false, format!("stopped: {}", reason));
+        }
+        match state.last_poll {
+            // Not failing liveness while the consumer is still starting up
+            None => ComponentStatus::new(true, "not started yet".to_string()),
+            Some(last_poll) if last_poll.elapsed() > LOOP_STALL_TIMEOUT => ComponentStatus::new(
+                false,
+                format!("no poll for {}s", last_poll.elapsed().as_secs()),
+            ),
+            Some(last_poll) => ComponentStatus::new(
+                true,
+                format!("last poll {}ms ago", last_poll.elapsed().as_millis()),
+            ),
+        }
+    }
+
+    /// Whether the process should be restarted: only a dead or stuck consume loop counts.
+    pub fn liveness(&self) -> HealthReport {
+        let state = self.state.lock().unwrap();
+        let components = BTreeMap::from([("consume_loop", Self::consume_loop(&state))]);
+        HealthReport::new("alive", "dead", components)
+    }
+
+    /// Whether the consumer is connected, assigned, caught up and serving fresh data.
+    pub fn readiness(&self) -> HealthReport {
+        let state = self.state.lock().unwrap();
+        let mut components = BTreeMap::new();
+
+        let mut consume_loop = Self::consume_loop(&state);
+        if state.last_poll.is_none() {
+            consume_loop.healthy = false;
+        }
+        components.insert("consume_loop", consume_loop);
+
+        components.insert("broker", match &state.broker {
+            None => ComponentStatus::new(false, "not checked yet".to_string()),
+            Some(Ok(brokers)) => ComponentStatus::new(true, format!("{} broker(s) reachable", brokers)),
+            Some(Err(e)) => ComponentStatus::new(false, e.clone()),
+        });
+
+        let partitions: Vec<String> = state
+            .assigned_partitions
+            .iter()
+            .map(|(topic, partition)| format!("{}[{}]", topic, partition))
+            .collect();
+        components.insert("assignment", if partitions.is_empty() {
+            ComponentStatus::new(false, "no partitions assigned".to_string())
+        } else {
+            ComponentStatus::new(true, partitions.join(", "))
+        });
+
+        components.insert("lag", match state.max_lag_seen {
+            None => ComponentStatus::new(false, "not measured yet".to_string()),
+            Some(lag) => ComponentStatus::new(
+                lag <= state.max_lag,
+                format!("max lag {} (threshold {})", lag, state.max_lag),
+            ),
+        });
+
+        components.insert("last_message", match state.last_message {
+            None => ComponentStatus::new(false, "no message received yet".to_string()),
+            Some(last_message) => {
+                let idle = last_message.elapsed();
+                ComponentStatus::new(
+                    idle <= state.max_idle,
+                    format!("{}s ago (threshold {}s)", idle.as_secs(), state.max_idle.as_secs()),
+                )
+            }
+        });
+
+        components.insert("state_restore", if state.restored {
+            ComponentStatus::new(true, "complete".to_string())
+        } else {
+            ComponentStatus::new(false, "in progress".to_string())
+        });
+
+        HealthReport::new("ready", "not_ready", components)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A consumer that has started, connected, caught up and restored.
+    fn ready() -> ConsumerHealth {
+        let health = ConsumerHealth::new();
+        health.record_poll();
+        health.record_message();
+        health.record_broker(Ok(3));
+        health.record_assignment(vec![("market-data".to_string(), 0), ("market-data".to_string(), 1)]);
+        health.record_max_lag(Some(10));
+        health.mark_restored();
+        health
+    }
+
+    fn unhealthy(report: &HealthReport) -> Vec<&'static str> {
+        report.components.iter().filter(|(_, c)| !c.healthy).map(|(name, _)| *name).collect()
+    }
+
+    fn ago(secs: u64) -> Option<Instant> {
+        Instant::now().checked_sub(Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn a_fresh_consumer_is_alive_but_not_ready() {
+        let health = ConsumerHealth::new();
+        let live = health.liveness();
+        assert_eq!((live.status, live.is_healthy()), ("alive", true));
+        let ready = health.readiness();
+        assert_eq!(ready.status, "not_ready");
+        assert_eq!(unhealthy(&ready), ["assignment", "broker", "consume_loop", "lag", "last_message", "state_restore"]);
+
+        let ready = self::ready().readiness();
+        assert_eq!((ready.status, ready.is_healthy()), ("ready", true));
+        assert_eq!(ready.components["assignment"].detail, "market-data[0], market-data[1]");
+    }
+
+    #[test]
+    fn losing_the_broker_or_the_assignment_drops_readiness() {
+        let health = ready();
+        health.record_broker(Err("metadata request timed out".to_string()));
+        let report = health.readiness();
+        assert_eq!(unhealthy(&report), ["broker"]);
+        assert_eq!(report.components["broker"].detail, "metadata request timed out");
+        // Broker trouble alone is no reason to restart
+        assert!(health.liveness().is_healthy());
+
+        health.record_broker(Ok(1));
+        health.record_assignment(Vec::new());
+        assert_eq!(unhealthy(&health.readiness()), ["assignment"]);
+    }
+
+    #[test]
+    fn stale_polls_fail_liveness_and_stale_messages_readiness() {
+        let health = ready();
+        health.update(|state| state.last_poll = ago(31));
+        let live = health.liveness();
+        assert_eq!(live.status, "dead");
+        assert!(live.components["consume_loop"].detail.starts_with("no poll for 3"));
+        assert_eq!(unhealthy(&health.readiness()), ["consume_loop"]);
+
+        let health = ready();
+        health.set_thresholds(DEFAULT_MAX_LAG, Duration::from_secs(5));
+        health.update(|state| state.last_message = ago(6));
+        assert_eq!(unhealthy(&health.readiness()), ["last_message"]);
+        assert!(health.liveness().is_healthy());
+
+        health.record_stopped("broker transport failure".to_string());
+        assert_eq!(health.liveness().components["consume_loop"].detail, "stopped: broker transport failure");
+    }
+
+    #[test]
+    fn lag_beyond_the_threshold_drops_readiness() {
+        let health = ready();
+        health.set_thresholds(100, DEFAULT_MAX_IDLE);
+        health.record_max_lag(Some(100));
+        assert!(health.readiness().is_healthy());
+        health.record_max_lag(Some(101));
+        let report = health.readiness();
+        assert_eq!(unhealthy(&report), ["lag"]);
+        assert_eq!(report.components["lag"].detail, "max lag 101 (threshold 100)");
+        health.record_max_lag(None);
+        assert_eq!(unhealthy(&health.readiness()), ["lag"]);
+    }
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::{Candle, Decimal, RsiData, RsiSignal, TradeData};
+
+pub const DEFAULT_PAGE_LIMIT: usize = 100;
+pub const MAX_PAGE_LIMIT: usize = 1000;
+/// Most items sharing one timestamp a cursor may skip.
+const MAX_CURSOR_SKIP: usize = MAX_PAGE_LIMIT * 100;
+
+/// Time range and paging parameters shared by the history endpoints.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+    /// Opaque cursor from a previous page's `next_cursor`.
+    pub cursor: Option<String>,
+}
+
+/// One page of results, newest first.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Pass as `cursor` to fetch the next (older) page; absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+/// Inclusive timestamp range and candidate count from [`HistoryQuery::window`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryWindow {
+    pub from_nanos: Option<i64>,
+    pub until_nanos: Option<i64>,
+    pub candidates: usize,
+}
+
+/// Position after the last returned item: its timestamp plus how many items
+/// sharing that timestamp were already returned, so ties are never skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Cursor {
+    timestamp_nanos: i64,
+    skip: usize,
+}
+
+impl Cursor {
+    fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid cursor '{}'", value);
+        let (timestamp, skip) = value.split_once('.').ok_or_else(invalid)?;
+        Ok(Self {
+            timestamp_nanos: timestamp.parse().map_err(|_| invalid())?,
+            skip: skip.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!("{}.{}", self.timestamp_nanos, self.skip)
+    }
+}
+
+impl HistoryQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            if from > to {
+                return Err("'from' must not be after 'to'".to_string());
+            }
+        }
+        if self.limit == Some(0) {
+            return Err("'limit' must be greater than zero".to_string());
+        }
+        if let Some(cursor) = &self.cursor {
+            if Cursor::parse(cursor)?.skip > MAX_CURSOR_SKIP {
+                return Err(format!("invalid cursor '{}'", cursor));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+    }
+
+    /// Range of timestamps, in nanoseconds, and number of newest items a
+    /// store has to load for [`paginate`](Self::paginate) to build this page.
+    pub fn window(&self) -> HistoryWindow {
+        let nanos = |time: DateTime<Utc>| time.timestamp_nanos_opt().unwrap_or(i64::MAX);
+        let cursor = self.cursor.as_deref().and_then(|cursor| Cursor::parse(cursor).ok());
+        let to = self.to.map(nanos);
+        let until = match (to, cursor) {
+            (Some(to), Some(cursor)) => Some(to.min(cursor.timestamp_nanos)),
+            (to, cursor) => to.or(cursor.map(|cursor| cursor.timestamp_nanos)),
+        };
+        HistoryWindow {
+            from_nanos: self.from.map(nanos),
+            until_nanos: until,
+            // The ties the cursor skips, the page and one more to tell if another follows
+            candidates: cursor.map_or(0, |cursor| cursor.skip).saturating_add(self.limit() + 1),
+        }
+    }
+
+    /// Pages through `items`, which must be sorted oldest first.
+    pub fn paginate<T: Clone>(&self, items: &[T], timestamp: impl Fn(&T) -> DateTime<Utc>) -> Page<T> {
+        let nanos = |item: &T| timestamp(item).timestamp_nanos_opt().unwrap_or(i64::MAX);
+        let cursor = self.cursor.as_deref().and_then(|cursor| Cursor::parse(cursor).ok());
+        let limit = self.limit();
+
+        let mut skipped_ties = 0;
+        let mut page = Vec::with_capacity(limit.min(items.len()));
+        let mut next_cursor = None;
+
+        for item in items.iter().rev() {
+            let time = timestamp(item);
+            if self.to.is_some_and(|to| time > to) {
+                continue;
+            }
+            if self.from.is_some_and(|from| time < from) {
+                break;
+            }
+            if let Some(cursor) = cursor {
+                let item_nanos = nanos(item);
+                if item_nanos > cursor.timestamp_nanos {
+                    continue;
+                }
+                if item_nanos == cursor.timestamp_nanos && skipped_ties < cursor.skip {
+                    skipped_ties += 1;
+                    continue;
+                }
+            }
+            if page.len() == limit {
+                let last: &T = page.last().expect("limit is at least 1");
+                let last_nanos = nanos(last);
+                let ties = page.iter().rev().take_while(|item| nanos(item) == last_nanos).count();
+                let already = match cursor {
+                    Some(cursor) if cursor.timestamp_nanos == last_nanos => cursor.skip,
+                    _ => 0,
+                };
+                next_cursor = Some(Cursor { timestamp_nanos: last_nanos, skip: already.saturating_add(ties) }.encode());
+                break;
+            }
+            page.push(item.clone());
+        }
+
+        Page { items: page, next_cursor }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RsiSnapshot {
+    pub value: f64,
+    pub signal: RsiSignal,
+    pub period: u32,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Current state of a tracked symbol.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SymbolSummary {
+    pub symbol: String,
+    pub last_price: Option<Decimal>,
+    pub last_trade_time: Option<DateTime<Utc>>,
+    /// Total traded volume since the consumer started.
+    pub volume: u64,
+    /// Total traded value, `price * volume`, since the consumer started.
+    pub notional: Decimal,
+    pub trade_count: u64,
+    /// Recent trades held out of indicators as suspected outliers.
+    pub quarantined: usize,
+    pub rsi: Option<RsiSnapshot>,
+}
+
+impl From<&RsiData> for RsiSnapshot {
+    fn from(rsi: &RsiData) -> Self {
+        Self {
+            value: rsi.rsi_value,
+            signal: rsi.signal,
+            period: rsi.period,
+            timestamp: rsi.timestamp,
+        }
+    }
+}
+
+pub fn trade_time(trade: &TradeData) -> DateTime<Utc> {
+    trade.timestamp
+}
+
+pub fn rsi_time(rsi: &RsiData) -> DateTime<Utc> {
+    rsi.timestamp
+}
+
+pub fn candle_time(candle: &Candle) -> DateTime<Utc> {
+    candle.open_time
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    /// `(second, label)` pairs, oldest first.
+    fn items(seconds: &[i64]) -> Vec<(DateTime<Utc>, usize)> {
+        seconds.iter().enumerate().map(|(label, secs)| (at(*secs), label)).collect()
+    }
+
+    fn query(limit: usize) -> HistoryQuery {
+        HistoryQuery { limit: Some(limit), ..HistoryQuery::default() }
+    }
+
+    /// Labels of every page, following `next_cursor` to the end.
+    fn all_pages(mut query: HistoryQuery, items: &[(DateTime<Utc>, usize)]) -> Vec<Vec<usize>> {
+        let mut pages = Vec::new();
+        loop {
+            query.validate().unwrap();
+            let page = query.paginate(items, |item| item.0);
+            pages.push(page.items.iter().map(|item| item.1).collect());
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return pages,
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_continues_newest_first_without_gaps() {
+        let items = items(&[1, 2, 3, 4, 5]);
+        assert_eq!(all_pages(query(2), &items), vec![vec![4, 3], vec![2, 1], vec![0]]);
+        assert_eq!(all_pages(query(5), &items), vec![vec![4, 3, 2, 1, 0]]);
+    }
+
+    #[test]
+    fn ties_split_across_pages_are_neither_skipped_nor_repeated() {
+        let items = items(&[1, 2, 2, 2, 2, 2, 3]);
+        assert_eq!(all_pages(query(2), &items), vec![vec![6, 5], vec![4, 3], vec![2, 1], vec![0]]);
+        assert_eq!(all_pages(query(3), &items), vec![vec![6, 5, 4], vec![3, 2, 1], vec![0]]);
+    }
+
+    #[test]
+    fn from_and_to_bound_every_page() {
+        let items = items(&[1, 2, 3, 4, 5, 6]);
+        let bounded = HistoryQuery { from: Some(at(2)), to: Some(at(5)), ..query(2) };
+        assert_eq!(all_pages(bounded, &items), vec![vec![4, 3], vec![2, 1]]);
+
+        let backwards = HistoryQuery { from: Some(at(5)), to: Some(at(2)), ..query(2) };
+        assert!(backwards.validate().is_err());
+    }
+
+    #[test]
+    fn window_covers_skipped_ties_and_the_page() {
+        let nanos = at(2).timestamp_nanos_opt().unwrap();
+        let query = HistoryQuery { to: Some(at(5)), cursor: Some(format!("{}.3", nanos)), ..query(10) };
+        let window = query.window();
+        assert_eq!(window.until_nanos, Some(nanos));
+        assert_eq!(window.from_nanos, None);
+        assert_eq!(window.candidates, 3 + 10 + 1);
+    }
+
+    #[test]
+    fn oversized_or_malformed_cursors_are_rejected() {
+        let cursor = |cursor: &str| HistoryQuery { cursor: Some(cursor.to_string()), ..query(10) };
+        assert!(cursor(&format!("1.{}", MAX_CURSOR_SKIP)).validate().is_ok());
+        assert!(cursor(&format!("1.{}", MAX_CURSOR_SKIP + 1)).validate().is_err());
+        assert!(cursor("1.x").validate().is_err());
+        assert!(cursor("1").validate().is_err());
+        // Unvalidated queries still size their window without overflowing
+        assert_eq!(cursor(&format!("1.{}", usize::MAX)).window().candidates, usize::MAX);
+        assert!(query(0).validate().is_err());
+    }
+}
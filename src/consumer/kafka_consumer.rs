@@ -1,88 +1,149 @@
-use rdkafka::config::ClientConfig;
 use rdkafka::consumer::{Consumer, StreamConsumer};
-use rdkafka::Message;
-use std::time::Duration;
+use rdkafka::message::{BorrowedMessage, Headers};
+use rdkafka::{Message, Offset};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
 use tokio::time::timeout;
+use tracing::Instrument;
 
+use crate::codec::{Envelope, MessageType, RecordDecoder, PRODUCER_ID_HEADER, SEQUENCE_HEADER};
+use crate::config::{log_security, KafkaConfig};
 use crate::models::TradeData;
 use crate::consumer::DataProcessor;
+use crate::telemetry::{self, RECORD_ID_HEADER, SYMBOL_HEADER};
+use crate::validation::{RejectRouter, TradeValidator};
+
+/// How often broker connectivity, assignment and lag are rechecked.
+const STATUS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const BROKER_REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub struct TradingConsumer {
     consumer: StreamConsumer,
+    trade_topic: String,
     data_processor: DataProcessor,
+    decoder: RecordDecoder,
+    validator: TradeValidator,
+    rejects: Option<RejectRouter>,
 }
 
 impl TradingConsumer {
-    pub fn new(brokers: &str, group_id: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut config = ClientConfig::new();
-        config
-            .set("bootstrap.servers", brokers)
+    pub fn new(
+        kafka: &KafkaConfig,
+        group_id: &str,
+        trade_topic: &str,
+        data_processor: DataProcessor,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let builder = kafka.client_builder()?
             .set("group.id", group_id)
             .set("enable.partition.eof", "false")
             .set("session.timeout.ms", "6000")
             .set("enable.auto.commit", "true")
             .set("auto.offset.reset", "earliest");
+        log_security(&builder);
 
-        // Add SASL authentication if environment variables are set
-        // Only use SASL_SSL if all required variables are present
-        if let (Ok(security_protocol), Ok(sasl_mechanism), Ok(sasl_username), Ok(sasl_password)) = (
-            std::env::var("KAFKA_SECURITY_PROTOCOL"),
-            std::env::var("KAFKA_SASL_MECHANISM"),
-            std::env::var("KAFKA_SASL_USERNAME"),
-            std::env::var("KAFKA_SASL_PASSWORD")
-        ) {
-            println!("🔐 Using SASL authentication");
-            config.set("security.protocol", security_protocol);
-            config.set("sasl.mechanism", sasl_mechanism);
-            config.set("sasl.username", sasl_username);
-            config.set("sasl.password", sasl_password);
-        } else {
-            println!("🔓 Using PLAINTEXT connection (no SASL)");
-            config.set("security.protocol", "PLAINTEXT");
-        }
-
-        let consumer: StreamConsumer = config.create()?;
-
-        let data_processor = DataProcessor::new();
+        let consumer: StreamConsumer = builder.build().create()?;
 
         Ok(Self {
             consumer,
+            trade_topic: trade_topic.to_string(),
             data_processor,
+            decoder: RecordDecoder::default(),
+            validator: TradeValidator::default(),
+            rejects: None,
         })
     }
 
+    /// Resolves Avro and Protobuf payloads through `decoder`'s schema registry.
+    pub fn with_decoder(mut self, decoder: RecordDecoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Checks decoded trades with `validator` before processing them.
+    /// Rejected records are forwarded to `rejects` as received, if set.
+    pub fn with_validation(mut self, validator: TradeValidator, rejects: Option<RejectRouter>) -> Self {
+        self.validator = validator;
+        self.rejects = rejects;
+        self
+    }
+
     pub async fn subscribe_to_trade_data(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.consumer.subscribe(&["trade-data"])?;
-        println!("📡 Subscribed to trade-data topic");
+        self.consumer.subscribe(&[&self.trade_topic])?;
+        tracing::info!(topic = %self.trade_topic, "subscribed");
         Ok(())
     }
 
     pub async fn consume_messages(&self) -> Result<(), Box<dyn std::error::Error>> {
-        println!("🔄 Starting message consumption...");
-        
+        tracing::info!("starting message consumption");
+        let metrics = self.data_processor.metrics().clone();
+        let health = self.data_processor.health().clone();
+        let mut last_status_check = None::<Instant>;
+
         loop {
+            health.record_poll();
+            if last_status_check.is_none_or(|checked| checked.elapsed() >= STATUS_CHECK_INTERVAL) {
+                self.check_status();
+                last_status_check = Some(Instant::now());
+            }
+
             match timeout(Duration::from_secs(1), self.consumer.recv()).await {
                 Ok(Ok(message)) => {
-                    if let Some(payload) = message.payload() {
-                        match serde_json::from_slice::<TradeData>(payload) {
-                            Ok(trade_data) => {
-                                println!("📊 Processing trade: {} - {} @ ${:.2}", 
-                                    trade_data.symbol, 
-                                    format!("{:?}", trade_data.side), 
-                                    trade_data.price
-                                );
-                                
-                                // Process the trade data
-                                self.data_processor.process_trade_data(trade_data).await;
-                            }
-                            Err(e) => {
-                                eprintln!("❌ Failed to parse trade data: {}", e);
+                    health.record_message();
+                    let topic = message.topic();
+                    metrics.record_consumed(topic);
+                    let headers = message_headers(&message);
+                    let span = message_span(&message, &headers);
+                    let Some(payload) = message.payload() else {
+                        metrics.record_failed(topic, "empty_payload");
+                        span.in_scope(|| tracing::warn!("empty payload"));
+                        continue;
+                    };
+                    let envelope = match Envelope::from_headers(&headers) {
+                        Ok(Some(envelope)) => envelope,
+                        Ok(None) => Envelope::legacy(MessageType::Trade, message_time(&message)),
+                        Err(e) => {
+                            metrics.record_failed(topic, "envelope_error");
+                            span.in_scope(|| tracing::warn!(error = %e, "invalid envelope"));
+                            continue;
+                        }
+                    };
+                    match self.decoder.decode::<TradeData>(payload, &envelope).await {
+                        Ok(mut trade_data) => {
+                            // Outlier flags are this consumer's own verdict, never the sender's
+                            trade_data.outlier = None;
+                            metrics.record_parsed(topic);
+                            span.record("trade_id", trade_data.id.as_str());
+                            span.record("symbol", trade_data.symbol.as_str());
+                            if let Err(rejection) = self.validator.validate(&trade_data) {
+                                metrics.record_rejected(topic, rejection.reason.as_str());
+                                span.in_scope(|| tracing::warn!(%rejection, "rejected trade"));
+                                if let Some(rejects) = &self.rejects {
+                                    rejects.route(&trade_data.symbol, payload, &headers, &rejection);
+                                }
+                                continue;
                             }
+                            span.in_scope(|| tracing::debug!(
+                                side = ?trade_data.side,
+                                price = %trade_data.price,
+                                volume = trade_data.volume,
+                                "processing trade"
+                            ));
+
+                            let trade_time = trade_data.timestamp;
+                            let started = Instant::now();
+                            self.data_processor.process_trade_data(trade_data).instrument(span).await;
+                            let end_to_end = (chrono::Utc::now() - trade_time).to_std().unwrap_or_default();
+                            metrics.record_processed(topic, started.elapsed(), end_to_end);
+                        }
+                        Err(e) => {
+                            metrics.record_failed(topic, e.reason());
+                            span.in_scope(|| tracing::warn!(error = %e, "failed to decode trade data"));
                         }
                     }
                 }
                 Ok(Err(e)) => {
-                    eprintln!("❌ Consumer error: {}", e);
+                    metrics.record_receive_error();
+                    tracing::error!(error = %e, "consumer error");
                 }
                 Err(_) => {
                     // Timeout - continue loop
@@ -90,4 +151,85 @@ impl TradingConsumer {
             }
         }
     }
+
+    /// Checks broker connectivity, the partition assignment and how far each
+    /// assigned partition is behind its high watermark. These requests block,
+    /// so the runtime is told to move other tasks off this thread.
+    fn check_status(&self) {
+        let metrics = self.data_processor.metrics();
+        let health = self.data_processor.health();
+
+        let metadata = tokio::task::block_in_place(|| {
+            self.consumer.fetch_metadata(Some(&self.trade_topic), BROKER_REQUEST_TIMEOUT)
+        });
+        health.record_broker(
+            metadata
+                .map(|metadata| metadata.brokers().len())
+                .map_err(|e| format!("metadata request failed: {}", e)),
+        );
+
+        let Ok(position) = self.consumer.position() else {
+            return;
+        };
+        let mut partitions = Vec::new();
+        let mut lag = BTreeMap::new();
+        for element in position.elements() {
+            partitions.push((element.topic().to_string(), element.partition()));
+            let watermarks = tokio::task::block_in_place(|| {
+                self.consumer.fetch_watermarks(element.topic(), element.partition(), BROKER_REQUEST_TIMEOUT)
+            });
+            let Ok((low, high)) = watermarks else {
+                continue;
+            };
+            // Nothing consumed from the partition yet: all of it is pending
+            let next = match element.offset() {
+                Offset::Offset(offset) => offset,
+                _ => low,
+            };
+            lag.insert((element.topic().to_string(), element.partition()), (high - next).max(0));
+        }
+
+        health.record_assignment(partitions);
+        health.record_max_lag(lag.values().copied().max());
+        metrics.set_lag(lag);
+    }
+}
+
+fn message_headers(message: &BorrowedMessage<'_>) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    if let Some(borrowed) = message.headers() {
+        for header in borrowed.iter() {
+            if let Some(value) = header.value.and_then(|value| std::str::from_utf8(value).ok()) {
+                headers.insert(header.key.to_string(), value.to_string());
+            }
+        }
+    }
+    headers
+}
+
+/// Broker timestamp of a record, standing in for the produced-at time of
+/// records without an envelope.
+fn message_time(message: &BorrowedMessage<'_>) -> chrono::DateTime<chrono::Utc> {
+    message
+        .timestamp()
+        .to_millis()
+        .and_then(chrono::DateTime::from_timestamp_millis)
+        .unwrap_or_else(chrono::Utc::now)
+}
+
+/// Span for one consumed record, a child of the producer's span when the
+/// record carries trace context headers.
+fn message_span(message: &BorrowedMessage<'_>, headers: &HashMap<String, String>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "process_trade",
+        trade_id = headers.get(RECORD_ID_HEADER).map(String::as_str),
+        symbol = headers.get(SYMBOL_HEADER).map(String::as_str),
+        producer_id = headers.get(PRODUCER_ID_HEADER).map(String::as_str),
+        sequence = headers.get(SEQUENCE_HEADER).map(String::as_str),
+        topic = message.topic(),
+        partition = message.partition(),
+        offset = message.offset(),
+    );
+    telemetry::set_remote_parent(&span, headers);
+    span
 }
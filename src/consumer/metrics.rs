@@ -0,0 +1,311 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::metrics::{Histogram, PrometheusWriter};
+
+/// Buckets for in-process work, which usually takes well under a millisecond.
+pub const PROCESSING_BUCKETS_MS: [f64; 11] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0];
+
+/// Consumption statistics for a single topic.
+#[derive(Debug, Clone)]
+pub struct ConsumedTopicStats {
+    pub consumed: u64,
+    pub parsed: u64,
+    pub failures_by_reason: BTreeMap<String, u64>,
+    /// Decoded messages that failed validation.
+    pub rejects_by_reason: BTreeMap<String, u64>,
+    pub processing_ms: Histogram,
+    /// Time from the trade's own timestamp until it was processed.
+    pub end_to_end_ms: Histogram,
+}
+
+impl Default for ConsumedTopicStats {
+    fn default() -> Self {
+        Self {
+            consumed: 0,
+            parsed: 0,
+            failures_by_reason: BTreeMap::new(),
+            rejects_by_reason: BTreeMap::new(),
+            processing_ms: Histogram::new(&PROCESSING_BUCKETS_MS),
+            end_to_end_ms: Histogram::latency_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestStats {
+    pub by_status: BTreeMap<u16, u64>,
+    pub latency_ms: Histogram,
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self {
+            by_status: BTreeMap::new(),
+            latency_ms: Histogram::latency_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Stats {
+    topics: BTreeMap<String, ConsumedTopicStats>,
+    receive_errors: u64,
+    /// Messages behind the high watermark per (topic, partition).
+    lag: BTreeMap<(String, i32), i64>,
+    tracked_symbols: usize,
+    /// Symbols given their own `symbol` label; the rest share `other`.
+    labelled_symbols: BTreeSet<String>,
+    rsi_computations: BTreeMap<String, u64>,
+    /// Keyed by (symbol label, outlier status).
+    outliers: BTreeMap<(String, String), u64>,
+    /// Keyed by (method, route).
+    requests: BTreeMap<(String, String), RequestStats>,
+    /// Events dropped because a sink's queue was full, by sink.
+    sink_dropped: BTreeMap<&'static str, u64>,
+}
+
+/// Shared consumer and API metrics, served at `GET /metrics`.
+#[derive(Debug, Clone, Default)]
+pub struct ConsumerMetrics {
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl ConsumerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update_topic(&self, topic: &str, f: impl FnOnce(&mut ConsumedTopicStats)) {
+        let mut stats = self.stats.lock().unwrap();
+        f(stats.topics.entry(topic.to_string()).or_default());
+    }
+
+    pub fn record_consumed(&self, topic: &str) {
+        self.update_topic(topic, |stats| stats.consumed += 1);
+    }
+
+    pub fn record_parsed(&self, topic: &str) {
+        self.update_topic(topic, |stats| stats.parsed += 1);
+    }
+
+    pub fn record_failed(&self, topic: &str, reason: &str) {
+        self.update_topic(topic, |stats| {
+            *stats.failures_by_reason.entry(reason.to_string()).or_default() += 1;
+        });
+    }
+
+    pub fn record_rejected(&self, topic: &str, reason: &str) {
+        self.update_topic(topic, |stats| {
+            *stats.rejects_by_reason.entry(reason.to_string()).or_default() += 1;
+        });
+    }
+
+    pub fn record_processed(&self, topic: &str, processing: Duration, end_to_end: Duration) {
+        self.update_topic(topic, |stats| {
+            stats.processing_ms.observe(processing.as_secs_f64() * 1000.0);
+            stats.end_to_end_ms.observe(end_to_end.as_secs_f64() * 1000.0);
+        });
+    }
+
+    pub fn record_receive_error(&self) {
+        self.stats.lock().unwrap().receive_errors += 1;
+    }
+
+    /// Replaces the lag of every assigned partition.
+    pub fn set_lag(&self, lag: BTreeMap<(String, i32), i64>) {
+        self.stats.lock().unwrap().lag = lag;
+    }
+
+    pub fn set_tracked_symbols(&self, count: usize) {
+        self.stats.lock().unwrap().tracked_symbols = count;
+    }
+
+    /// Symbols come from the wire, so only these are labelled individually.
+    pub fn set_labelled_symbols(&self, symbols: BTreeSet<String>) {
+        self.stats.lock().unwrap().labelled_symbols = symbols;
+    }
+
+    pub fn record_rsi_computation(&self, symbol: &str) {
+        let mut stats = self.stats.lock().unwrap();
+        let symbol = symbol_label(&stats.labelled_symbols, symbol);
+        *stats.rsi_computations.entry(symbol).or_default() += 1;
+    }
+
+    pub fn record_outlier(&self, symbol: &str, status: &str) {
+        let mut stats = self.stats.lock().unwrap();
+        let symbol = symbol_label(&stats.labelled_symbols, symbol);
+        *stats.outliers.entry((symbol, status.to_string())).or_default() += 1;
+    }
+
+    pub fn record_sink_dropped(&self, sink: &'static str) {
+        *self.stats.lock().unwrap().sink_dropped.entry(sink).or_default() += 1;
+    }
+
+    pub fn record_request(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let request = stats.requests.entry((method.to_string(), route.to_string())).or_default();
+        *request.by_status.entry(status).or_default() += 1;
+        request.latency_ms.observe(latency.as_secs_f64() * 1000.0);
+    }
+
+    /// Renders all metrics in Prometheus text format.
+    pub fn render(&self) -> String {
+        let stats = self.stats.lock().unwrap().clone();
+        let mut writer = PrometheusWriter::new();
+
+        writer.header("consumer_messages_consumed_total", "counter", "Messages received from Kafka");
+        for (topic, topic_stats) in &stats.topics {
+            writer.sample("consumer_messages_consumed_total", &[("topic", topic)], topic_stats.consumed as f64);
+        }
+
+        writer.header("consumer_messages_parsed_total", "counter", "Messages decoded successfully");
+        for (topic, topic_stats) in &stats.topics {
+            writer.sample("consumer_messages_parsed_total", &[("topic", topic)], topic_stats.parsed as f64);
+        }
+
+        writer.header("consumer_messages_failed_total", "counter", "Messages that could not be decoded, by reason");
+        for (topic, topic_stats) in &stats.topics {
+            for (reason, count) in &topic_stats.failures_by_reason {
+                writer.sample("consumer_messages_failed_total", &[("topic", topic), ("reason", reason)], *count as f64);
+            }
+        }
+
+        writer.header("consumer_messages_rejected_total", "counter", "Decoded messages that failed validation, by reason");
+        for (topic, topic_stats) in &stats.topics {
+            for (reason, count) in &topic_stats.rejects_by_reason {
+                writer.sample("consumer_messages_rejected_total", &[("topic", topic), ("reason", reason)], *count as f64);
+            }
+        }
+
+        writer.header("consumer_receive_errors_total", "counter", "Errors returned by the Kafka client");
+        writer.sample("consumer_receive_errors_total", &[], stats.receive_errors as f64);
+
+        writer.header("consumer_processing_latency_ms", "histogram", "Time spent processing a decoded message");
+        for (topic, topic_stats) in &stats.topics {
+            writer.histogram("consumer_processing_latency_ms", &[("topic", topic)], &topic_stats.processing_ms);
+        }
+
+        writer.header("consumer_end_to_end_latency_ms", "histogram", "Time from trade timestamp to processing");
+        for (topic, topic_stats) in &stats.topics {
+            writer.histogram("consumer_end_to_end_latency_ms", &[("topic", topic)], &topic_stats.end_to_end_ms);
+        }
+
+        writer.header("consumer_lag", "gauge", "Messages behind the high watermark per partition");
+        for ((topic, partition), lag) in &stats.lag {
+            let partition = partition.to_string();
+            writer.sample("consumer_lag", &[("topic", topic), ("partition", &partition)], *lag as f64);
+        }
+
+        writer.header("consumer_tracked_symbols", "gauge", "Symbols with price history");
+        writer.sample("consumer_tracked_symbols", &[], stats.tracked_symbols as f64);
+
+        writer.header("consumer_rsi_computations_total", "counter", "RSI values calculated");
+        for (symbol, count) in &stats.rsi_computations {
+            writer.sample("consumer_rsi_computations_total", &[("symbol", symbol)], *count as f64);
+        }
+
+        writer.header("consumer_outliers_total", "counter", "Trades flagged, confirmed or discarded as outliers");
+        for ((symbol, status), count) in &stats.outliers {
+            writer.sample("consumer_outliers_total", &[("symbol", symbol), ("status", status)], *count as f64);
+        }
+
+        writer.header("consumer_sink_dropped_total", "counter", "Events a sink could not keep up with, by sink");
+        for (sink, count) in &stats.sink_dropped {
+            writer.sample("consumer_sink_dropped_total", &[("sink", sink)], *count as f64);
+        }
+
+        writer.header("api_requests_total", "counter", "HTTP requests served by the API");
+        for ((method, route), request) in &stats.requests {
+            for (status, count) in &request.by_status {
+                let status = status.to_string();
+                let labels = [("method", method.as_str()), ("route", route.as_str()), ("status", status.as_str())];
+                writer.sample("api_requests_total", &labels, *count as f64);
+            }
+        }
+
+        writer.header("api_request_latency_ms", "histogram", "Time to produce an HTTP response");
+        for ((method, route), request) in &stats.requests {
+            writer.histogram("api_request_latency_ms", &[("method", method), ("route", route)], &request.latency_ms);
+        }
+
+        writer.finish()
+    }
+}
+
+fn symbol_label(labelled: &BTreeSet<String>, symbol: &str) -> String {
+    if labelled.contains(symbol) {
+        symbol.to_string()
+    } else {
+        "other".to_string()
+    }
+}
+
+/// Routes served by the API, used as the `route` label.
+pub(crate) const ROUTES: [&str; 21] = [
+    "/health",
+    "/health/live",
+    "/health/ready",
+    "/metrics",
+    "/openapi.json",
+    "/api/v1/prices",
+    "/api/v1/rsi",
+    "/api/v1/symbols",
+    "/api/v1/symbols/{symbol}",
+    "/api/v1/symbols/{symbol}/trades",
+    "/api/v1/symbols/{symbol}/rsi/history",
+    "/api/v1/symbols/{symbol}/candles",
+    "/api/v1/alerts",
+    "/api/v1/alerts/fired",
+    "/api/v1/alerts/{id}",
+    "/api/v1/webhooks",
+    "/api/v1/export/trades",
+    "/api/v1/export/candles",
+    "/api/v1/export/rsi",
+    "/api/v1/stream",
+    "/api/v1/ws",
+];
+
+/// Collapses a request path to its route pattern so ids and symbols do not
+/// create a metric series each; anything unknown becomes `other`.
+pub fn route_label(path: &str) -> &'static str {
+    let mut segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    match segments.as_slice() {
+        ["api", "v1", "symbols", _, ..] => segments[3] = "{symbol}",
+        ["api", "v1", "alerts", id] if *id != "fired" => segments[3] = "{id}",
+        _ => {}
+    }
+    let route = format!("/{}", segments.join("/"));
+    ROUTES.iter().find(|known| **known == route).copied().unwrap_or("other")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unconfigured_symbols_share_one_label() {
+        let metrics = ConsumerMetrics::new();
+        metrics.set_labelled_symbols(BTreeSet::from(["AAPL".to_string()]));
+        metrics.record_rsi_computation("AAPL");
+        for symbol in ["ZZZ1", "ZZZ2", "ZZZ3"] {
+            metrics.record_rsi_computation(symbol);
+            metrics.record_outlier(symbol, "suspect");
+        }
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"consumer_rsi_computations_total{symbol="AAPL"} 1"#));
+        assert!(rendered.contains(r#"consumer_rsi_computations_total{symbol="other"} 3"#));
+        assert!(rendered.contains(r#"consumer_outliers_total{symbol="other",status="suspect"} 3"#));
+        assert!(!rendered.contains("ZZZ"));
+    }
+
+    #[test]
+    fn request_paths_collapse_to_routes() {
+        assert_eq!(route_label("/api/v1/symbols/AAPL/trades"), "/api/v1/symbols/{symbol}/trades");
+        assert_eq!(route_label("/api/v1/alerts/fired"), "/api/v1/alerts/fired");
+        assert_eq!(route_label("/api/v1/alerts/abc"), "/api/v1/alerts/{id}");
+        assert_eq!(route_label("/wp-admin/login.php"), "other");
+    }
+}
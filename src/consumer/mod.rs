@@ -1,5 +1,27 @@
 pub mod kafka_consumer;
 pub mod data_processor;
+pub mod events;
+pub mod history;
+pub mod alerts;
+pub mod webhooks;
+pub mod metrics;
+pub mod health;
+pub mod outliers;
+#[cfg(feature = "sqlite")]
+pub mod store;
+#[cfg(feature = "parquet")]
+pub mod archive;
 
 pub use kafka_consumer::*;
 pub use data_processor::*;
+pub use events::*;
+pub use history::*;
+pub use alerts::*;
+pub use webhooks::*;
+pub use metrics::*;
+pub use health::*;
+pub use outliers::*;
+#[cfg(feature = "sqlite")]
+pub use store::*;
+#[cfg(feature = "parquet")]
+pub use archive::*;
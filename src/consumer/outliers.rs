@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use crate::config::{OutlierConfig, OutlierMethod};
+use crate::consumer::PriceHistory;
+use crate::models::{price_to_f64, Decimal, OutlierStatus, TradeData};
+
+/// Scales a median absolute deviation to the standard deviation of a normal
+/// distribution, giving the modified z-score.
+const MAD_SCALE: f64 = 0.6745;
+
+/// Smallest MAD, as a fraction of the median, so that a quiet window where
+/// most trades print at one price does not flag ordinary moves.
+const MIN_RELATIVE_MAD: f64 = 0.005;
+
+/// What screening one trade changed.
+#[derive(Debug, Clone)]
+pub struct Screening {
+    /// The trade as it should be recorded, flagged if it is an outlier.
+    pub trade: TradeData,
+    /// Trades to feed to candles and indicators, oldest first: the trade
+    /// itself, any quarantined trades it confirmed followed by it, or none.
+    pub released: Vec<TradeData>,
+    /// Earlier suspects that were confirmed or discarded.
+    pub resolved: Vec<TradeData>,
+}
+
+/// Statistics of a symbol's accepted trades that a new price is judged by.
+enum Baseline {
+    /// Median and median absolute deviation of recent accepted prices.
+    Mad { median: Decimal, mad: f64 },
+    Vwap { vwap: Decimal },
+}
+
+impl Baseline {
+    fn reference(&self) -> Decimal {
+        match self {
+            Baseline::Mad { median, .. } => *median,
+            Baseline::Vwap { vwap } => *vwap,
+        }
+    }
+}
+
+/// Holds suspect ticks out of indicators per symbol. A suspect is confirmed
+/// once `confirm_after` further trades print near it, and discarded as soon
+/// as a trade prints back within the usual range. Trades following a
+/// confirmed move are accepted until the window catches up with it.
+#[derive(Debug, Clone)]
+pub struct OutlierFilter {
+    config: Arc<OutlierConfig>,
+}
+
+impl Default for OutlierFilter {
+    fn default() -> Self {
+        Self::new(&OutlierConfig::default())
+    }
+}
+
+impl OutlierFilter {
+    pub fn new(config: &OutlierConfig) -> Self {
+        Self { config: Arc::new(config.clone()) }
+    }
+
+    /// Screens `trade` against `history`, updating the symbol's quarantine
+    /// and the flags of its recorded trades. The MAD never shrinks below
+    /// `tick_size`. Any flag the trade arrived with is ignored.
+    pub fn screen(&self, history: &mut PriceHistory, tick_size: Decimal, mut trade: TradeData) -> Screening {
+        trade.outlier = None;
+        let baseline = if self.config.enabled { self.baseline(history, tick_size) } else { None };
+        let Some(baseline) = baseline.filter(|baseline| !self.within(baseline, baseline.reference(), trade.price)) else {
+            let resolved = resolve(history, OutlierStatus::Discarded);
+            return Screening { released: vec![trade.clone()], trade, resolved };
+        };
+
+        // Trades carrying on from a confirmed move count until the statistics catch up
+        let last_accepted = history.trades.iter().rev().find(|recorded| !recorded.is_outlier());
+        let continues = last_accepted.is_some_and(|last| {
+            last.outlier == Some(OutlierStatus::Confirmed) && self.within(&baseline, last.price, trade.price)
+        });
+        if continues {
+            let resolved = resolve(history, OutlierStatus::Discarded);
+            trade.outlier = Some(OutlierStatus::Confirmed);
+            return Screening { released: vec![trade.clone()], trade, resolved };
+        }
+
+        let corroborates = history.quarantine.last().is_some_and(|last| self.within(&baseline, last.price, trade.price));
+        if corroborates && history.quarantine.len() >= self.config.confirm_after {
+            let resolved = resolve(history, OutlierStatus::Confirmed);
+            trade.outlier = Some(OutlierStatus::Confirmed);
+            let mut released = resolved.clone();
+            released.push(trade.clone());
+            return Screening { trade, released, resolved };
+        }
+
+        // A suspect in another direction or range starts a new quarantine
+        let resolved = if corroborates { Vec::new() } else { resolve(history, OutlierStatus::Discarded) };
+        trade.outlier = Some(OutlierStatus::Suspect);
+        history.quarantine.push(trade.clone());
+        Screening { trade, released: Vec::new(), resolved }
+    }
+
+    /// `None` until the symbol has `min_samples` accepted trades.
+    fn baseline(&self, history: &PriceHistory, tick_size: Decimal) -> Option<Baseline> {
+        match self.config.method {
+            OutlierMethod::Mad => {
+                let start = history.prices.len().saturating_sub(self.config.window);
+                let mut prices = history.prices[start..].to_vec();
+                if prices.len() < self.config.min_samples {
+                    return None;
+                }
+                prices.sort();
+                let middle = prices.len() / 2;
+                let median = if prices.len().is_multiple_of(2) {
+                    (prices[middle - 1] + prices[middle]) / Decimal::TWO
+                } else {
+                    prices[middle]
+                };
+                let mut deviations: Vec<f64> = prices.iter().map(|price| price_to_f64((price - median).abs())).collect();
+                let mad = median_of(&mut deviations)
+                    .max(price_to_f64(tick_size))
+                    .max(price_to_f64(median) * MIN_RELATIVE_MAD);
+                Some(Baseline::Mad { median, mad })
+            }
+            OutlierMethod::Vwap => {
+                let recent: Vec<&TradeData> = history
+                    .trades
+                    .iter()
+                    .rev()
+                    .filter(|trade| !trade.is_outlier())
+                    .take(self.config.window)
+                    .collect();
+                if recent.len() < self.config.min_samples {
+                    return None;
+                }
+                let volume = recent.iter().fold(0u64, |sum, trade| sum.saturating_add(trade.volume));
+                let notional = recent.iter().try_fold(Decimal::ZERO, |sum, trade| sum.checked_add(trade.notional()?))?;
+                let vwap = notional.checked_div(Decimal::from(volume))?;
+                Some(Baseline::Vwap { vwap })
+            }
+        }
+    }
+
+    /// Whether a move from `from` to `to` is within the configured threshold.
+    fn within(&self, baseline: &Baseline, from: Decimal, to: Decimal) -> bool {
+        match baseline {
+            Baseline::Mad { mad, .. } => MAD_SCALE * price_to_f64((to - from).abs()) / mad <= self.config.mad_threshold,
+            Baseline::Vwap { .. } => (to - from)
+                .checked_div(from)
+                .is_some_and(|change| price_to_f64(change.abs()) * 100.0 <= self.config.vwap_deviation_pct),
+        }
+    }
+}
+
+/// Empties the quarantine, marking its trades and their recorded copies
+/// with `status`.
+fn resolve(history: &mut PriceHistory, status: OutlierStatus) -> Vec<TradeData> {
+    let mut resolved = std::mem::take(&mut history.quarantine);
+    for trade in &mut resolved {
+        trade.outlier = Some(status);
+        if let Some(recorded) = history.trades.iter_mut().rev().find(|recorded| recorded.id == trade.id) {
+            recorded.outlier = Some(status);
+        }
+    }
+    resolved
+}
+
+fn median_of(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TradeSide;
+
+    fn filter() -> OutlierFilter {
+        OutlierFilter::new(&OutlierConfig { window: 20, min_samples: 10, confirm_after: 2, ..OutlierConfig::default() })
+    }
+
+    /// Screens a trade at `price` and records it as `DataProcessor` does.
+    fn feed(filter: &OutlierFilter, history: &mut PriceHistory, price: &str) -> Screening {
+        let trade = TradeData::new("AAPL".to_string(), price.parse().unwrap(), 100, TradeSide::Buy, "NASDAQ".to_string());
+        feed_trade(filter, history, trade)
+    }
+
+    fn feed_trade(filter: &OutlierFilter, history: &mut PriceHistory, trade: TradeData) -> Screening {
+        let screening = filter.screen(history, Decimal::new(1, 2), trade);
+        history.add_trade(screening.trade.clone());
+        for trade in &screening.released {
+            history.add_price(trade.price, trade.timestamp);
+        }
+        screening
+    }
+
+    /// A history of 20 trades printing around 100.
+    fn settled(filter: &OutlierFilter) -> PriceHistory {
+        let mut history = PriceHistory::new("AAPL".to_string());
+        for price in ["100.00", "100.10"].iter().cycle().take(20) {
+            assert_eq!(feed(filter, &mut history, price).trade.outlier, None);
+        }
+        history
+    }
+
+    fn statuses(trades: &[TradeData]) -> Vec<Option<OutlierStatus>> {
+        trades.iter().map(|trade| trade.outlier).collect()
+    }
+
+    #[test]
+    fn nothing_is_flagged_before_min_samples() {
+        let filter = filter();
+        let mut history = PriceHistory::new("AAPL".to_string());
+        for price in ["100", "150", "100", "50"] {
+            let screening = feed(&filter, &mut history, price);
+            assert_eq!((screening.trade.outlier, screening.released.len()), (None, 1));
+        }
+    }
+
+    #[test]
+    fn out_of_line_price_is_quarantined() {
+        let filter = filter();
+        let mut history = settled(&filter);
+        let screening = feed(&filter, &mut history, "120");
+        assert_eq!(screening.trade.outlier, Some(OutlierStatus::Suspect));
+        assert!(screening.released.is_empty() && screening.resolved.is_empty());
+        assert_eq!(history.quarantine.len(), 1);
+        assert_eq!(history.prices.len(), 20);
+    }
+
+    #[test]
+    fn suspect_is_discarded_when_prices_return() {
+        let filter = filter();
+        let mut history = settled(&filter);
+        let suspect = feed(&filter, &mut history, "120").trade;
+
+        let screening = feed(&filter, &mut history, "100.05");
+        assert_eq!(screening.trade.outlier, None);
+        assert_eq!(screening.released.len(), 1);
+        assert_eq!(statuses(&screening.resolved), [Some(OutlierStatus::Discarded)]);
+        assert!(history.quarantine.is_empty());
+        let recorded = history.trades.iter().find(|trade| trade.id == suspect.id).unwrap();
+        assert_eq!(recorded.outlier, Some(OutlierStatus::Discarded));
+        assert!(!history.prices.contains(&suspect.price));
+    }
+
+    #[test]
+    fn suspect_is_confirmed_by_corroborating_trades() {
+        let filter = filter();
+        let mut history = settled(&filter);
+        assert_eq!(feed(&filter, &mut history, "120").trade.outlier, Some(OutlierStatus::Suspect));
+        assert_eq!(feed(&filter, &mut history, "120.50").trade.outlier, Some(OutlierStatus::Suspect));
+
+        let screening = feed(&filter, &mut history, "121");
+        assert_eq!(screening.trade.outlier, Some(OutlierStatus::Confirmed));
+        assert_eq!(statuses(&screening.resolved), [Some(OutlierStatus::Confirmed); 2]);
+        let released: Vec<String> = screening.released.iter().map(|trade| trade.price.to_string()).collect();
+        assert_eq!(released, ["120", "120.50", "121"]);
+        assert!(history.quarantine.is_empty());
+
+        // Later trades at the new level count before the statistics catch up
+        let screening = feed(&filter, &mut history, "121.50");
+        assert_eq!((screening.trade.outlier, screening.released.len()), (Some(OutlierStatus::Confirmed), 1));
+    }
+
+    #[test]
+    fn suspect_in_a_new_range_restarts_the_quarantine() {
+        let filter = filter();
+        let mut history = settled(&filter);
+        feed(&filter, &mut history, "120");
+        let screening = feed(&filter, &mut history, "80");
+        assert_eq!(screening.trade.outlier, Some(OutlierStatus::Suspect));
+        assert_eq!(statuses(&screening.resolved), [Some(OutlierStatus::Discarded)]);
+        assert_eq!(history.quarantine.len(), 1);
+    }
+
+    #[test]
+    fn flags_arriving_with_a_trade_are_ignored() {
+        let filter = filter();
+        let mut history = settled(&filter);
+        let mut spoofed = TradeData::new("AAPL".to_string(), "120".parse().unwrap(), 100, TradeSide::Buy, "NASDAQ".to_string());
+        spoofed.outlier = Some(OutlierStatus::Confirmed);
+        assert_eq!(feed_trade(&filter, &mut history, spoofed).trade.outlier, Some(OutlierStatus::Suspect));
+
+        let mut ordinary = TradeData::new("AAPL".to_string(), "100".parse().unwrap(), 100, TradeSide::Buy, "NASDAQ".to_string());
+        ordinary.outlier = Some(OutlierStatus::Discarded);
+        let screening = feed_trade(&filter, &mut history, ordinary);
+        assert_eq!((screening.trade.outlier, screening.released.len()), (None, 1));
+    }
+}
@@ -0,0 +1,357 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use rusqlite::{params, Connection, OpenFlags};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::{RetentionConfig, StorageConfig};
+use crate::consumer::{candle_time, rsi_time, trade_time, HistoryQuery, HistoryWindow, Page};
+use crate::models::{notional, Candle, Decimal, RsiData, TradeData};
+
+/// Records waiting for the writer before `save` waits for room.
+const QUEUE_CAPACITY: usize = 10_000;
+
+/// How often records older than their retention are deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Each table keeps the record as JSON next to the columns it is looked up by.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS trades (
+        id TEXT PRIMARY KEY,
+        symbol TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS trades_symbol_timestamp ON trades (symbol, timestamp);
+
+    CREATE TABLE IF NOT EXISTS candles (
+        symbol TEXT NOT NULL,
+        interval_secs INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (symbol, interval_secs, timestamp)
+    );
+    CREATE INDEX IF NOT EXISTS candles_symbol_timestamp ON candles (symbol, timestamp);
+
+    CREATE TABLE IF NOT EXISTS rsi (
+        id TEXT PRIMARY KEY,
+        symbol TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS rsi_symbol_timestamp ON rsi (symbol, timestamp);
+";
+
+/// A record queued for the next batch.
+#[derive(Debug, Clone)]
+pub enum StoredRecord {
+    Trade(TradeData),
+    /// Written again on every update while the candle is open.
+    Candle(Candle),
+    Rsi(RsiData),
+}
+
+/// The most recent records of one symbol, oldest first, used to rebuild the
+/// in-memory state on startup.
+#[derive(Debug, Clone, Default)]
+pub struct RestoredHistory {
+    pub trades: Vec<TradeData>,
+    pub candles: Vec<Candle>,
+    pub rsi: Vec<RsiData>,
+    /// Totals over every retained trade, not just the restored ones.
+    pub trade_count: u64,
+    pub total_volume: u64,
+    pub total_notional: Decimal,
+}
+
+/// Embedded SQLite store for trades, candles and RSI values. Writes are
+/// queued and committed in batches by a background task, so reads can lag
+/// behind the in-memory state by up to the flush interval.
+#[derive(Clone)]
+pub struct HistoryStore {
+    reader: Arc<Mutex<Connection>>,
+    queue: mpsc::Sender<StoredRecord>,
+}
+
+impl HistoryStore {
+    /// Opens or creates the database and starts the writer and retention tasks.
+    pub fn open(config: &StorageConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = config.path.as_deref().ok_or("no storage path configured")?;
+        let open_error = |e: rusqlite::Error| format!("failed to open store '{}': {}", path, e);
+
+        let writer = Connection::open(path).map_err(open_error)?;
+        writer
+            .execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+            .and_then(|_| writer.execute_batch(SCHEMA))
+            .map_err(open_error)?;
+        let reader = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(open_error)?;
+
+        let writer = Arc::new(Mutex::new(writer));
+        let (queue, pending) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(write_batches(
+            writer.clone(),
+            pending,
+            config.batch_size,
+            Duration::from_millis(config.flush_interval_ms),
+        ));
+        tokio::spawn(prune_expired(writer, config.retention));
+
+        Ok(Self { reader: Arc::new(Mutex::new(reader)), queue })
+    }
+
+    /// Queues a record, waiting while the writer is a full queue behind.
+    pub async fn save(&self, record: StoredRecord) {
+        if self.queue.send(record).await.is_err() {
+            tracing::error!("store writer has stopped, record dropped");
+        }
+    }
+
+    /// Returns `None` for symbols without stored trades.
+    pub async fn trades(&self, symbol: &str, query: &HistoryQuery) -> Result<Option<Page<TradeData>>, String> {
+        self.page("trades", symbol, query, trade_time).await
+    }
+
+    pub async fn rsi_history(&self, symbol: &str, query: &HistoryQuery) -> Result<Option<Page<RsiData>>, String> {
+        self.page("rsi", symbol, query, rsi_time).await
+    }
+
+    pub async fn candles(&self, symbol: &str, query: &HistoryQuery) -> Result<Option<Page<Candle>>, String> {
+        self.page("candles", symbol, query, candle_time).await
+    }
+
+    async fn page<T>(
+        &self,
+        table: &'static str,
+        symbol: &str,
+        query: &HistoryQuery,
+        time: fn(&T) -> DateTime<Utc>,
+    ) -> Result<Option<Page<T>>, String>
+    where
+        T: DeserializeOwned + Clone + Send + 'static,
+    {
+        let symbol = symbol.to_string();
+        let window = query.window();
+        let reader = self.reader.clone();
+        let rows = tokio::task::spawn_blocking(move || {
+            let db = reader.lock().unwrap();
+            let rows: Vec<T> = select_recent(&db, table, &symbol, window)?;
+            if rows.is_empty() && !has_symbol(&db, table, &symbol)? {
+                return Ok(None);
+            }
+            Ok::<_, String>(Some(rows))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        Ok(rows.map(|rows| query.paginate(&rows, time)))
+    }
+
+    /// Loads the latest `limit` records of each kind for every stored symbol.
+    pub async fn restore(&self, limit: usize) -> Result<HashMap<String, RestoredHistory>, String> {
+        let reader = self.reader.clone();
+        tokio::task::spawn_blocking(move || {
+            let db = reader.lock().unwrap();
+            let window = HistoryWindow { from_nanos: None, until_nanos: None, candidates: limit };
+            let mut restored: HashMap<String, RestoredHistory> = HashMap::new();
+            for (symbol, totals) in trade_totals(&db)? {
+                let history = restored.entry(symbol.clone()).or_default();
+                history.trade_count = totals.count;
+                history.total_volume = totals.volume;
+                history.total_notional = totals.notional;
+                history.trades = select_recent(&db, "trades", &symbol, window)?;
+            }
+            for symbol in symbols(&db, "candles")? {
+                restored.entry(symbol.clone()).or_default().candles = select_recent(&db, "candles", &symbol, window)?;
+            }
+            for symbol in symbols(&db, "rsi")? {
+                restored.entry(symbol.clone()).or_default().rsi = select_recent(&db, "rsi", &symbol, window)?;
+            }
+            Ok(restored)
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+fn nanos(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt().unwrap_or(i64::MAX)
+}
+
+/// The newest `window.candidates` records within the window, oldest first
+/// with ties in insertion order, as `HistoryQuery::paginate` expects.
+fn select_recent<T: DeserializeOwned>(db: &Connection, table: &str, symbol: &str, window: HistoryWindow) -> Result<Vec<T>, String> {
+    let sql = format!(
+        "SELECT data FROM {} WHERE symbol = ?1 AND timestamp >= ?2 AND timestamp <= ?3 \
+         ORDER BY timestamp DESC, rowid DESC LIMIT ?4",
+        table
+    );
+    let bounds = params![
+        symbol,
+        window.from_nanos.unwrap_or(i64::MIN),
+        window.until_nanos.unwrap_or(i64::MAX),
+        window.candidates as i64,
+    ];
+    let mut statement = db.prepare_cached(&sql).map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(bounds, |row| row.get::<_, String>(0))
+        .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())
+        .map_err(|e| format!("failed to read {}: {}", table, e))?;
+    rows.iter()
+        .rev()
+        .map(|data| serde_json::from_str(data).map_err(|e| format!("corrupt row in {}: {}", table, e)))
+        .collect()
+}
+
+fn has_symbol(db: &Connection, table: &str, symbol: &str) -> Result<bool, String> {
+    let sql = format!("SELECT EXISTS (SELECT 1 FROM {} WHERE symbol = ?1)", table);
+    db.query_row(&sql, [symbol], |row| row.get(0)).map_err(|e| e.to_string())
+}
+
+fn symbols(db: &Connection, table: &str) -> Result<Vec<String>, String> {
+    let sql = format!("SELECT DISTINCT symbol FROM {}", table);
+    let mut statement = db.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map([], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string());
+    rows
+}
+
+/// Trade count and volume per symbol.
+/// Trade count, volume and notional per symbol. Prices are summed here
+/// rather than in SQL, which would round them through `REAL`.
+fn trade_totals(db: &Connection) -> Result<HashMap<String, TradeTotals>, String> {
+    let mut statement = db
+        .prepare("SELECT symbol, CAST(json_extract(data, '$.price') AS TEXT), json_extract(data, '$.volume') FROM trades")
+        .map_err(|e| e.to_string())?;
+    let mut rows = statement.query([]).map_err(|e| e.to_string())?;
+    let mut totals: HashMap<String, TradeTotals> = HashMap::new();
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let symbol: String = row.get(0).map_err(|e| e.to_string())?;
+        let price: Option<String> = row.get(1).map_err(|e| e.to_string())?;
+        let volume: Option<i64> = row.get(2).map_err(|e| e.to_string())?;
+        let price = price.and_then(|price| Decimal::from_str(&price).ok()).unwrap_or_default();
+        let volume = volume.unwrap_or(0) as u64;
+        let symbol_totals = totals.entry(symbol).or_default();
+        symbol_totals.count += 1;
+        symbol_totals.volume = symbol_totals.volume.saturating_add(volume);
+        symbol_totals.notional = symbol_totals.notional.saturating_add(notional(price, volume).unwrap_or(Decimal::MAX));
+    }
+    Ok(totals)
+}
+
+#[derive(Default)]
+struct TradeTotals {
+    count: u64,
+    volume: u64,
+    notional: Decimal,
+}
+
+fn to_json(record: &impl Serialize) -> rusqlite::Result<String> {
+    serde_json::to_string(record).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// Writes one batch in a single transaction. Trades and candles replace their
+/// earlier version, which keeps outlier flags and open candles current.
+fn write_batch(db: &Mutex<Connection>, records: &[StoredRecord]) -> rusqlite::Result<()> {
+    let mut db = db.lock().unwrap();
+    let transaction = db.transaction()?;
+    {
+        let mut trades = transaction
+            .prepare_cached(
+                "INSERT INTO trades (id, symbol, timestamp, data) VALUES (?1, ?2, ?3, ?4) \
+                 ON CONFLICT (id) DO UPDATE SET data = excluded.data",
+            )?;
+        let mut candles = transaction.prepare_cached(
+            "INSERT INTO candles (symbol, interval_secs, timestamp, data) VALUES (?1, ?2, ?3, ?4) \
+             ON CONFLICT (symbol, interval_secs, timestamp) DO UPDATE SET data = excluded.data",
+        )?;
+        let mut rsi = transaction
+            .prepare_cached("INSERT OR REPLACE INTO rsi (id, symbol, timestamp, data) VALUES (?1, ?2, ?3, ?4)")?;
+        for record in records {
+            match record {
+                StoredRecord::Trade(trade) => {
+                    trades.execute(params![trade.id, trade.symbol, nanos(trade.timestamp), to_json(trade)?])?;
+                }
+                StoredRecord::Candle(candle) => {
+                    let key = params![candle.symbol, candle.interval_secs, nanos(candle.open_time), to_json(candle)?];
+                    candles.execute(key)?;
+                }
+                StoredRecord::Rsi(value) => {
+                    rsi.execute(params![value.id, value.symbol, nanos(value.timestamp), to_json(value)?])?;
+                }
+            }
+        }
+    }
+    transaction.commit()
+}
+
+/// Collects queued records until `batch_size` are waiting or the oldest has
+/// waited `flush_interval`, then commits them together.
+async fn write_batches(
+    db: Arc<Mutex<Connection>>,
+    mut pending: mpsc::Receiver<StoredRecord>,
+    batch_size: usize,
+    flush_interval: Duration,
+) {
+    while let Some(first) = pending.recv().await {
+        let mut batch = Vec::with_capacity(batch_size);
+        batch.push(first);
+        let deadline = tokio::time::sleep(flush_interval);
+        tokio::pin!(deadline);
+        while batch.len() < batch_size {
+            tokio::select! {
+                record = pending.recv() => match record {
+                    Some(record) => batch.push(record),
+                    None => break,
+                },
+                _ = &mut deadline => break,
+            }
+        }
+
+        let count = batch.len();
+        let db = db.clone();
+        match tokio::task::spawn_blocking(move || write_batch(&db, &batch)).await {
+            Ok(Ok(())) => tracing::debug!(records = count, "stored batch"),
+            Ok(Err(e)) => tracing::error!(error = %e, records = count, "failed to store batch"),
+            Err(e) => tracing::error!(error = %e, records = count, "store writer panicked"),
+        }
+    }
+}
+
+/// Deletes records past their retention at startup and every `PRUNE_INTERVAL`.
+async fn prune_expired(db: Arc<Mutex<Connection>>, retention: RetentionConfig) {
+    let tables = [
+        ("trades", retention.trades_days),
+        ("candles", retention.candles_days),
+        ("rsi", retention.rsi_days),
+    ];
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let db = db.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let db = db.lock().unwrap();
+            let mut deleted = Vec::new();
+            for (table, days) in tables.iter().filter(|(_, days)| *days > 0) {
+                let cutoff = nanos(Utc::now() - ChronoDuration::days(i64::from(*days)));
+                let sql = format!("DELETE FROM {} WHERE timestamp < ?1", table);
+                deleted.push((*table, db.execute(&sql, [cutoff])?));
+            }
+            Ok::<_, rusqlite::Error>(deleted)
+        })
+        .await;
+        match result {
+            Ok(Ok(deleted)) => {
+                for (table, rows) in deleted.into_iter().filter(|(_, rows)| *rows > 0) {
+                    tracing::info!(table, rows, "pruned expired records");
+                }
+            }
+            Ok(Err(e)) => tracing::error!(error = %e, "failed to prune expired records"),
+            Err(e) => tracing::error!(error = %e, "store pruning panicked"),
+        }
+    }
+}
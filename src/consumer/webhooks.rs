@@ -0,0 +1,530 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use uuid::Uuid;
+use utoipa::ToSchema;
+
+use crate::config::{WebhookEndpoint, WebhookEvent, WebhooksConfig};
+use crate::consumer::{DataProcessor, MarketEvent};
+
+/// Oldest notifications are dropped once an endpoint has this many pending.
+const MAX_PENDING_PER_ENDPOINT: usize = 10_000;
+
+/// Events the dispatcher may fall behind by before it starts dropping them.
+const EVENT_QUEUE_CAPACITY: usize = 1024;
+
+/// How long the outbox writer waits after a change so a burst of changes
+/// is written once.
+const OUTBOX_WRITE_DELAY: Duration = Duration::from_millis(200);
+
+pub const WEBHOOK_ID_HEADER: &str = "x-webhook-id";
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const WEBHOOK_ATTEMPT_HEADER: &str = "x-webhook-attempt";
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+/// JSON body posted to webhooks. The id stays the same across retries so
+/// receivers can deduplicate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub id: String,
+    pub event: WebhookEvent,
+    pub created_at: DateTime<Utc>,
+    /// The `SignalChange` or `FiredAlert` that triggered the notification.
+    pub data: serde_json::Value,
+}
+
+/// Signature sent in `X-Webhook-Signature`: HMAC-SHA256 over
+/// `"{timestamp}.{body}"`, where timestamp is the `X-Webhook-Timestamp` value.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// A notification waiting to be delivered to one endpoint; this is what the
+/// outbox file holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Delivery {
+    endpoint: String,
+    payload: WebhookPayload,
+    attempts: u32,
+    next_attempt_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EndpointStatus {
+    pub name: String,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub pending: usize,
+    pub delivered: u64,
+    /// Notifications given up on after a permanent error or the last attempt.
+    pub failed: u64,
+    /// Notifications dropped because too many were pending.
+    pub dropped: u64,
+    pub retries: u64,
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+struct Failure {
+    status: Option<u16>,
+    message: String,
+    /// Retrying will not help, e.g. a 400 or 404 response.
+    permanent: bool,
+}
+
+struct Endpoint {
+    config: WebhookEndpoint,
+    secret: Option<String>,
+    wake: Notify,
+}
+
+struct State {
+    pending: Vec<Delivery>,
+    status: BTreeMap<String, EndpointStatus>,
+}
+
+struct Inner {
+    endpoints: Vec<Endpoint>,
+    outbox_path: PathBuf,
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    client: reqwest::Client,
+    state: Mutex<State>,
+    /// Signalled when `pending` changed and the outbox needs rewriting.
+    dirty: Notify,
+    /// Keeps a stale snapshot from overwriting a newer one.
+    writing: tokio::sync::Mutex<()>,
+}
+
+/// Delivers RSI signal changes and fired alerts to the configured webhooks.
+///
+/// Each endpoint is served in order by its own task, retrying failures with
+/// exponential backoff. Undelivered notifications are kept in an outbox file
+/// so they survive restarts; it is rewritten in the background shortly after
+/// changes rather than on every one.
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    inner: Arc<Inner>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(config: &WebhooksConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let endpoints = config
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                Ok(Endpoint {
+                    config: endpoint.clone(),
+                    secret: endpoint.secret()?,
+                    wake: Notify::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let status = endpoints
+            .iter()
+            .map(|endpoint| {
+                let status = EndpointStatus {
+                    name: endpoint.config.name.clone(),
+                    url: endpoint.config.url.clone(),
+                    events: endpoint.config.events.clone(),
+                    pending: 0,
+                    delivered: 0,
+                    failed: 0,
+                    dropped: 0,
+                    retries: 0,
+                    last_status: None,
+                    last_error: None,
+                    last_success_at: None,
+                    last_failure_at: None,
+                    next_attempt_at: None,
+                };
+                (endpoint.config.name.clone(), status)
+            })
+            .collect();
+
+        let outbox_path = PathBuf::from(&config.outbox_path);
+        let pending = if endpoints.is_empty() {
+            Vec::new()
+        } else {
+            load_outbox(&outbox_path)?
+                .into_iter()
+                .filter(|delivery| {
+                    let known = endpoints.iter().any(|endpoint| endpoint.config.name == delivery.endpoint);
+                    if !known {
+                        tracing::warn!(id = %delivery.payload.id, endpoint = %delivery.endpoint,
+                            "dropping queued webhook for removed endpoint");
+                    }
+                    known
+                })
+                .collect()
+        };
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                endpoints,
+                outbox_path,
+                max_attempts: config.max_attempts,
+                initial_backoff: Duration::from_millis(config.initial_backoff_ms),
+                max_backoff: Duration::from_millis(config.max_backoff_ms),
+                client,
+                state: Mutex::new(State { pending, status }),
+                dirty: Notify::new(),
+                writing: tokio::sync::Mutex::new(()),
+            }),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.endpoints.is_empty()
+    }
+
+    /// Starts listening for events from `processor` and delivering them.
+    pub fn start(&self, processor: &DataProcessor) {
+        if self.is_empty() {
+            return;
+        }
+
+        let pending = self.inner.state.lock().unwrap().pending.len();
+        if pending > 0 {
+            tracing::info!(pending, "resuming queued webhook notifications");
+        }
+        for index in 0..self.inner.endpoints.len() {
+            let dispatcher = self.clone();
+            tokio::spawn(async move { dispatcher.run_endpoint(index).await });
+        }
+        let dispatcher = self.clone();
+        tokio::spawn(async move { dispatcher.run_writer().await });
+
+        // A queue of its own rather than a broadcast subscription, so a
+        // backlog elsewhere does not cost notifications
+        let dispatcher = self.clone();
+        let mut events = processor.tap("webhooks", EVENT_QUEUE_CAPACITY, |event| matches!(event, MarketEvent::Signal(_) | MarketEvent::Alert(_)));
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                dispatcher.enqueue(&event);
+            }
+        });
+    }
+
+    /// Queues `event` for every endpoint subscribed to it.
+    pub fn enqueue(&self, event: &MarketEvent) {
+        let (kind, data) = match event {
+            MarketEvent::Signal(change) => (WebhookEvent::Signals, serde_json::to_value(change)),
+            MarketEvent::Alert(alert) => (WebhookEvent::Alerts, serde_json::to_value(alert)),
+            _ => return,
+        };
+        let Ok(data) = data else {
+            return;
+        };
+
+        let now = Utc::now();
+        let mut state = self.inner.state.lock().unwrap();
+        let mut woken = Vec::new();
+        for endpoint in &self.inner.endpoints {
+            if !endpoint.config.events.contains(&kind) {
+                continue;
+            }
+            let name = &endpoint.config.name;
+            let queued = state.pending.iter().filter(|delivery| &delivery.endpoint == name).count();
+            if queued >= MAX_PENDING_PER_ENDPOINT {
+                if let Some(oldest) = state.pending.iter().position(|delivery| &delivery.endpoint == name) {
+                    state.pending.remove(oldest);
+                }
+                if let Some(status) = state.status.get_mut(name) {
+                    status.dropped += 1;
+                }
+            }
+            state.pending.push(Delivery {
+                endpoint: name.clone(),
+                payload: WebhookPayload {
+                    id: Uuid::new_v4().to_string(),
+                    event: kind,
+                    created_at: now,
+                    data: data.clone(),
+                },
+                attempts: 0,
+                next_attempt_at: now,
+            });
+            woken.push(&endpoint.wake);
+        }
+        drop(state);
+        if woken.is_empty() {
+            return;
+        }
+        self.persist();
+        for wake in woken {
+            wake.notify_one();
+        }
+    }
+
+    /// Delivery statistics per endpoint.
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let state = self.inner.state.lock().unwrap();
+        state
+            .status
+            .values()
+            .map(|status| {
+                let queued: Vec<&Delivery> = state
+                    .pending
+                    .iter()
+                    .filter(|delivery| delivery.endpoint == status.name)
+                    .collect();
+                EndpointStatus {
+                    pending: queued.len(),
+                    next_attempt_at: queued.first().map(|delivery| delivery.next_attempt_at),
+                    ..status.clone()
+                }
+            })
+            .collect()
+    }
+
+    async fn run_endpoint(&self, index: usize) {
+        let endpoint = &self.inner.endpoints[index];
+        loop {
+            let next = {
+                let state = self.inner.state.lock().unwrap();
+                state
+                    .pending
+                    .iter()
+                    .find(|delivery| delivery.endpoint == endpoint.config.name)
+                    .cloned()
+            };
+            let Some(delivery) = next else {
+                endpoint.wake.notified().await;
+                continue;
+            };
+
+            if let Ok(wait) = (delivery.next_attempt_at - Utc::now()).to_std() {
+                tokio::time::sleep(wait).await;
+            }
+            let result = self.send(endpoint, &delivery).await;
+            self.record(endpoint, &delivery, result);
+        }
+    }
+
+    async fn send(&self, endpoint: &Endpoint, delivery: &Delivery) -> Result<u16, Failure> {
+        let body = serde_json::to_string(&delivery.payload).map_err(|e| Failure {
+            status: None,
+            message: e.to_string(),
+            permanent: true,
+        })?;
+        let timestamp = Utc::now().timestamp();
+
+        let mut request = self
+            .inner
+            .client
+            .post(&endpoint.config.url)
+            .header("content-type", "application/json")
+            .header(WEBHOOK_ID_HEADER, delivery.payload.id.as_str())
+            .header(WEBHOOK_TIMESTAMP_HEADER, timestamp.to_string())
+            .header(WEBHOOK_ATTEMPT_HEADER, (delivery.attempts + 1).to_string());
+        if let Some(secret) = &endpoint.secret {
+            request = request.header(WEBHOOK_SIGNATURE_HEADER, sign(secret, timestamp, &body));
+        }
+
+        match request.body(body).send().await {
+            Ok(response) if response.status().is_success() => Ok(response.status().as_u16()),
+            Ok(response) => {
+                let status = response.status();
+                Err(Failure {
+                    status: Some(status.as_u16()),
+                    message: format!("HTTP {}", status),
+                    permanent: status.is_client_error() && status.as_u16() != 408 && status.as_u16() != 429,
+                })
+            }
+            Err(e) => Err(Failure {
+                status: None,
+                message: e.to_string(),
+                permanent: false,
+            }),
+        }
+    }
+
+    fn record(&self, endpoint: &Endpoint, delivery: &Delivery, result: Result<u16, Failure>) {
+        let now = Utc::now();
+        let name = &endpoint.config.name;
+        let mut state = self.inner.state.lock().unwrap();
+        let Some(position) = state
+            .pending
+            .iter()
+            .position(|queued| &queued.endpoint == name && queued.payload.id == delivery.payload.id)
+        else {
+            // Dropped while the request was in flight
+            return;
+        };
+
+        match result {
+            Ok(code) => {
+                state.pending.remove(position);
+                if let Some(status) = state.status.get_mut(name) {
+                    status.delivered += 1;
+                    status.last_status = Some(code);
+                    status.last_success_at = Some(now);
+                }
+            }
+            Err(failure) => {
+                let attempts = delivery.attempts + 1;
+                let give_up = failure.permanent || attempts >= self.inner.max_attempts;
+                if give_up {
+                    state.pending.remove(position);
+                    tracing::error!(endpoint = %name, id = %delivery.payload.id, attempts,
+                        error = %failure.message, "webhook delivery gave up");
+                } else {
+                    let backoff = self.backoff(attempts);
+                    let queued = &mut state.pending[position];
+                    queued.attempts = attempts;
+                    queued.next_attempt_at = now + chrono::Duration::from_std(backoff).unwrap_or_default();
+                    tracing::warn!(endpoint = %name, attempt = attempts, error = %failure.message,
+                        retry_in = ?backoff, "webhook delivery failed");
+                }
+                if let Some(status) = state.status.get_mut(name) {
+                    if give_up {
+                        status.failed += 1;
+                    } else {
+                        status.retries += 1;
+                    }
+                    status.last_status = failure.status;
+                    status.last_error = Some(failure.message);
+                    status.last_failure_at = Some(now);
+                }
+            }
+        }
+        drop(state);
+        self.persist();
+    }
+
+    /// Delay before the attempt following `attempts` failed ones.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u32 << attempts.saturating_sub(1).min(16);
+        self.inner.initial_backoff.saturating_mul(factor).min(self.inner.max_backoff)
+    }
+
+    /// Schedules an outbox write.
+    fn persist(&self) {
+        self.inner.dirty.notify_one();
+    }
+
+    async fn run_writer(&self) {
+        loop {
+            self.inner.dirty.notified().await;
+            tokio::time::sleep(OUTBOX_WRITE_DELAY).await;
+            self.flush().await;
+        }
+    }
+
+    /// Writes the pending notifications to the outbox file now.
+    pub async fn flush(&self) {
+        let _writing = self.inner.writing.lock().await;
+        let pending = self.inner.state.lock().unwrap().pending.clone();
+        let path = self.inner.outbox_path.clone();
+        let written = tokio::task::spawn_blocking(move || save_outbox(&path, &pending)).await;
+        if let Err(e) = written.map_err(|e| e.to_string()).and_then(|result| result.map_err(|e| e.to_string())) {
+            tracing::warn!(path = %self.inner.outbox_path.display(), error = %e, "failed to write webhook outbox");
+        }
+    }
+}
+
+fn load_outbox(path: &Path) -> Result<Vec<Delivery>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|e| format!("invalid webhook outbox '{}': {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("failed to read webhook outbox '{}': {}", path.display(), e)),
+    }
+}
+
+/// Writes to a temporary file first so a crash never leaves a torn outbox.
+fn save_outbox(path: &Path, pending: &[Delivery]) -> std::io::Result<()> {
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, serde_json::to_vec(pending)?)?;
+    std::fs::rename(&temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(outbox_path: &Path) -> WebhooksConfig {
+        WebhooksConfig {
+            endpoints: vec![WebhookEndpoint {
+                name: "hook".to_string(),
+                url: "http://127.0.0.1:9/hook".to_string(),
+                secret: None,
+                secret_file: None,
+                events: WebhookEvent::all(),
+            }],
+            outbox_path: outbox_path.display().to_string(),
+            max_attempts: 8,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 5000,
+            timeout_ms: 1000,
+        }
+    }
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        // echo -n '1700000000.{"a":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign("secret", 1_700_000_000, r#"{"a":1}"#),
+            "sha256=49f24e537407743fa4a0242bb63b94b9a47ee99cbbe071ccd8a22550ae411686"
+        );
+        assert_ne!(sign("secret", 1_700_000_000, "{}"), sign("secret", 1_700_000_001, "{}"));
+        assert_ne!(sign("secret", 1_700_000_000, "{}"), sign("other", 1_700_000_000, "{}"));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let path = std::env::temp_dir().join(format!("webhook-outbox-{}.json", Uuid::new_v4()));
+        let dispatcher = WebhookDispatcher::new(&config(&path)).unwrap();
+        let delays: Vec<u64> = (1..=6).map(|attempts| dispatcher.backoff(attempts).as_millis() as u64).collect();
+        assert_eq!(delays, [500, 1000, 2000, 4000, 5000, 5000]);
+        assert_eq!(dispatcher.backoff(u32::MAX), Duration::from_millis(5000));
+    }
+
+    #[test]
+    fn outbox_round_trips_and_drops_removed_endpoints() {
+        let path = std::env::temp_dir().join(format!("webhook-outbox-{}.json", Uuid::new_v4()));
+        let delivery = |endpoint: &str| Delivery {
+            endpoint: endpoint.to_string(),
+            payload: WebhookPayload {
+                id: Uuid::new_v4().to_string(),
+                event: WebhookEvent::Alerts,
+                created_at: Utc::now(),
+                data: serde_json::json!({ "symbol": "AAPL" }),
+            },
+            attempts: 2,
+            next_attempt_at: Utc::now(),
+        };
+        save_outbox(&path, &[delivery("hook"), delivery("removed")]).unwrap();
+        assert_eq!(load_outbox(&path).unwrap().len(), 2);
+
+        let dispatcher = WebhookDispatcher::new(&config(&path)).unwrap();
+        let status = dispatcher.status();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].pending, 1);
+
+        assert!(load_outbox(&path.with_extension("missing")).unwrap().is_empty());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(WebhookDispatcher::new(&config(&path)).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,35 +1,82 @@
-mod models;
-mod consumer;
-mod api;
-
 use std::sync::Arc;
+use std::time::Duration;
 
-use consumer::{TradingConsumer, DataProcessor};
-use api::{ApiState, create_routes};
+use trading_system::codec::RecordDecoder;
+use trading_system::config::{AppConfig, Component};
+use trading_system::consumer::{TradingConsumer, DataProcessor, WebhookDispatcher};
+#[cfg(feature = "sqlite")]
+use trading_system::consumer::HistoryStore;
+#[cfg(feature = "parquet")]
+use trading_system::consumer::ParquetArchiver;
+use trading_system::api::{ApiState, create_routes, API_PREFIX};
+use trading_system::telemetry;
+use trading_system::validation::{RejectRouter, TradeValidator};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🚀 Starting Trading Data Consumer...");
-    
-    // Configuration - Read from environment variables
-    let brokers = std::env::var("KAFKA_BROKERS")
-        .unwrap_or_else(|_| "localhost:19092".to_string());
-    let group_id = "trading-consumer-group";
-    let api_port = std::env::var("PORT")
-        .unwrap_or_else(|_| "3001".to_string())
-        .parse::<u16>()
-        .unwrap_or(3001);
+    // Configuration - CLI flags, environment and optional config file
+    let config = AppConfig::from_env(Component::Consumer)?;
+    let _telemetry = telemetry::init(&config.logging, "trading-consumer")?;
+    tracing::info!("starting trading data consumer");
+    tracing::info!("{}", config);
+    let brokers = &config.kafka.brokers;
+    let api_port = config.consumer.api_port;
     
     // Initialize data processor
-    let data_processor = DataProcessor::new();
-    let api_state = Arc::new(ApiState::new(data_processor.clone()));
+    let data_processor = DataProcessor::new()
+        .with_price_specs(config.prices.clone())
+        .with_outliers(&config.outliers);
+    data_processor.metrics().set_labelled_symbols(config.known_symbols());
+    let health = data_processor.health().clone();
+    health.set_thresholds(
+        config.consumer.ready_max_lag,
+        Duration::from_secs(config.consumer.ready_max_idle_secs),
+    );
+    // Reload recent history when persisting it; otherwise it is rebuilt from the topic
+    #[cfg(feature = "sqlite")]
+    let data_processor = match &config.storage.path {
+        Some(path) => {
+            let store = HistoryStore::open(&config.storage)?;
+            tracing::info!(%path, "persisting history");
+            data_processor.with_store(store).await?
+        }
+        None => data_processor,
+    };
+    health.mark_restored();
+
+    // Deliver signal changes and fired alerts to configured webhooks
+    let webhooks = WebhookDispatcher::new(&config.webhooks)?;
+    webhooks.start(&data_processor);
+    // Archive trades and RSI for offline research
+    #[cfg(feature = "parquet")]
+    if let Some(path) = &config.archive.path {
+        ParquetArchiver::new(&config.archive)?.start(&data_processor)?;
+        tracing::info!(%path, "archiving to Parquet");
+    }
+    let api_state = Arc::new(
+        ApiState::new(data_processor.clone(), &config.api).with_webhooks(webhooks),
+    );
+    if !api_state.auth.is_enabled() {
+        tracing::warn!("API authentication is disabled, the API is open to anyone who can reach it");
+    }
     
-    // Initialize consumer
-    let consumer = TradingConsumer::new(&brokers, group_id)?;
+    // Initialize consumer; trades failing validation are counted and routed
+    let rejects = match &config.topics.rejects {
+        Some(topic) => Some(RejectRouter::new(&config.kafka, topic, "consumer")?),
+        None => None,
+    };
+    let consumer = TradingConsumer::new(
+        &config.kafka,
+        &config.consumer.group_id,
+        &config.topics.trade,
+        data_processor,
+    )?
+    .with_decoder(RecordDecoder::new(&config.serialization)?)
+    .with_validation(TradeValidator::new(&config.validation), rejects);
     consumer.subscribe_to_trade_data().await?;
     
-    println!("📡 Connected to Redpanda at {}", brokers);
-    println!("🌐 Starting API server on port {}", api_port);
+    tracing::info!(%brokers, "consumer connected");
+    tracing::info!(port = api_port, "starting API server");
     
     // Start API server
     let api_routes = create_routes(api_state.clone());
@@ -39,27 +86,37 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Start consumer in background
     let consumer_task = tokio::spawn(async move {
         if let Err(e) = consumer.consume_messages().await {
-            eprintln!("❌ Consumer error: {}", e);
+            tracing::error!(error = %e, "consumer stopped");
+            health.record_stopped(e.to_string());
         }
     });
     
     // Start API server in background
     let api_task = tokio::spawn(api_server);
     
-    println!("✅ Consumer and API server started successfully!");
-    println!("📊 API endpoints:");
-    println!("   - Health: http://localhost:{}/health", api_port);
-    println!("   - Prices: http://localhost:{}/prices", api_port);
-    println!("   - RSI: http://localhost:{}/rsi", api_port);
-    println!("⏰ Processing messages... (press Ctrl+C to stop)\n");
+    tracing::info!("consumer and API server started");
+    tracing::info!("API endpoints (OpenAPI: http://localhost:{}/openapi.json):", api_port);
+    tracing::info!("   - Health: http://localhost:{}/health/live, /health/ready", api_port);
+    tracing::info!("   - Metrics: http://localhost:{}/metrics", api_port);
+    let base = format!("http://localhost:{}{}", api_port, API_PREFIX);
+    tracing::info!("   - Prices: {}/prices", base);
+    tracing::info!("   - RSI: {}/rsi", base);
+    tracing::info!("   - Symbols: {}/symbols", base);
+    tracing::info!("   - History: {}/symbols/AAPL/{{trades,rsi/history,candles}}?limit=100", base);
+    tracing::info!("   - Export: {}/export/{{trades,candles,rsi}}?symbols=AAPL&format=csv", base);
+    tracing::info!("   - Alerts: {}/alerts (fired: /alerts/fired)", base);
+    tracing::info!("   - Webhooks: {}/webhooks", base);
+    tracing::info!("   - Stream: ws://localhost:{}{}/ws", api_port, API_PREFIX);
+    tracing::info!("   - Events: {}/stream?symbols=AAPL&channels=rsi", base);
+    tracing::info!("processing messages (press Ctrl+C to stop)");
     
     // Wait for either task to complete
     tokio::select! {
         _ = consumer_task => {
-            println!("Consumer task completed");
+            tracing::info!("consumer task completed");
         }
         _ = api_task => {
-            println!("API server task completed");
+            tracing::info!("API server task completed");
         }
     }
     
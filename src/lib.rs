@@ -0,0 +1,9 @@
+pub mod config;
+pub mod metrics;
+pub mod models;
+pub mod producer;
+pub mod consumer;
+pub mod api;
+pub mod codec;
+pub mod validation;
+pub mod telemetry;
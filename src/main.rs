@@ -1,56 +1,195 @@
-mod models;
-mod producer;
-
-use producer::{TradingProducer, DataGenerator};
+use trading_system::codec::RecordEncoder;
+use trading_system::config::{AppConfig, Component, Simulation};
+use trading_system::models::PriceSpecs;
+use trading_system::telemetry;
+use trading_system::validation::{RejectRouter, TradeValidator};
+use trading_system::producer::{
+    metrics_routes, DataGenerator, OrderBookSimulator, ProducerMetrics, Scenario, ScenarioRunner,
+    TradingProducer,
+};
 use std::time::Duration;
 use tokio::time::sleep;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("🚀 Starting Trading Data Producer...");
-    
-    // Configuration - Use Redpanda Cloud
-    let brokers = "d3hb4i514inc2e6cdmfg.any.us-east-1.mpx.prd.cloud.redpanda.com:9092";
-    let trade_topic = "trade-data";
-    let rsi_topic = "rsi-data";
-    
-    // Initialize producer
-    let producer = TradingProducer::new(brokers, trade_topic, rsi_topic)?;
+    // Configuration - CLI flags, environment and optional config file
+    let config = AppConfig::from_env(Component::Producer)?;
+    let _telemetry = telemetry::init(&config.logging, "trading-producer")?;
+    tracing::info!("starting trading data producer");
+    tracing::info!("{}", config);
+    let brokers = &config.kafka.brokers;
+    let topics = &config.topics;
+
+    // Initialize producer; trades failing validation are counted and routed
+    let rejects = match &topics.rejects {
+        Some(topic) => Some(RejectRouter::new(&config.kafka, topic, "producer")?),
+        None => None,
+    };
+    let metrics = ProducerMetrics::new();
+    let producer = TradingProducer::new(
+        &config.kafka,
+        topics,
+        &config.producer.id,
+        config.producer.max_in_flight,
+        metrics.clone(),
+    )?
+    .with_encoder(RecordEncoder::new(&config.serialization)?)
+    .with_validation(TradeValidator::new(&config.validation), rejects);
+
+    tracing::info!(%brokers, producer_id = %config.producer.id, format = %config.serialization.format,
+        trade = %topics.trade, rsi = %topics.rsi, quote = %topics.quote, depth = %topics.depth, "producer connected");
+
+    // Serve delivery metrics in the background unless disabled
+    if config.producer.metrics_port == 0 {
+        tracing::info!("metrics endpoint disabled");
+    } else {
+        let address = (config.producer.metrics_address, config.producer.metrics_port);
+        let (address, server) = warp::serve(metrics_routes(metrics)).try_bind_ephemeral(address)?;
+        tokio::spawn(server);
+        tracing::info!("metrics: http://{}/metrics", address);
+    }
+
+    // Run a scripted scenario instead of random data if one is configured
+    if let Some(path) = &config.producer.scenario {
+        let scenario = Scenario::from_file(path)?;
+        return run_scenario(&producer, scenario, &config.prices).await;
+    }
+
+    let interval = Duration::from_millis(config.producer.interval_ms);
+    tracing::info!(simulation = %config.producer.simulation, "starting data generation (press Ctrl+C to stop)");
+    match config.producer.simulation {
+        Simulation::OrderBook => run_order_book(&producer, interval, &config.prices).await,
+        Simulation::Random => run_random(&producer, interval, &config.prices).await,
+    }
+}
+
+async fn run_order_book(
+    producer: &TradingProducer,
+    interval: Duration,
+    prices: &PriceSpecs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut simulator = OrderBookSimulator::new().with_price_specs(prices);
+    let mut trade_counter: u64 = 0;
+    let mut quote_counter = 0;
+    let mut iterations: u64 = 0;
+
+    loop {
+        // Each step sends one order into a book; trades only come from matching
+        let update = simulator.step();
+        for trade_data in &update.trades {
+            if let Err(e) = producer.send_trade_data(trade_data).await {
+                tracing::error!(error = %e, "trade data error");
+            } else {
+                trade_counter += 1;
+            }
+        }
+        if let Some(quote) = &update.quote {
+            if let Err(e) = producer.send_quote(quote).await {
+                tracing::error!(error = %e, "quote error");
+            } else {
+                quote_counter += 1;
+            }
+        }
+        if let Some(depth) = &update.depth {
+            if let Err(e) = producer.send_depth_update(depth).await {
+                tracing::error!(error = %e, "depth update error");
+            }
+        }
+
+        // Print stats every 20 orders
+        iterations += 1;
+        if iterations.is_multiple_of(20) {
+            tracing::info!(orders = iterations, trades = trade_counter, quotes = quote_counter, "stats");
+        }
+
+        sleep(interval).await;
+    }
+}
+
+async fn run_random(
+    producer: &TradingProducer,
+    interval: Duration,
+    prices: &PriceSpecs,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut data_generator = DataGenerator::new();
-    
-    println!("📡 Connected to Redpanda at {}", brokers);
-    println!("📊 Producing data to topics: {} and {}", trade_topic, rsi_topic);
-    println!("⏰ Starting data generation (press Ctrl+C to stop)...\n");
-    
+    data_generator.set_price_specs(prices);
+
     // Main data generation loop
-    let mut trade_counter = 0;
+    let mut trade_counter: u64 = 0;
     let mut rsi_counter = 0;
-    
+
     loop {
         // Generate and send trade data
         let trade_data = data_generator.generate_trade_data();
         if let Err(e) = producer.send_trade_data(&trade_data).await {
-            eprintln!("❌ Trade data error: {}", e);
+            tracing::error!(error = %e, "trade data error");
         } else {
             trade_counter += 1;
         }
-        
+
         // Generate and send RSI data (every 5th iteration)
-        if trade_counter % 5 == 0 {
+        if trade_counter.is_multiple_of(5) {
             let rsi_data = data_generator.generate_rsi_data();
             if let Err(e) = producer.send_rsi_data(&rsi_data).await {
-                eprintln!("❌ RSI data error: {}", e);
+                tracing::error!(error = %e, "RSI data error");
             } else {
                 rsi_counter += 1;
             }
         }
-        
+
         // Print stats every 10 trades
-        if trade_counter % 10 == 0 {
-            println!("📈 Stats - Trades: {}, RSI: {}", trade_counter, rsi_counter);
+        if trade_counter.is_multiple_of(10) {
+            tracing::info!(trades = trade_counter, rsi = rsi_counter, "stats");
         }
-        
+
         // Wait before next iteration
-        sleep(Duration::from_millis(500)).await;
+        sleep(interval).await;
+    }
+}
+
+async fn run_scenario(
+    producer: &TradingProducer,
+    scenario: Scenario,
+    prices: &PriceSpecs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    tracing::info!(
+        name = scenario.name.as_deref().unwrap_or("unnamed"),
+        seed = scenario.seed,
+        steps = scenario.steps.len(),
+        duration = ?scenario.duration(),
+        "running scenario"
+    );
+    for (index, step) in scenario.steps.iter().enumerate() {
+        tracing::info!("   {}. {}", index + 1, step);
+    }
+
+    // Random RSI messages would contradict the scripted signals, so only trades are sent
+    let mut runner = ScenarioRunner::new(scenario).with_price_specs(prices);
+    let mut trade_counter: u64 = 0;
+
+    loop {
+        if runner.at_step_start() {
+            if let Some((number, step)) = runner.current_step() {
+                tracing::info!(step = number, "{}", step);
+            }
+        }
+
+        let Some(trades) = runner.next_tick() else {
+            break;
+        };
+
+        for trade_data in &trades {
+            if let Err(e) = producer.send_trade_data(trade_data).await {
+                tracing::error!(error = %e, "trade data error");
+            } else {
+                trade_counter += 1;
+            }
+        }
+
+        sleep(runner.tick_interval()).await;
     }
+
+    producer.flush().await?;
+    tracing::info!(trades = trade_counter, "scenario complete");
+    Ok(())
 }
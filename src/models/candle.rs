@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use utoipa::ToSchema;
+
+use crate::models::{notional, Decimal};
+
+/// OHLCV bar aggregated from trades over a fixed interval.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Candle {
+    pub symbol: String,
+    pub interval_secs: u32,
+    pub open_time: DateTime<Utc>,
+    pub close_time: DateTime<Utc>,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: u64,
+    /// Sum of `price * volume` over the bar's trades.
+    #[serde(default)]
+    pub notional: Decimal,
+    pub trade_count: u64,
+    /// False while trades can still be added to the bar.
+    pub closed: bool,
+}
+
+impl Candle {
+    /// Starts a bar for the interval containing `timestamp`.
+    pub fn open(symbol: String, interval_secs: u32, price: Decimal, volume: u64, timestamp: DateTime<Utc>) -> Self {
+        let interval = Duration::seconds(interval_secs as i64);
+        let open_time = timestamp.duration_trunc(interval).unwrap_or(timestamp);
+        Self {
+            symbol,
+            interval_secs,
+            open_time,
+            close_time: open_time + interval,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume,
+            notional: notional(price, volume).unwrap_or(Decimal::MAX),
+            trade_count: 1,
+            closed: false,
+        }
+    }
+
+    /// Volume-weighted average price, `None` for a bar without volume.
+    pub fn vwap(&self) -> Option<Decimal> {
+        (self.volume > 0).then(|| self.notional / Decimal::from(self.volume))
+    }
+
+    pub fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        timestamp >= self.open_time && timestamp < self.close_time
+    }
+
+    pub fn update(&mut self, price: Decimal, volume: u64) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        // Totals saturate rather than panic on a trade the validator missed.
+        self.volume = self.volume.saturating_add(volume);
+        self.notional = self.notional.saturating_add(notional(price, volume).unwrap_or(Decimal::MAX));
+        self.trade_count += 1;
+    }
+}
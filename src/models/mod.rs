@@ -1,5 +1,11 @@
 pub mod trade_data;
 pub mod rsi_data;
+pub mod quote;
+pub mod candle;
+pub mod price;
 
 pub use trade_data::*;
 pub use rsi_data::*;
+pub use quote::*;
+pub use candle::*;
+pub use price::*;
@@ -0,0 +1,155 @@
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+pub use rust_decimal::Decimal;
+
+/// Most decimal places a price may carry; archives store prices at this scale.
+pub const MAX_PRICE_PRECISION: u32 = 8;
+
+/// Minimum increment and number of decimal places of a symbol's prices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceSpec {
+    pub tick_size: Decimal,
+    pub precision: u32,
+}
+
+impl Default for PriceSpec {
+    /// Cent ticks, two decimal places.
+    fn default() -> Self {
+        Self { tick_size: Decimal::new(1, 2), precision: 2 }
+    }
+}
+
+impl PriceSpec {
+    pub fn new(tick_size: Decimal, precision: u32) -> Result<Self, String> {
+        if tick_size <= Decimal::ZERO {
+            return Err(format!("tick size {} must be positive", tick_size));
+        }
+        if precision > MAX_PRICE_PRECISION {
+            return Err(format!("precision {} exceeds {} decimal places", precision, MAX_PRICE_PRECISION));
+        }
+        if tick_size.normalize().scale() > precision {
+            return Err(format!("tick size {} needs more than {} decimal places", tick_size, precision));
+        }
+        Ok(Self { tick_size, precision })
+    }
+
+    /// Rounds `price` to the nearest tick, written with exactly `precision`
+    /// decimal places.
+    pub fn round(&self, price: Decimal) -> Decimal {
+        let mut rounded = (price / self.tick_size).round() * self.tick_size;
+        rounded.rescale(self.precision);
+        rounded
+    }
+
+    /// Writes `price` with exactly `precision` decimal places when that drops
+    /// no digits, so a price reads the same whichever format carried it.
+    pub fn normalize(&self, price: Decimal) -> Decimal {
+        let mut normalized = price.normalize();
+        if normalized.scale() > self.precision {
+            return price;
+        }
+        normalized.rescale(self.precision);
+        normalized
+    }
+
+    /// Converts a simulated `f64` price into an exact one on the tick grid.
+    pub fn from_f64(&self, price: f64) -> Option<Decimal> {
+        Decimal::from_f64(price).map(|price| self.round(price))
+    }
+
+    pub fn ticks(&self, price: Decimal) -> i64 {
+        (price / self.tick_size).round().to_i64().unwrap_or_default()
+    }
+
+    pub fn from_ticks(&self, ticks: i64) -> Decimal {
+        self.round(Decimal::from(ticks) * self.tick_size)
+    }
+}
+
+impl fmt::Display for PriceSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tick {} / {} dp", self.tick_size, self.precision)
+    }
+}
+
+/// Price specs per symbol, falling back to a default for unlisted symbols.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PriceSpecs {
+    pub default: PriceSpec,
+    pub symbols: HashMap<String, PriceSpec>,
+}
+
+impl PriceSpecs {
+    pub fn get(&self, symbol: &str) -> PriceSpec {
+        self.symbols.get(symbol).copied().unwrap_or(self.default)
+    }
+}
+
+/// Traded value `price * volume`, `None` when it does not fit a `Decimal`.
+pub fn notional(price: Decimal, volume: u64) -> Option<Decimal> {
+    price.checked_mul(Decimal::from(volume))
+}
+
+/// Indicator math runs in `f64`; this is the one place prices cross over.
+pub fn price_to_f64(price: Decimal) -> f64 {
+    price.to_f64().unwrap_or(f64::NAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn specs_need_a_positive_tick_that_fits_the_precision() {
+        assert_eq!(PriceSpec::new(decimal("0.05"), 2), Ok(PriceSpec { tick_size: decimal("0.05"), precision: 2 }));
+        // Trailing zeros do not count against the precision
+        assert!(PriceSpec::new(decimal("0.500"), 1).is_ok());
+        assert!(PriceSpec::new(Decimal::ZERO, 2).is_err());
+        assert!(PriceSpec::new(decimal("-0.01"), 2).is_err());
+        assert!(PriceSpec::new(decimal("0.001"), 2).is_err());
+        assert!(PriceSpec::new(decimal("0.01"), MAX_PRICE_PRECISION + 1).is_err());
+    }
+
+    #[test]
+    fn prices_round_to_the_nearest_tick_at_the_spec_precision() {
+        let nickel = PriceSpec::new(decimal("0.05"), 2).unwrap();
+        assert_eq!(nickel.round(decimal("100.02")).to_string(), "100.00");
+        assert_eq!(nickel.round(decimal("100.03")).to_string(), "100.05");
+        assert_eq!(nickel.round(decimal("100")).to_string(), "100.00");
+        assert_eq!(nickel.ticks(decimal("100.05")), 2001);
+        assert_eq!(nickel.from_ticks(2001).to_string(), "100.05");
+        assert_eq!(nickel.from_f64(99.99).unwrap().to_string(), "100.00");
+        assert_eq!(nickel.from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn normalize_pads_to_the_precision_without_dropping_digits() {
+        let spec = PriceSpec::default();
+        assert_eq!(spec.normalize(decimal("150.5")).to_string(), "150.50");
+        assert_eq!(spec.normalize(decimal("150.5000")).to_string(), "150.50");
+        assert_eq!(spec.normalize(decimal("150.505")).to_string(), "150.505");
+    }
+
+    #[test]
+    fn unlisted_symbols_use_the_default_spec() {
+        let tenth = PriceSpec::new(decimal("0.1"), 1).unwrap();
+        let specs = PriceSpecs { default: PriceSpec::default(), symbols: [("BRK.A".to_string(), tenth)].into() };
+        assert_eq!(specs.get("BRK.A"), tenth);
+        assert_eq!(specs.get("AAPL"), PriceSpec::default());
+    }
+
+    #[test]
+    fn conversions_to_f64_and_notional() {
+        assert_eq!(price_to_f64(decimal("150.25")), 150.25);
+        assert_eq!(price_to_f64(decimal("-0.5")), -0.5);
+        assert_eq!(notional(decimal("150.25"), 4), Some(decimal("601.00")));
+        assert_eq!(notional(Decimal::MAX, 2), None);
+    }
+}
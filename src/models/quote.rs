@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::Decimal;
+
+/// Top of book for a symbol.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quote {
+    pub id: String,
+    pub symbol: String,
+    pub bid_price: Decimal,
+    pub bid_size: u64,
+    pub ask_price: Decimal,
+    pub ask_size: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriceLevel {
+    pub price: Decimal,
+    /// Total resting size at `price`; 0 means the level was removed.
+    pub size: u64,
+}
+
+/// Incremental L2 update: only levels that changed since the previous
+/// update for the same symbol, in `sequence` order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthUpdate {
+    pub id: String,
+    pub symbol: String,
+    pub sequence: u64,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Quote {
+    pub fn new(symbol: String, bid_price: Decimal, bid_size: u64, ask_price: Decimal, ask_size: u64) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            symbol,
+            bid_price,
+            bid_size,
+            ask_price,
+            ask_size,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn spread(&self) -> Decimal {
+        self.ask_price - self.bid_price
+    }
+
+    pub fn mid_price(&self) -> Decimal {
+        (self.bid_price + self.ask_price) / Decimal::TWO
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+impl DepthUpdate {
+    pub fn new(symbol: String, sequence: u64, bids: Vec<PriceLevel>, asks: Vec<PriceLevel>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            symbol,
+            sequence,
+            bids,
+            asks,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
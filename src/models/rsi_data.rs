@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::str::FromStr;
 use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RsiData {
     pub id: String,
     pub symbol: String,
@@ -12,7 +14,7 @@ pub struct RsiData {
     pub signal: RsiSignal,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum RsiSignal {
     Overbought,
     Oversold,
@@ -37,7 +39,26 @@ impl RsiData {
         }
     }
 
+    /// Stamps the value with the time of the trade it was calculated from.
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
     }
 }
+
+impl FromStr for RsiSignal {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "overbought" => Ok(RsiSignal::Overbought),
+            "oversold" => Ok(RsiSignal::Oversold),
+            "neutral" => Ok(RsiSignal::Neutral),
+            other => Err(format!("unknown signal '{}'", other)),
+        }
+    }
+}
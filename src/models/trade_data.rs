@@ -1,26 +1,55 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::models::{notional, Decimal};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TradeData {
     pub id: String,
     pub symbol: String,
-    pub price: f64,
+    /// Exact, and written as a string in JSON.
+    pub price: Decimal,
     pub volume: u64,
     pub timestamp: DateTime<Utc>,
     pub side: TradeSide,
     pub exchange: String,
+    /// Set by the consumer's outlier filter; absent for ordinary trades.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outlier: Option<OutlierStatus>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum TradeSide {
     Buy,
     Sell,
 }
 
+/// Where a suspect trade stands in the consumer's outlier quarantine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum OutlierStatus {
+    /// Held out of candles and indicators until later trades confirm or refute it.
+    Suspect,
+    /// Later trades corroborated the move, so it counts after all.
+    Confirmed,
+    /// The next trade returned to the prior range; it never counts.
+    Discarded,
+}
+
+impl OutlierStatus {
+    /// Label for the outlier metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutlierStatus::Suspect => "suspect",
+            OutlierStatus::Confirmed => "confirmed",
+            OutlierStatus::Discarded => "discarded",
+        }
+    }
+}
+
 impl TradeData {
-    pub fn new(symbol: String, price: f64, volume: u64, side: TradeSide, exchange: String) -> Self {
+    pub fn new(symbol: String, price: Decimal, volume: u64, side: TradeSide, exchange: String) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
             symbol,
@@ -29,9 +58,21 @@ impl TradeData {
             timestamp: Utc::now(),
             side,
             exchange,
+            outlier: None,
         }
     }
 
+    /// Whether the trade is held out of candles and indicators.
+    pub fn is_outlier(&self) -> bool {
+        matches!(self.outlier, Some(OutlierStatus::Suspect | OutlierStatus::Discarded))
+    }
+
+    /// Traded value, `price * volume`; `None` if it overflows, which the
+    /// validator rejects.
+    pub fn notional(&self) -> Option<Decimal> {
+        notional(self.price, self.volume)
+    }
+
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
     }
@@ -1,16 +1,35 @@
-use rand::Rng;
-use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
 
-use crate::models::{TradeData, RsiData, TradeSide};
+use crate::models::{price_to_f64, PriceSpecs, TradeData, RsiData, TradeSide};
 
 pub struct DataGenerator {
     symbols: Vec<String>,
     base_prices: HashMap<String, f64>,
     rsi_values: HashMap<String, f64>,
+    halted: HashSet<String>,
+    price_specs: PriceSpecs,
+    rng: StdRng,
+}
+
+impl Default for DataGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DataGenerator {
     pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+
+    /// Creates a generator whose output is fully determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(mut rng: StdRng) -> Self {
         let symbols = vec![
             "AAPL".to_string(),
             "GOOGL".to_string(),
@@ -26,54 +45,126 @@ impl DataGenerator {
         let mut rsi_values = HashMap::new();
 
         for symbol in &symbols {
-            base_prices.insert(symbol.clone(), rand::thread_rng().gen_range(50.0..500.0));
-            rsi_values.insert(symbol.clone(), rand::thread_rng().gen_range(20.0..80.0));
+            base_prices.insert(symbol.clone(), rng.gen_range(50.0..500.0));
+            rsi_values.insert(symbol.clone(), rng.gen_range(20.0..80.0));
         }
 
         Self {
             symbols,
             base_prices,
             rsi_values,
+            halted: HashSet::new(),
+            price_specs: PriceSpecs::default(),
+            rng,
         }
     }
 
+    /// Rounds generated prices to each symbol's tick size and precision.
+    pub fn set_price_specs(&mut self, specs: &PriceSpecs) {
+        self.price_specs = specs.clone();
+    }
+
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    pub fn price(&self, symbol: &str) -> Option<f64> {
+        self.base_prices.get(symbol).copied()
+    }
+
+    /// Sets the current price of `symbol`, adding it to the universe if unknown.
+    pub fn set_price(&mut self, symbol: &str, price: f64) {
+        if !self.base_prices.contains_key(symbol) {
+            self.symbols.push(symbol.to_string());
+            self.rsi_values.insert(symbol.to_string(), 50.0);
+        }
+        self.base_prices.insert(symbol.to_string(), price);
+    }
+
+    /// Stops `symbol` from trading until [`DataGenerator::resume`] is called.
+    pub fn halt(&mut self, symbol: &str) {
+        self.halted.insert(symbol.to_string());
+    }
+
+    pub fn resume(&mut self, symbol: &str) {
+        self.halted.remove(symbol);
+    }
+
+    pub fn is_halted(&self, symbol: &str) -> bool {
+        self.halted.contains(symbol)
+    }
+
     pub fn generate_trade_data(&mut self) -> TradeData {
-        let mut rng = rand::thread_rng();
-        let symbol = self.symbols[rng.gen_range(0..self.symbols.len())].clone();
-        
+        self.generate_random_trade(0.05) // ±5% volatility
+            .expect("all symbols are halted")
+    }
+
+    /// Generates a trade for a random non-halted symbol, moving its price by
+    /// up to `volatility` (as a fraction). Returns `None` if every symbol is halted.
+    pub fn generate_random_trade(&mut self, volatility: f64) -> Option<TradeData> {
+        let tradable: Vec<&String> = self.symbols.iter()
+            .filter(|symbol| !self.halted.contains(*symbol))
+            .collect();
+        if tradable.is_empty() {
+            return None;
+        }
+        let symbol = tradable[self.rng.gen_range(0..tradable.len())].clone();
+
         // Get current base price and add some volatility
         let base_price = *self.base_prices.get(&symbol).unwrap();
-        let volatility = rng.gen_range(-0.05..0.05); // ±5% volatility
-        let price = base_price * (1.0 + volatility);
-        
+        let change = if volatility > 0.0 {
+            self.rng.gen_range(-volatility..volatility)
+        } else {
+            0.0
+        };
+        let price = base_price * (1.0 + change);
+
+        Some(self.generate_trade_for(&symbol, price))
+    }
+
+    /// Generates a trade for `symbol` at `price` rounded to the symbol's tick,
+    /// which becomes the symbol's new base price.
+    pub fn generate_trade_for(&mut self, symbol: &str, price: f64) -> TradeData {
+        let price = self.price_specs.get(symbol).from_f64(price).unwrap_or_default();
+        if !self.base_prices.contains_key(symbol) {
+            self.set_price(symbol, price_to_f64(price));
+        }
         // Update base price for next trade
-        self.base_prices.insert(symbol.clone(), price);
-        
-        let volume = rng.gen_range(100..10000);
-        let side = if rng.gen_bool(0.5) { TradeSide::Buy } else { TradeSide::Sell };
-        let exchange = match rng.gen_range(0..3) {
+        self.base_prices.insert(symbol.to_string(), price_to_f64(price));
+
+        let volume = self.rng.gen_range(100..10000);
+        let side = if self.rng.gen_bool(0.5) { TradeSide::Buy } else { TradeSide::Sell };
+        let exchange = match self.rng.gen_range(0..3) {
             0 => "NYSE".to_string(),
             1 => "NASDAQ".to_string(),
             _ => "BATS".to_string(),
         };
 
-        TradeData::new(symbol, price, volume, side, exchange)
+        TradeData::new(symbol.to_string(), price, volume, side, exchange)
     }
 
     pub fn generate_rsi_data(&mut self) -> RsiData {
-        let mut rng = rand::thread_rng();
-        let symbol = self.symbols[rng.gen_range(0..self.symbols.len())].clone();
-        
+        let symbol = self.symbols[self.rng.gen_range(0..self.symbols.len())].clone();
+
         // Get current RSI and add some movement
         let current_rsi = *self.rsi_values.get(&symbol).unwrap();
-        let rsi_change = rng.gen_range(-2.0..2.0);
+        let rsi_change = self.rng.gen_range(-2.0..2.0);
         let rsi_value = (current_rsi + rsi_change).clamp(0.0, 100.0);
-        
+
         // Update RSI for next calculation
         self.rsi_values.insert(symbol.clone(), rsi_value);
-        
+
         let period = 14; // Standard RSI period
 
         RsiData::new(symbol, rsi_value, period)
     }
+
+    /// Noise factor in `[-amplitude, amplitude)` drawn from the generator's RNG.
+    pub fn noise(&mut self, amplitude: f64) -> f64 {
+        if amplitude > 0.0 {
+            self.rng.gen_range(-amplitude..amplitude)
+        } else {
+            0.0
+        }
+    }
 }
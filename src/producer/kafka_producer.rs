@@ -1,103 +1,320 @@
-use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
-use rdkafka::util::Timeout;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::Instrument;
 
-use crate::models::{TradeData, RsiData};
+use crate::codec::{Envelope, EnvelopeStamper, MessageType, RecordEncoder};
+use crate::config::{log_security, KafkaConfig, TopicsConfig};
+use crate::models::{DepthUpdate, Quote, TradeData, RsiData};
+use crate::producer::ProducerMetrics;
+use crate::telemetry::{self, RECORD_ID_HEADER, SYMBOL_HEADER};
+use crate::validation::{RejectRouter, TradeValidator};
 
 pub struct TradingProducer {
     producer: FutureProducer,
     trade_topic: String,
     rsi_topic: String,
+    quote_topic: String,
+    depth_topic: String,
+    in_flight: Arc<Semaphore>,
+    max_in_flight: u32,
+    metrics: ProducerMetrics,
+    encoder: RecordEncoder,
+    envelopes: EnvelopeStamper,
+    /// Rejects are numbered apart, so they leave no gap in the trade sequence.
+    reject_envelopes: EnvelopeStamper,
+    validator: TradeValidator,
+    rejects: Option<RejectRouter>,
 }
 
 impl TradingProducer {
-    pub fn new(brokers: &str, trade_topic: &str, rsi_topic: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut config = ClientConfig::new();
-        config
-            .set("bootstrap.servers", brokers)
+    pub fn new(
+        kafka: &KafkaConfig,
+        topics: &TopicsConfig,
+        producer_id: &str,
+        max_in_flight: u32,
+        metrics: ProducerMetrics,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let builder = kafka.client_builder()?
             .set("message.timeout.ms", "5000")
             .set("acks", "all")
             .set("retries", "3")
             .set("retry.backoff.ms", "100");
+        log_security(&builder);
 
-        // Add SASL authentication if environment variables are set
-        // Only use SASL_SSL if all required variables are present
-        if let (Ok(security_protocol), Ok(sasl_mechanism), Ok(sasl_username), Ok(sasl_password)) = (
-            std::env::var("KAFKA_SECURITY_PROTOCOL"),
-            std::env::var("KAFKA_SASL_MECHANISM"),
-            std::env::var("KAFKA_SASL_USERNAME"),
-            std::env::var("KAFKA_SASL_PASSWORD")
-        ) {
-            println!("🔐 Using SASL authentication");
-            config.set("security.protocol", security_protocol);
-            config.set("sasl.mechanism", sasl_mechanism);
-            config.set("sasl.username", sasl_username);
-            config.set("sasl.password", sasl_password);
-        } else {
-            println!("🔓 Using PLAINTEXT connection (no SASL)");
-            config.set("security.protocol", "PLAINTEXT");
-        }
-
-        let producer: FutureProducer = config.create()?;
+        let producer: FutureProducer = builder.build().create()?;
 
         Ok(Self {
             producer,
-            trade_topic: trade_topic.to_string(),
-            rsi_topic: rsi_topic.to_string(),
+            trade_topic: topics.trade.clone(),
+            rsi_topic: topics.rsi.clone(),
+            quote_topic: topics.quote.clone(),
+            depth_topic: topics.depth.clone(),
+            in_flight: Arc::new(Semaphore::new(max_in_flight as usize)),
+            max_in_flight,
+            metrics,
+            encoder: RecordEncoder::default(),
+            envelopes: EnvelopeStamper::new(producer_id),
+            reject_envelopes: EnvelopeStamper::new(producer_id),
+            validator: TradeValidator::default(),
+            rejects: None,
         })
     }
 
+    /// Encodes trades and RSI values with `encoder` instead of plain JSON.
+    /// Quotes and depth updates are always JSON.
+    pub fn with_encoder(mut self, encoder: RecordEncoder) -> Self {
+        self.encoder = encoder;
+        self
+    }
+
+    /// Checks trades with `validator` before sending them, forwarding
+    /// rejected trades to `rejects` if set.
+    pub fn with_validation(mut self, validator: TradeValidator, rejects: Option<RejectRouter>) -> Self {
+        self.validator = validator;
+        self.rejects = rejects;
+        self
+    }
+
+    pub fn metrics(&self) -> &ProducerMetrics {
+        &self.metrics
+    }
+
+    /// Enqueues a trade and returns once it is handed to the client. The
+    /// delivery report is tracked in the background. Trades failing
+    /// validation are not sent to the trade topic and return the rejection.
     pub async fn send_trade_data(&self, trade_data: &TradeData) -> Result<(), Box<dyn std::error::Error>> {
-        let json_data = trade_data.to_json()?;
-        let key = &trade_data.symbol;
-        
-        let record = FutureRecord::to(&self.trade_topic)
-            .key(key)
-            .payload(&json_data);
-
-        match self.producer.send(record, Timeout::After(Duration::from_secs(5))).await {
-            Ok(_) => {
-                println!("✅ Sent trade data: {} - {} @ ${:.2}", 
-                    trade_data.symbol, 
-                    format!("{:?}", trade_data.side), 
-                    trade_data.price
-                );
-                Ok(())
-            }
-            Err((e, _)) => {
-                eprintln!("❌ Failed to send trade data: {}", e);
-                Err(e.into())
+        if let Err(rejection) = self.validator.validate(trade_data) {
+            self.metrics.record_rejected(&self.trade_topic, rejection.reason.as_str());
+            if let Some(rejects) = &self.rejects {
+                // Encoded and enveloped as it would have been sent, so it can be replayed
+                let payload = self.encoder.encode(&self.trade_topic, trade_data).await?;
+                let envelope = self.reject_envelopes.next(MessageType::Trade);
+                let headers = record_headers(&trade_data.id, &trade_data.symbol, &envelope);
+                rejects.route(&trade_data.symbol, &payload, &headers, &rejection);
             }
+            return Err(rejection.into());
         }
+        let payload = self.encoder.encode(&self.trade_topic, trade_data).await?;
+        let description = format!("trade data: {} - {:?} @ ${:.2}",
+            trade_data.symbol,
+            trade_data.side,
+            trade_data.price
+        );
+        self.send_tracked(&self.trade_topic, MessageType::Trade, &trade_data.id, &trade_data.symbol, payload, description).await
     }
 
+    /// Enqueues an RSI record; see [`TradingProducer::send_trade_data`].
     pub async fn send_rsi_data(&self, rsi_data: &RsiData) -> Result<(), Box<dyn std::error::Error>> {
-        let json_data = rsi_data.to_json()?;
-        let key = &rsi_data.symbol;
-        
-        let record = FutureRecord::to(&self.rsi_topic)
-            .key(key)
-            .payload(&json_data);
-
-        match self.producer.send(record, Timeout::After(Duration::from_secs(5))).await {
-            Ok(_) => {
-                println!("📊 Sent RSI data: {} - RSI: {:.2} ({:?})", 
-                    rsi_data.symbol, 
-                    rsi_data.rsi_value,
-                    rsi_data.signal
-                );
-                Ok(())
+        let payload = self.encoder.encode(&self.rsi_topic, rsi_data).await?;
+        let description = format!("RSI data: {} - RSI: {:.2} ({:?})",
+            rsi_data.symbol,
+            rsi_data.rsi_value,
+            rsi_data.signal
+        );
+        self.send_tracked(&self.rsi_topic, MessageType::Rsi, &rsi_data.id, &rsi_data.symbol, payload, description).await
+    }
+
+    pub async fn send_quote(&self, quote: &Quote) -> Result<(), Box<dyn std::error::Error>> {
+        let payload = quote.to_json()?.into_bytes();
+        let description = format!("quote: {} - {:.2} x {:.2}",
+            quote.symbol,
+            quote.bid_price,
+            quote.ask_price
+        );
+        self.send_tracked(&self.quote_topic, MessageType::Quote, &quote.id, &quote.symbol, payload, description).await
+    }
+
+    pub async fn send_depth_update(&self, depth: &DepthUpdate) -> Result<(), Box<dyn std::error::Error>> {
+        let payload = depth.to_json()?.into_bytes();
+        let description = format!("depth update: {} #{} ({} bids, {} asks)",
+            depth.symbol,
+            depth.sequence,
+            depth.bids.len(),
+            depth.asks.len()
+        );
+        self.send_tracked(&self.depth_topic, MessageType::Depth, &depth.id, &depth.symbol, payload, description).await
+    }
+
+    /// Waits for a slot in the in-flight window, enqueues the record and spawns
+    /// a task that records its delivery report. The envelope, record id, symbol
+    /// and trace context travel as headers so the consumer can continue the span.
+    async fn send_tracked(
+        &self,
+        topic: &str,
+        message_type: MessageType,
+        id: &str,
+        symbol: &str,
+        payload: Vec<u8>,
+        description: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let span = tracing::debug_span!("produce", record_id = %id, %symbol, %topic);
+        let permit = self.in_flight.clone().acquire_owned().instrument(span.clone()).await?;
+
+        let started = Instant::now();
+        let envelope = self.envelopes.next(message_type);
+        let sent = span.in_scope(|| {
+            let mut headers = OwnedHeaders::new();
+            for (key, value) in &record_headers(id, symbol, &envelope) {
+                headers = headers.insert(Header { key, value: Some(value) });
             }
-            Err((e, _)) => {
-                eprintln!("❌ Failed to send RSI data: {}", e);
-                Err(e.into())
+            let record = FutureRecord::to(topic)
+                .key(symbol)
+                .payload(&payload)
+                .headers(headers);
+            self.producer.send_result(record).map_err(|(e, _)| e)
+        });
+        let delivery = match sent {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                self.metrics.record_failed(topic, &failure_reason(&e), false, false);
+                span.in_scope(|| tracing::error!(error = %e, "failed to enqueue {}", description));
+                return Err(e.into());
             }
-        }
+        };
+        self.metrics.record_enqueued(topic);
+
+        let metrics = self.metrics.clone();
+        let topic = topic.to_string();
+        tokio::spawn(async move {
+            match delivery.await {
+                Ok(Ok((partition, offset))) => {
+                    metrics.record_delivered(&topic, started.elapsed());
+                    tracing::debug!(partition, offset, "sent {}", description);
+                }
+                Ok(Err((e, _))) => {
+                    metrics.record_failed(&topic, &failure_reason(&e), retries_exhausted(&e), true);
+                    tracing::error!(error = %e, "failed to send {}", description);
+                }
+                Err(_) => {
+                    // The producer was dropped before the report arrived
+                    metrics.record_failed(&topic, "canceled", false, true);
+                }
+            }
+            drop(permit);
+        }.instrument(span));
+
+        Ok(())
     }
 
+    /// Flushes queued messages and waits for every outstanding delivery report.
+    /// The flushes block, so the runtime is told to move other tasks off this
+    /// thread.
     pub async fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
-        self.producer.flush(Duration::from_secs(10))?;
+        tokio::task::block_in_place(|| self.producer.flush(Duration::from_secs(10)))?;
+        if let Some(rejects) = &self.rejects {
+            tokio::task::block_in_place(|| rejects.flush())?;
+        }
+        let _all = self.in_flight.acquire_many(self.max_in_flight).await?;
         Ok(())
     }
 }
+
+/// Headers sent with every record: its id, symbol, envelope and the trace
+/// context of the current span.
+fn record_headers(id: &str, symbol: &str, envelope: &Envelope) -> HashMap<String, String> {
+    let mut headers = HashMap::from([
+        (RECORD_ID_HEADER.to_string(), id.to_string()),
+        (SYMBOL_HEADER.to_string(), symbol.to_string()),
+    ]);
+    headers.extend(envelope.to_headers().map(|(key, value)| (key.to_string(), value)));
+    headers.extend(telemetry::current_trace_context());
+    headers
+}
+
+fn failure_reason(error: &KafkaError) -> String {
+    match error.rdkafka_error_code() {
+        Some(code) => format!("{:?}", code),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Whether librdkafka retried this error before giving up.
+fn retries_exhausted(error: &KafkaError) -> bool {
+    matches!(
+        error.rdkafka_error_code(),
+        Some(
+            RDKafkaErrorCode::MessageTimedOut
+                | RDKafkaErrorCode::RequestTimedOut
+                | RDKafkaErrorCode::NetworkException
+                | RDKafkaErrorCode::LeaderNotAvailable
+                | RDKafkaErrorCode::NotLeaderForPartition
+                | RDKafkaErrorCode::NotEnoughReplicas
+                | RDKafkaErrorCode::NotEnoughReplicasAfterAppend
+                | RDKafkaErrorCode::AllBrokersDown
+                | RDKafkaErrorCode::BrokerTransportFailure
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SecurityConfig;
+    use crate::models::{RsiData, TradeSide};
+    use std::collections::BTreeMap;
+
+    /// Deliveries fail after half a second, as nothing listens on the broker
+    /// address.
+    fn unreachable_kafka() -> KafkaConfig {
+        KafkaConfig {
+            brokers: "127.0.0.1:1".to_string(),
+            security: SecurityConfig::default(),
+            client_overrides: BTreeMap::from([("message.timeout.ms".to_string(), "500".to_string())]),
+        }
+    }
+
+    fn unreachable_producer(max_in_flight: u32) -> TradingProducer {
+        let kafka = unreachable_kafka();
+        let topics = TopicsConfig {
+            trade: "trades".to_string(),
+            rsi: "rsi".to_string(),
+            quote: "quotes".to_string(),
+            depth: "depth".to_string(),
+            rejects: None,
+        };
+        TradingProducer::new(&kafka, &topics, "producer-1", max_in_flight, ProducerMetrics::new()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_wait_for_a_slot_in_the_in_flight_window() {
+        let producer = unreachable_producer(1);
+        let rsi = RsiData::new("AAPL".to_string(), 50.0, 14);
+        producer.send_rsi_data(&rsi).await.unwrap();
+
+        // The first record holds the only permit until its delivery fails
+        let second = producer.send_rsi_data(&rsi);
+        tokio::pin!(second);
+        assert!(tokio::time::timeout(Duration::from_millis(200), &mut second).await.is_err());
+        tokio::time::timeout(Duration::from_secs(10), second).await.expect("permit never released").unwrap();
+        assert_eq!(producer.in_flight.available_permits(), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_leave_no_gap_in_the_trade_sequence() {
+        let rejects = RejectRouter::new(&unreachable_kafka(), "trades-rejected", "producer").unwrap();
+        let producer = unreachable_producer(10).with_validation(TradeValidator::default(), Some(rejects));
+        let trade = |price: &str| TradeData::new("AAPL".to_string(), price.parse().unwrap(), 100, TradeSide::Buy, "NASDAQ".to_string());
+
+        assert!(producer.send_trade_data(&trade("0")).await.is_err());
+        producer.send_trade_data(&trade("150")).await.unwrap();
+        producer.send_trade_data(&trade("151")).await.unwrap();
+
+        // The valid trades took 1 and 2; the reject was numbered on its own
+        assert_eq!(producer.envelopes.next(MessageType::Trade).sequence, 3);
+        assert_eq!(producer.reject_envelopes.next(MessageType::Trade).sequence, 2);
+    }
+
+    #[test]
+    fn records_carry_id_symbol_and_envelope() {
+        let envelope = EnvelopeStamper::new("producer-1").next(MessageType::Trade);
+        let headers = record_headers("trade-1", "AAPL", &envelope);
+        assert_eq!(headers[RECORD_ID_HEADER], "trade-1");
+        assert_eq!(headers[SYMBOL_HEADER], "AAPL");
+        assert_eq!(Envelope::from_headers(&headers), Ok(Some(envelope)));
+    }
+}
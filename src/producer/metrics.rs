@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use warp::Filter;
+
+use crate::metrics::{Histogram, PrometheusWriter};
+
+/// Delivery statistics for a single topic.
+#[derive(Debug, Clone)]
+pub struct TopicStats {
+    pub enqueued: u64,
+    pub delivered: u64,
+    pub failed: u64,
+    /// Failures on errors librdkafka would have retried, i.e. retries ran out.
+    pub retries_exhausted: u64,
+    pub in_flight: u64,
+    pub failures_by_reason: BTreeMap<String, u64>,
+    /// Records that failed validation and were never sent.
+    pub rejects_by_reason: BTreeMap<String, u64>,
+    pub latency_ms: Histogram,
+}
+
+impl Default for TopicStats {
+    fn default() -> Self {
+        Self {
+            enqueued: 0,
+            delivered: 0,
+            failed: 0,
+            retries_exhausted: 0,
+            in_flight: 0,
+            failures_by_reason: BTreeMap::new(),
+            rejects_by_reason: BTreeMap::new(),
+            latency_ms: Histogram::latency_ms(),
+        }
+    }
+}
+
+/// Name, help text and accessor of a per-topic counter.
+type Counter = (&'static str, &'static str, fn(&TopicStats) -> u64);
+
+/// Shared delivery metrics, updated from delivery tracking tasks.
+#[derive(Debug, Clone, Default)]
+pub struct ProducerMetrics {
+    topics: Arc<Mutex<BTreeMap<String, TopicStats>>>,
+}
+
+impl ProducerMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update(&self, topic: &str, f: impl FnOnce(&mut TopicStats)) {
+        let mut topics = self.topics.lock().unwrap();
+        f(topics.entry(topic.to_string()).or_default());
+    }
+
+    pub fn record_enqueued(&self, topic: &str) {
+        self.update(topic, |stats| {
+            stats.enqueued += 1;
+            stats.in_flight += 1;
+        });
+    }
+
+    pub fn record_delivered(&self, topic: &str, latency: Duration) {
+        self.update(topic, |stats| {
+            stats.delivered += 1;
+            stats.in_flight = stats.in_flight.saturating_sub(1);
+            stats.latency_ms.observe(latency.as_secs_f64() * 1000.0);
+        });
+    }
+
+    pub fn record_failed(&self, topic: &str, reason: &str, retries_exhausted: bool, in_flight: bool) {
+        self.update(topic, |stats| {
+            stats.failed += 1;
+            if in_flight {
+                stats.in_flight = stats.in_flight.saturating_sub(1);
+            }
+            if retries_exhausted {
+                stats.retries_exhausted += 1;
+            }
+            *stats.failures_by_reason.entry(reason.to_string()).or_default() += 1;
+        });
+    }
+
+    pub fn record_rejected(&self, topic: &str, reason: &str) {
+        self.update(topic, |stats| {
+            *stats.rejects_by_reason.entry(reason.to_string()).or_default() += 1;
+        });
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, TopicStats> {
+        self.topics.lock().unwrap().clone()
+    }
+
+    /// Renders all metrics in Prometheus text format.
+    pub fn render(&self) -> String {
+        let topics = self.snapshot();
+        let mut writer = PrometheusWriter::new();
+
+        let counters: [Counter; 4] = [
+            ("producer_messages_enqueued_total", "Messages handed to the Kafka client", |s| s.enqueued),
+            ("producer_messages_delivered_total", "Messages acknowledged by the broker", |s| s.delivered),
+            ("producer_messages_failed_total", "Messages that failed delivery", |s| s.failed),
+            ("producer_retries_exhausted_total", "Deliveries that failed after exhausting retries", |s| s.retries_exhausted),
+        ];
+        for (name, help, value) in counters {
+            writer.header(name, "counter", help);
+            for (topic, stats) in &topics {
+                writer.sample(name, &[("topic", topic)], value(stats) as f64);
+            }
+        }
+
+        writer.header("producer_delivery_failures_total", "counter", "Failed deliveries by error");
+        for (topic, stats) in &topics {
+            for (reason, count) in &stats.failures_by_reason {
+                writer.sample("producer_delivery_failures_total", &[("topic", topic), ("reason", reason)], *count as f64);
+            }
+        }
+
+        writer.header("producer_messages_rejected_total", "counter", "Records that failed validation, by reason");
+        for (topic, stats) in &topics {
+            for (reason, count) in &stats.rejects_by_reason {
+                writer.sample("producer_messages_rejected_total", &[("topic", topic), ("reason", reason)], *count as f64);
+            }
+        }
+
+        writer.header("producer_messages_in_flight", "gauge", "Messages awaiting a delivery report");
+        for (topic, stats) in &topics {
+            writer.sample("producer_messages_in_flight", &[("topic", topic)], stats.in_flight as f64);
+        }
+
+        writer.header("producer_delivery_latency_ms", "histogram", "Time from enqueue to delivery report");
+        for (topic, stats) in &topics {
+            writer.histogram("producer_delivery_latency_ms", &[("topic", topic)], &stats.latency_ms);
+        }
+
+        writer.finish()
+    }
+}
+
+pub fn metrics_routes(metrics: ProducerMetrics) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path("metrics")
+        .and(warp::get())
+        .map(move || {
+            warp::reply::with_header(metrics.render(), "content-type", "text/plain; version=0.0.4")
+        })
+}
@@ -1,5 +1,11 @@
 pub mod kafka_producer;
 pub mod data_generator;
+pub mod scenario;
+pub mod metrics;
+pub mod order_book;
 
 pub use kafka_producer::*;
 pub use data_generator::*;
+pub use scenario::*;
+pub use metrics::*;
+pub use order_book::*;
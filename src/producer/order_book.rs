@@ -0,0 +1,502 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::{price_to_f64, DepthUpdate, PriceLevel, PriceSpec, PriceSpecs, Quote, TradeData, TradeSide};
+
+/// Number of price levels kept on each side when replenishing a book.
+const TARGET_DEPTH: usize = 10;
+
+/// A fill produced by matching an incoming order against resting liquidity.
+#[derive(Debug, Clone, Copy)]
+pub struct Fill {
+    pub price: i64,
+    pub size: u64,
+    /// Side of the incoming (aggressive) order.
+    pub aggressor: TradeSide,
+}
+
+/// Price-level limit order book for a single symbol. Prices are integer
+/// ticks of the symbol's [`PriceSpec`].
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    symbol: String,
+    spec: PriceSpec,
+    bids: BTreeMap<i64, u64>,
+    asks: BTreeMap<i64, u64>,
+    sequence: u64,
+    /// Levels touched since the last depth update, with their new size.
+    changed_bids: BTreeMap<i64, u64>,
+    changed_asks: BTreeMap<i64, u64>,
+}
+
+impl OrderBook {
+    pub fn new(symbol: String, spec: PriceSpec) -> Self {
+        Self {
+            symbol,
+            spec,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            sequence: 0,
+            changed_bids: BTreeMap::new(),
+            changed_asks: BTreeMap::new(),
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn spec(&self) -> PriceSpec {
+        self.spec
+    }
+
+    pub fn best_bid(&self) -> Option<(i64, u64)> {
+        self.bids.iter().next_back().map(|(price, size)| (*price, *size))
+    }
+
+    pub fn best_ask(&self) -> Option<(i64, u64)> {
+        self.asks.iter().next().map(|(price, size)| (*price, *size))
+    }
+
+    pub fn depth(&self, side: TradeSide) -> usize {
+        match side {
+            TradeSide::Buy => self.bids.len(),
+            TradeSide::Sell => self.asks.len(),
+        }
+    }
+
+    fn set_level(&mut self, side: TradeSide, price: i64, size: u64) {
+        let (levels, changes) = match side {
+            TradeSide::Buy => (&mut self.bids, &mut self.changed_bids),
+            TradeSide::Sell => (&mut self.asks, &mut self.changed_asks),
+        };
+        if size == 0 {
+            levels.remove(&price);
+        } else {
+            levels.insert(price, size);
+        }
+        changes.insert(price, size);
+    }
+
+    /// Adds a limit order. Any part that crosses the spread is matched first;
+    /// the remainder rests on the book.
+    pub fn limit(&mut self, side: TradeSide, price: i64, size: u64) -> Vec<Fill> {
+        let fills = self.match_against(side, size, Some(price));
+        let filled: u64 = fills.iter().map(|fill| fill.size).sum();
+        let remaining = size - filled;
+        if remaining > 0 {
+            let resting = match side {
+                TradeSide::Buy => self.bids.get(&price),
+                TradeSide::Sell => self.asks.get(&price),
+            }
+            .copied()
+            .unwrap_or(0);
+            self.set_level(side, price, resting + remaining);
+        }
+        fills
+    }
+
+    /// Executes a market order against the opposite side.
+    pub fn market(&mut self, side: TradeSide, size: u64) -> Vec<Fill> {
+        self.match_against(side, size, None)
+    }
+
+    /// Removes up to `size` from the level at `price`.
+    pub fn cancel(&mut self, side: TradeSide, price: i64, size: u64) {
+        let resting = match side {
+            TradeSide::Buy => self.bids.get(&price),
+            TradeSide::Sell => self.asks.get(&price),
+        }
+        .copied();
+        if let Some(resting) = resting {
+            self.set_level(side, price, resting.saturating_sub(size));
+        }
+    }
+
+    fn match_against(&mut self, side: TradeSide, mut size: u64, limit: Option<i64>) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        while size > 0 {
+            let best = match side {
+                TradeSide::Buy => self.best_ask(),
+                TradeSide::Sell => self.best_bid(),
+            };
+            let Some((price, resting)) = best else {
+                break;
+            };
+            let crosses = match (side, limit) {
+                (_, None) => true,
+                (TradeSide::Buy, Some(limit)) => price <= limit,
+                (TradeSide::Sell, Some(limit)) => price >= limit,
+            };
+            if !crosses {
+                break;
+            }
+
+            let traded = size.min(resting);
+            let passive = match side {
+                TradeSide::Buy => TradeSide::Sell,
+                TradeSide::Sell => TradeSide::Buy,
+            };
+            self.set_level(passive, price, resting - traded);
+            fills.push(Fill { price, size: traded, aggressor: side });
+            size -= traded;
+        }
+        fills
+    }
+
+    pub fn quote(&self) -> Option<Quote> {
+        let (bid, bid_size) = self.best_bid()?;
+        let (ask, ask_size) = self.best_ask()?;
+        Some(Quote::new(self.symbol.clone(), self.spec.from_ticks(bid), bid_size, self.spec.from_ticks(ask), ask_size))
+    }
+
+    /// Drains the levels changed since the last call into a depth update.
+    pub fn take_depth_update(&mut self) -> Option<DepthUpdate> {
+        if self.changed_bids.is_empty() && self.changed_asks.is_empty() {
+            return None;
+        }
+        let spec = self.spec;
+        let levels = |changes: &mut BTreeMap<i64, u64>| -> Vec<PriceLevel> {
+            std::mem::take(changes)
+                .into_iter()
+                .map(|(price, size)| PriceLevel { price: spec.from_ticks(price), size })
+                .collect()
+        };
+        let mut bids = levels(&mut self.changed_bids);
+        bids.reverse(); // best bid first
+        let asks = levels(&mut self.changed_asks);
+        self.sequence += 1;
+        Some(DepthUpdate::new(self.symbol.clone(), self.sequence, bids, asks))
+    }
+}
+
+/// Nearest tick to a simulated `f64` price.
+fn to_ticks(spec: PriceSpec, price: f64) -> i64 {
+    (price / price_to_f64(spec.tick_size)).round() as i64
+}
+
+/// Everything one simulation step published for a symbol.
+#[derive(Debug, Default)]
+pub struct MarketUpdate {
+    pub trades: Vec<TradeData>,
+    pub quote: Option<Quote>,
+    pub depth: Option<DepthUpdate>,
+}
+
+/// Drives an [`OrderBook`] per symbol with random order flow. Trades only
+/// come out of matching, so trade prices always sit inside the quoted book.
+pub struct OrderBookSimulator {
+    books: HashMap<String, OrderBook>,
+    fair_values: HashMap<String, f64>,
+    symbols: Vec<String>,
+    rng: StdRng,
+}
+
+impl Default for OrderBookSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderBookSimulator {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(mut rng: StdRng) -> Self {
+        let symbols: Vec<String> = ["AAPL", "GOOGL", "MSFT", "TSLA", "AMZN", "NVDA", "META", "NFLX"]
+            .iter()
+            .map(|symbol| symbol.to_string())
+            .collect();
+
+        let mut books = HashMap::new();
+        let mut fair_values = HashMap::new();
+        for symbol in &symbols {
+            let fair_value = rng.gen_range(50.0..500.0);
+            books.insert(symbol.clone(), seeded_book(symbol, PriceSpec::default(), fair_value, &mut rng));
+            fair_values.insert(symbol.clone(), fair_value);
+        }
+
+        Self {
+            books,
+            fair_values,
+            symbols,
+            rng,
+        }
+    }
+
+    /// Reseeds every book on its symbol's tick grid.
+    pub fn with_price_specs(mut self, specs: &PriceSpecs) -> Self {
+        for symbol in &self.symbols {
+            let fair_value = self.fair_values[symbol];
+            let book = seeded_book(symbol, specs.get(symbol), fair_value, &mut self.rng);
+            self.books.insert(symbol.clone(), book);
+        }
+        self
+    }
+
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    pub fn book(&self, symbol: &str) -> Option<&OrderBook> {
+        self.books.get(symbol)
+    }
+
+    /// Sends one random order into a random symbol's book.
+    pub fn step(&mut self) -> MarketUpdate {
+        let symbol = self.symbols[self.rng.gen_range(0..self.symbols.len())].clone();
+        self.step_symbol(&symbol)
+    }
+
+    pub fn step_symbol(&mut self, symbol: &str) -> MarketUpdate {
+        let rng = &mut self.rng;
+        let (Some(book), Some(fair_value)) = (self.books.get_mut(symbol), self.fair_values.get_mut(symbol)) else {
+            return MarketUpdate::default();
+        };
+
+        // Fair value random walk, ±0.2% per step
+        *fair_value *= 1.0 + rng.gen_range(-0.002..0.002);
+        let fair = to_ticks(book.spec, *fair_value);
+
+        let before = (book.best_bid(), book.best_ask());
+        let side = if rng.gen_bool(0.5) { TradeSide::Buy } else { TradeSide::Sell };
+        let fills = match rng.gen_range(0..10) {
+            // Limit order around fair value; may cross and trade
+            0..=4 => {
+                let offset = rng.gen_range(-3..10);
+                let price = match side {
+                    TradeSide::Buy => fair - offset,
+                    TradeSide::Sell => fair + offset,
+                };
+                book.limit(side, price.max(1), rng.gen_range(1..10) * 100)
+            }
+            // Cancel part of a resting level
+            5..=6 => {
+                let levels: Vec<i64> = match side {
+                    TradeSide::Buy => book.bids.keys().copied().collect(),
+                    TradeSide::Sell => book.asks.keys().copied().collect(),
+                };
+                if !levels.is_empty() {
+                    let price = levels[rng.gen_range(0..levels.len())];
+                    book.cancel(side, price, rng.gen_range(1..5) * 100);
+                }
+                Vec::new()
+            }
+            // Market order
+            _ => book.market(side, rng.gen_range(1..15) * 100),
+        };
+
+        if book.depth(TradeSide::Buy) < TARGET_DEPTH / 2 || book.depth(TradeSide::Sell) < TARGET_DEPTH / 2 {
+            replenish(book, *fair_value, rng);
+        }
+
+        let trades = fills
+            .into_iter()
+            .map(|fill| {
+                let exchange = match rng.gen_range(0..3) {
+                    0 => "NYSE",
+                    1 => "NASDAQ",
+                    _ => "BATS",
+                };
+                TradeData::new(symbol.to_string(), book.spec.from_ticks(fill.price), fill.size, fill.aggressor, exchange.to_string())
+            })
+            .collect();
+
+        let after = (book.best_bid(), book.best_ask());
+        MarketUpdate {
+            trades,
+            quote: if before != after { book.quote() } else { None },
+            depth: book.take_depth_update(),
+        }
+    }
+}
+
+fn seeded_book(symbol: &str, spec: PriceSpec, fair_value: f64, rng: &mut StdRng) -> OrderBook {
+    let mut book = OrderBook::new(symbol.to_string(), spec);
+    replenish(&mut book, fair_value, rng);
+    // The seeded book is the starting state, not an update
+    book.changed_bids.clear();
+    book.changed_asks.clear();
+    book
+}
+
+/// Tops up both sides of `book` to [`TARGET_DEPTH`] levels around `fair_value`.
+fn replenish(book: &mut OrderBook, fair_value: f64, rng: &mut StdRng) {
+    let fair = to_ticks(book.spec, fair_value);
+    let best_bid = book.best_bid().map(|(price, _)| price).unwrap_or(fair - 1);
+    let best_ask = book.best_ask().map(|(price, _)| price).unwrap_or(fair + 1);
+
+    let mut price = best_bid.min(best_ask - 1);
+    while book.depth(TradeSide::Buy) < TARGET_DEPTH && price > 0 {
+        if !book.bids.contains_key(&price) {
+            book.set_level(TradeSide::Buy, price, rng.gen_range(1..10) * 100);
+        }
+        price -= rng.gen_range(1..3);
+    }
+
+    let mut price = best_ask.max(best_bid + 1);
+    while book.depth(TradeSide::Sell) < TARGET_DEPTH {
+        if !book.asks.contains_key(&price) {
+            book.set_level(TradeSide::Sell, price, rng.gen_range(1..10) * 100);
+        }
+        price += rng.gen_range(1..3);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bids at 98 and 99, asks at 101, 102 and 103, 100 lots each.
+    fn book() -> OrderBook {
+        let mut book = OrderBook::new("AAPL".to_string(), PriceSpec::default());
+        for price in [98, 99] {
+            book.limit(TradeSide::Buy, price, 100);
+        }
+        for price in [101, 102, 103] {
+            book.limit(TradeSide::Sell, price, 100);
+        }
+        book.take_depth_update();
+        book
+    }
+
+    fn levels(update: &[PriceLevel]) -> Vec<(String, u64)> {
+        update.iter().map(|level| (level.price.to_string(), level.size)).collect()
+    }
+
+    /// Bid and ask levels of a book.
+    type Levels = (BTreeMap<i64, u64>, BTreeMap<i64, u64>);
+    /// Best bid and ask of a book.
+    type Top = (Option<(i64, u64)>, Option<(i64, u64)>);
+
+    fn assert_uncrossed(book: &OrderBook) {
+        if let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) {
+            assert!(bid < ask, "crossed book: bid {} >= ask {}", bid, ask);
+        }
+    }
+
+    #[test]
+    fn crossing_order_sweeps_levels_and_rests_the_remainder() {
+        let mut book = book();
+        let fills = book.limit(TradeSide::Buy, 102, 250);
+        let fills: Vec<(i64, u64)> = fills.iter().map(|fill| (fill.price, fill.size)).collect();
+        assert_eq!(fills, [(101, 100), (102, 100)]);
+
+        // 50 lots were left over at the limit, now the best bid
+        assert_eq!(book.best_bid(), Some((102, 50)));
+        assert_eq!(book.best_ask(), Some((103, 100)));
+        assert_uncrossed(&book);
+        let quote = book.quote().unwrap();
+        assert_eq!((quote.bid_price.to_string(), quote.bid_size), ("1.02".to_string(), 50));
+        assert_eq!((quote.ask_price.to_string(), quote.ask_size), ("1.03".to_string(), 100));
+
+        let depth = book.take_depth_update().unwrap();
+        assert_eq!(levels(&depth.bids), [("1.02".to_string(), 50)]);
+        assert_eq!(levels(&depth.asks), [("1.01".to_string(), 0), ("1.02".to_string(), 0)]);
+        assert!(book.take_depth_update().is_none());
+    }
+
+    #[test]
+    fn partial_fill_leaves_the_rest_of_the_level() {
+        let mut book = book();
+        let fills = book.market(TradeSide::Sell, 30);
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].price, fills[0].size, fills[0].aggressor), (99, 30, TradeSide::Sell));
+        assert_eq!(book.best_bid(), Some((99, 70)));
+        assert_eq!(book.depth(TradeSide::Buy), 2);
+        assert_uncrossed(&book);
+
+        let depth = book.take_depth_update().unwrap();
+        assert_eq!(levels(&depth.bids), [("0.99".to_string(), 70)]);
+        assert!(depth.asks.is_empty());
+        assert_eq!(depth.sequence, 2);
+    }
+
+    #[test]
+    fn orders_that_do_not_cross_only_rest() {
+        let mut book = book();
+        assert!(book.limit(TradeSide::Sell, 100, 100).is_empty());
+        assert_eq!(book.best_ask(), Some((100, 100)));
+        assert!(book.limit(TradeSide::Sell, 100, 50).is_empty());
+        assert_eq!(book.best_ask(), Some((100, 150)));
+        book.cancel(TradeSide::Sell, 100, 500);
+        assert_eq!(book.best_ask(), Some((101, 100)));
+        // A market order larger than the book takes what there is
+        let filled: u64 = book.market(TradeSide::Buy, 1000).iter().map(|fill| fill.size).sum();
+        assert_eq!(filled, 300);
+        assert_eq!(book.best_ask(), None);
+        assert!(book.quote().is_none());
+    }
+
+    #[test]
+    fn simulated_books_stay_consistent_with_their_updates() {
+        let mut simulator = OrderBookSimulator::with_seed(7);
+        let mut mirrors: HashMap<String, Levels> = simulator
+            .symbols()
+            .iter()
+            .map(|symbol| {
+                let book = simulator.book(symbol).unwrap();
+                (symbol.clone(), (book.bids.clone(), book.asks.clone()))
+            })
+            .collect();
+        let mut sequences: HashMap<String, u64> = HashMap::new();
+
+        for _ in 0..2000 {
+            let before: HashMap<String, Top> = simulator
+                .symbols()
+                .iter()
+                .map(|symbol| {
+                    let book = simulator.book(symbol).unwrap();
+                    (symbol.clone(), (book.best_bid(), book.best_ask()))
+                })
+                .collect();
+            let update = simulator.step();
+            for symbol in simulator.symbols() {
+                assert_uncrossed(simulator.book(symbol).unwrap());
+            }
+
+            if let Some(depth) = update.depth {
+                let book = simulator.book(&depth.symbol).unwrap();
+                let spec = book.spec();
+                let sequence = sequences.entry(depth.symbol.clone()).or_default();
+                *sequence += 1;
+                assert_eq!(depth.sequence, *sequence);
+
+                let (bids, asks) = mirrors.get_mut(&depth.symbol).unwrap();
+                for (mirror, changes) in [(bids, &depth.bids), (asks, &depth.asks)] {
+                    for level in changes {
+                        match level.size {
+                            0 => mirror.remove(&spec.ticks(level.price)),
+                            size => mirror.insert(spec.ticks(level.price), size),
+                        };
+                    }
+                }
+                assert_eq!(mirrors[&depth.symbol], (book.bids.clone(), book.asks.clone()));
+            }
+
+            if let Some(quote) = update.quote {
+                let book = simulator.book(&quote.symbol).unwrap();
+                let (bid, bid_size) = book.best_bid().unwrap();
+                let (ask, ask_size) = book.best_ask().unwrap();
+                assert_eq!((book.spec().ticks(quote.bid_price), quote.bid_size), (bid, bid_size));
+                assert_eq!((book.spec().ticks(quote.ask_price), quote.ask_size), (ask, ask_size));
+            }
+
+            // Aggressive orders trade through the far side of the book they found
+            for trade in &update.trades {
+                let price = simulator.book(&trade.symbol).unwrap().spec().ticks(trade.price);
+                let (bid, ask) = before[&trade.symbol];
+                match trade.side {
+                    TradeSide::Buy => assert!(ask.is_some_and(|(ask, _)| price >= ask)),
+                    TradeSide::Sell => assert!(bid.is_some_and(|(bid, _)| price <= bid)),
+                }
+            }
+        }
+    }
+}
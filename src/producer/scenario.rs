@@ -0,0 +1,451 @@
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::models::{PriceSpecs, RsiSignal, TradeData};
+use crate::producer::DataGenerator;
+
+/// A scripted sequence of market conditions that drives [`DataGenerator`]
+/// deterministically, e.g. to check that RSI signals fire when expected.
+///
+/// Scenarios are JSON files:
+///
+/// ```json
+/// {
+///   "name": "rally-crash-halt",
+///   "seed": 42,
+///   "tick": "500ms",
+///   "initial_prices": { "AAPL": 180.0, "TSLA": 250.0 },
+///   "steps": [
+///     { "action": "move", "symbol": "AAPL", "percent": 8.0, "over": "2m", "expect": "Overbought" },
+///     { "action": "flash_crash", "symbol": "TSLA", "percent": 15.0, "over": "20s", "recover_over": "1m", "expect": "Oversold" },
+///     { "action": "halt", "symbol": "NFLX", "for": "30s" }
+///   ]
+/// }
+/// ```
+///
+/// Steps run one after another. Every tick emits one trade per symbol driven by
+/// the current step plus one background trade for a random non-halted symbol.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub seed: u64,
+    #[serde(default = "default_tick", deserialize_with = "deserialize_duration")]
+    pub tick: Duration,
+    /// Relative noise applied to driven prices; 0 follows the path exactly.
+    #[serde(default)]
+    pub noise_pct: f64,
+    /// Maximum relative move of background trades.
+    #[serde(default = "default_background_volatility")]
+    pub background_volatility_pct: f64,
+    #[serde(default)]
+    pub initial_prices: HashMap<String, f64>,
+    pub steps: Vec<ScenarioStep>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ScenarioStep {
+    /// Moves `symbol` by `percent` (negative for a sell-off) along a geometric path.
+    Move {
+        symbol: String,
+        percent: f64,
+        #[serde(deserialize_with = "deserialize_duration")]
+        over: Duration,
+        #[serde(default)]
+        expect: Option<RsiSignal>,
+    },
+    /// Drops `symbol` by `percent`, then recovers to the pre-crash price.
+    FlashCrash {
+        symbol: String,
+        percent: f64,
+        #[serde(deserialize_with = "deserialize_duration")]
+        over: Duration,
+        #[serde(deserialize_with = "deserialize_duration")]
+        recover_over: Duration,
+        #[serde(default)]
+        expect: Option<RsiSignal>,
+    },
+    /// Stops all trading in `symbol`.
+    Halt {
+        symbol: String,
+        #[serde(rename = "for", deserialize_with = "deserialize_duration")]
+        duration: Duration,
+    },
+    /// Emits background trades only.
+    Idle {
+        #[serde(rename = "for", deserialize_with = "deserialize_duration")]
+        duration: Duration,
+    },
+}
+
+fn default_tick() -> Duration {
+    Duration::from_millis(500)
+}
+
+fn default_background_volatility() -> f64 {
+    1.0
+}
+
+/// Parses durations such as `"500ms"`, `"30s"`, `"2m"` or `"1h"`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: f64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+    let seconds = match unit.trim() {
+        "ms" => amount / 1000.0,
+        "s" | "" => amount,
+        "m" => amount * 60.0,
+        "h" => amount * 3600.0,
+        other => return Err(format!("unknown duration unit '{}' in '{}'", other, value)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration '{}': {}", value, e))
+}
+
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Seconds(f64),
+        Text(String),
+    }
+
+    match Raw::deserialize(deserializer)? {
+        Raw::Seconds(seconds) => Duration::try_from_secs_f64(seconds)
+            .map_err(|e| serde::de::Error::custom(format!("invalid duration {}: {}", seconds, e))),
+        Raw::Text(text) => parse_duration(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+impl Scenario {
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read scenario '{}': {}", path, e))?;
+        let scenario: Scenario = serde_json::from_str(&contents)
+            .map_err(|e| format!("invalid scenario '{}': {}", path, e))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tick.is_zero() {
+            return Err("scenario tick must be greater than zero".to_string());
+        }
+        if self.steps.is_empty() {
+            return Err("scenario has no steps".to_string());
+        }
+        if let Some((symbol, price)) = self.initial_prices.iter().find(|(_, price)| **price <= 0.0) {
+            return Err(format!("initial price for {} must be positive, got {}", symbol, price));
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            match step {
+                ScenarioStep::Move { percent, .. } if *percent <= -100.0 => {
+                    return Err(format!("step {}: cannot move by {}%", index + 1, percent));
+                }
+                ScenarioStep::FlashCrash { percent, .. } if *percent <= 0.0 || *percent >= 100.0 => {
+                    return Err(format!("step {}: crash percent must be between 0 and 100, got {}", index + 1, percent));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Total scripted duration of all steps.
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(ScenarioStep::duration).fold(Duration::ZERO, Duration::saturating_add)
+    }
+}
+
+impl ScenarioStep {
+    pub fn duration(&self) -> Duration {
+        match self {
+            ScenarioStep::Move { over, .. } => *over,
+            ScenarioStep::FlashCrash { over, recover_over, .. } => over.saturating_add(*recover_over),
+            ScenarioStep::Halt { duration, .. } | ScenarioStep::Idle { duration } => *duration,
+        }
+    }
+
+    pub fn expected_signal(&self) -> Option<&RsiSignal> {
+        match self {
+            ScenarioStep::Move { expect, .. } | ScenarioStep::FlashCrash { expect, .. } => expect.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ScenarioStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioStep::Move { symbol, percent, over, .. } => {
+                write!(f, "{} moves {:+.2}% over {:?}", symbol, percent, over)
+            }
+            ScenarioStep::FlashCrash { symbol, percent, over, recover_over, .. } => {
+                write!(f, "{} flash-crashes {:.2}% over {:?} and recovers over {:?}", symbol, percent, over, recover_over)
+            }
+            ScenarioStep::Halt { symbol, duration } => write!(f, "{} halted for {:?}", symbol, duration),
+            ScenarioStep::Idle { duration } => write!(f, "idle for {:?}", duration),
+        }?;
+        if let Some(signal) = self.expected_signal() {
+            write!(f, " (expect {:?})", signal)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays a [`Scenario`] tick by tick.
+pub struct ScenarioRunner {
+    scenario: Scenario,
+    generator: DataGenerator,
+    step_index: usize,
+    tick_in_step: u64,
+    step_start_price: Option<f64>,
+}
+
+impl ScenarioRunner {
+    pub fn new(scenario: Scenario) -> Self {
+        let mut generator = DataGenerator::with_seed(scenario.seed);
+        let mut initial: Vec<_> = scenario.initial_prices.iter().collect();
+        initial.sort_by(|a, b| a.0.cmp(b.0));
+        for (symbol, price) in initial {
+            generator.set_price(symbol, *price);
+        }
+
+        Self {
+            scenario,
+            generator,
+            step_index: 0,
+            tick_in_step: 0,
+            step_start_price: None,
+        }
+    }
+
+    pub fn with_price_specs(mut self, specs: &PriceSpecs) -> Self {
+        self.generator.set_price_specs(specs);
+        self
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        self.scenario.tick
+    }
+
+    /// The step the next tick belongs to, with its 1-based position.
+    pub fn current_step(&self) -> Option<(usize, &ScenarioStep)> {
+        self.scenario.steps.get(self.step_index).map(|step| (self.step_index + 1, step))
+    }
+
+    /// Returns true if the next call to [`ScenarioRunner::next_tick`] starts a new step.
+    pub fn at_step_start(&self) -> bool {
+        self.tick_in_step == 0
+    }
+
+    fn ticks_for(&self, duration: Duration) -> u64 {
+        let tick = self.scenario.tick.as_secs_f64();
+        ((duration.as_secs_f64() / tick).ceil() as u64).max(1)
+    }
+
+    /// Advances the scenario by one tick and returns the trades it produced,
+    /// or `None` once every step has completed.
+    pub fn next_tick(&mut self) -> Option<Vec<TradeData>> {
+        let step = self.scenario.steps.get(self.step_index)?.clone();
+        let mut trades = Vec::new();
+
+        if self.tick_in_step == 0 {
+            self.step_start_price = match &step {
+                ScenarioStep::Move { symbol, .. } | ScenarioStep::FlashCrash { symbol, .. } => {
+                    self.generator.resume(symbol);
+                    self.generator.price(symbol)
+                }
+                ScenarioStep::Halt { symbol, .. } => {
+                    self.generator.halt(symbol);
+                    None
+                }
+                ScenarioStep::Idle { .. } => None,
+            };
+        }
+
+        // Background trades come first so the driven trade below always has
+        // the final say on the driven symbol's price.
+        let volatility = self.scenario.background_volatility_pct / 100.0;
+        let driven = step_symbol(&step);
+        if let Some(trade) = self.generator.generate_random_trade(volatility) {
+            if Some(trade.symbol.as_str()) != driven {
+                trades.push(trade);
+            }
+        }
+
+        let tick = self.tick_in_step + 1;
+        let step_ticks = match &step {
+            ScenarioStep::Move { symbol, percent, over, .. } => {
+                let ticks = self.ticks_for(*over);
+                let start = self.step_start_price_or_default(symbol);
+                let target = start * (1.0 + percent / 100.0);
+                let price = interpolate(start, target, tick as f64 / ticks as f64);
+                trades.push(self.driven_trade(symbol, price));
+                ticks
+            }
+            ScenarioStep::FlashCrash { symbol, percent, over, recover_over, .. } => {
+                let down_ticks = self.ticks_for(*over);
+                let up_ticks = self.ticks_for(*recover_over);
+                let start = self.step_start_price_or_default(symbol);
+                let bottom = start * (1.0 - percent / 100.0);
+                let price = if tick <= down_ticks {
+                    interpolate(start, bottom, tick as f64 / down_ticks as f64)
+                } else {
+                    interpolate(bottom, start, (tick - down_ticks) as f64 / up_ticks as f64)
+                };
+                trades.push(self.driven_trade(symbol, price));
+                down_ticks + up_ticks
+            }
+            ScenarioStep::Halt { duration, .. } | ScenarioStep::Idle { duration } => self.ticks_for(*duration),
+        };
+
+        self.tick_in_step = tick;
+        if tick >= step_ticks {
+            if let ScenarioStep::Halt { symbol, .. } = &step {
+                self.generator.resume(symbol);
+            }
+            self.step_index += 1;
+            self.tick_in_step = 0;
+            self.step_start_price = None;
+        }
+
+        Some(trades)
+    }
+
+    fn step_start_price_or_default(&mut self, symbol: &str) -> f64 {
+        if let Some(price) = self.step_start_price {
+            return price;
+        }
+        // Symbols not seeded via `initial_prices` start at a fixed level
+        let price = 100.0;
+        self.generator.set_price(symbol, price);
+        self.step_start_price = Some(price);
+        price
+    }
+
+    fn driven_trade(&mut self, symbol: &str, price: f64) -> TradeData {
+        let noise = self.generator.noise(self.scenario.noise_pct / 100.0);
+        self.generator.generate_trade_for(symbol, price * (1.0 + noise))
+    }
+}
+
+fn step_symbol(step: &ScenarioStep) -> Option<&str> {
+    match step {
+        ScenarioStep::Move { symbol, .. } | ScenarioStep::FlashCrash { symbol, .. } => Some(symbol),
+        _ => None,
+    }
+}
+
+/// Geometric interpolation, so equal ticks produce equal percentage changes.
+fn interpolate(from: f64, to: f64, fraction: f64) -> f64 {
+    from * (to / from).powf(fraction.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{price_to_f64, TradeSide};
+
+    const SCENARIO: &str = r#"{
+        "seed": 7,
+        "tick": "1s",
+        "noise_pct": 0.5,
+        "initial_prices": { "AAPL": 180.0, "TSLA": 250.0 },
+        "steps": [
+            { "action": "move", "symbol": "AAPL", "percent": 10.0, "over": "10s" },
+            { "action": "halt", "symbol": "TSLA", "for": 3 },
+            { "action": "flash_crash", "symbol": "TSLA", "percent": 20.0, "over": "4s", "recover_over": "4s" }
+        ]
+    }"#;
+
+    fn play(scenario: Scenario) -> Vec<(String, String, u64, TradeSide, String)> {
+        let mut runner = ScenarioRunner::new(scenario);
+        let mut trades = Vec::new();
+        while let Some(tick) = runner.next_tick() {
+            trades.extend(tick.into_iter().map(|trade| {
+                (trade.symbol, trade.price.to_string(), trade.volume, trade.side, trade.exchange)
+            }));
+        }
+        trades
+    }
+
+    fn scenario() -> Scenario {
+        let scenario: Scenario = serde_json::from_str(SCENARIO).unwrap();
+        scenario.validate().unwrap();
+        scenario
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_trades() {
+        let first = play(scenario());
+        assert!(!first.is_empty());
+        assert_eq!(first, play(scenario()));
+
+        let mut reseeded = scenario();
+        reseeded.seed = 8;
+        assert_ne!(first, play(reseeded));
+    }
+
+    #[test]
+    fn steps_follow_their_script() {
+        let mut runner = ScenarioRunner::new(scenario());
+        let mut driven = Vec::new();
+        for _ in 0..10 {
+            let trades = runner.next_tick().unwrap();
+            driven.push(trades.iter().rfind(|trade| trade.symbol == "AAPL").unwrap().price);
+        }
+        let last = price_to_f64(*driven.last().unwrap());
+        assert!((last / 180.0 - 1.1).abs() < 0.01, "AAPL ended at {}", last);
+
+        for _ in 0..3 {
+            let trades = runner.next_tick().unwrap();
+            assert!(trades.iter().all(|trade| trade.symbol != "TSLA"));
+        }
+        assert_eq!(runner.current_step().map(|(position, _)| position), Some(3));
+        assert_eq!((0..8).filter_map(|_| runner.next_tick()).count(), 8);
+        assert!(runner.next_tick().is_none());
+    }
+
+    #[test]
+    fn durations_parse_and_reject_out_of_range_values() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert!(parse_duration("1e400s").is_err());
+        assert!(parse_duration("99999999999999999999999h").is_err());
+        assert!(parse_duration("5d").is_err());
+
+        let huge = r#"{ "steps": [{ "action": "idle", "for": 1e300 }] }"#;
+        assert!(serde_json::from_str::<Scenario>(huge).is_err());
+        let negative = r#"{ "steps": [{ "action": "idle", "for": -1 }] }"#;
+        assert!(serde_json::from_str::<Scenario>(negative).is_err());
+    }
+
+    #[test]
+    fn total_duration_saturates() {
+        let scenario: Scenario = serde_json::from_str(
+            r#"{ "steps": [
+                { "action": "idle", "for": 1.8e19 },
+                { "action": "flash_crash", "symbol": "AAPL", "percent": 5, "over": 1.8e19, "recover_over": 1.8e19 }
+            ] }"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.duration(), Duration::MAX);
+    }
+}
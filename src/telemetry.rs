@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::{LogFormat, LoggingConfig};
+
+/// Kafka header carrying the id of the record (trade, quote, ...).
+pub const RECORD_ID_HEADER: &str = "record-id";
+pub const SYMBOL_HEADER: &str = "symbol";
+
+/// Flushes exported spans when dropped; keep it alive for the whole of `main`.
+pub struct TelemetryGuard {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            let _ = provider.shutdown();
+        }
+    }
+}
+
+/// Installs the global subscriber: leveled logs filtered by `config.filter`,
+/// as text or JSON, plus OTLP span export when an endpoint is configured.
+pub fn init(config: &LoggingConfig, service: &'static str) -> Result<TelemetryGuard, Box<dyn std::error::Error>> {
+    let filter = EnvFilter::try_new(&config.filter)
+        .map_err(|e| format!("invalid log filter '{}': {}", config.filter, e))?;
+    let output = match config.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+
+    #[cfg(feature = "otlp")]
+    {
+        let (otel, provider) = match &config.otlp_endpoint {
+            Some(endpoint) => {
+                let (layer, provider) = otlp::layer(endpoint, service)?;
+                (Some(layer), Some(provider))
+            }
+            None => (None, None),
+        };
+        tracing_subscriber::registry().with(filter).with(output).with(otel).try_init()?;
+        Ok(TelemetryGuard { provider })
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        let _ = service;
+        tracing_subscriber::registry().with(filter).with(output).try_init()?;
+        Ok(TelemetryGuard {})
+    }
+}
+
+/// W3C trace context (`traceparent`, `tracestate`) of the current span, to be
+/// sent along with a record. Empty unless spans are exported over OTLP.
+pub fn current_trace_context() -> HashMap<String, String> {
+    #[allow(unused_mut)]
+    let mut carrier = HashMap::new();
+    #[cfg(feature = "otlp")]
+    otlp::inject(&Span::current(), &mut carrier);
+    carrier
+}
+
+/// Makes `span` a child of the remote span described by W3C headers in
+/// `carrier`, so producer, consumer and API spans join one trace.
+pub fn set_remote_parent(span: &Span, carrier: &HashMap<String, String>) {
+    #[cfg(feature = "otlp")]
+    otlp::extract(span, carrier);
+    #[cfg(not(feature = "otlp"))]
+    let _ = (span, carrier);
+}
+
+/// Span for an API request, continuing the caller's trace if it sent one.
+pub fn request_span(info: warp::trace::Info) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %info.method(),
+        path = %info.path(),
+        remote = ?info.remote_addr(),
+        api_key = tracing::field::Empty,
+    );
+    let carrier: HashMap<String, String> = ["traceparent", "tracestate"]
+        .iter()
+        .filter_map(|name| {
+            let value = info.request_headers().get(*name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect();
+    set_remote_parent(&span, &carrier);
+    span
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::{global, KeyValue};
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use opentelemetry_sdk::{runtime, Resource};
+    use std::collections::HashMap;
+    use tracing::Span;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    pub(super) fn layer<S>(
+        endpoint: &str,
+        service: &'static str,
+    ) -> Result<(tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::Tracer>, TracerProvider), Box<dyn std::error::Error>>
+    where
+        S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .build()?;
+        let provider = TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_resource(Resource::new(vec![KeyValue::new("service.name", service)]))
+            .build();
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer = provider.tracer(service);
+        Ok((tracing_opentelemetry::layer().with_tracer(tracer), provider))
+    }
+
+    pub(super) fn inject(span: &Span, carrier: &mut HashMap<String, String>) {
+        let context = span.context();
+        global::get_text_map_propagator(|propagator| propagator.inject_context(&context, carrier));
+    }
+
+    pub(super) fn extract(span: &Span, carrier: &HashMap<String, String>) {
+        if carrier.is_empty() {
+            return;
+        }
+        let context = global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+        span.set_parent(context);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_filters_are_reported_before_anything_is_installed() {
+        let config = LoggingConfig {
+            filter: "trading_system=loud".to_string(),
+            format: LogFormat::Json,
+            otlp_endpoint: None,
+        };
+        let error = init(&config, "test").err().unwrap().to_string();
+        assert!(error.starts_with("invalid log filter 'trading_system=loud'"), "{}", error);
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    #[test]
+    fn trace_context_is_empty_without_otlp() {
+        let span = tracing::info_span!("publish");
+        assert!(current_trace_context().is_empty());
+        let carrier = HashMap::from([("traceparent".to_string(), "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string())]);
+        set_remote_parent(&span, &carrier);
+    }
+
+    #[cfg(feature = "otlp")]
+    #[test]
+    fn trace_context_round_trips_through_record_headers() {
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, || {
+            let producer = tracing::info_span!("publish");
+            let sent = producer.in_scope(current_trace_context);
+            let traceparent = &sent["traceparent"];
+            let trace_id = traceparent.split('-').nth(1).unwrap();
+            assert_eq!(traceparent.split('-').count(), 4, "{}", traceparent);
+
+            // The consumer's span joins the producer's trace
+            let consumer = tracing::info_span!("consume");
+            set_remote_parent(&consumer, &sent);
+            let received = consumer.in_scope(current_trace_context);
+            assert_eq!(received["traceparent"].split('-').nth(1), Some(trace_id));
+            assert_ne!(received["traceparent"], *traceparent);
+        });
+    }
+}
@@ -0,0 +1,5 @@
+pub mod rules;
+pub mod rejects;
+
+pub use rules::*;
+pub use rejects::*;
@@ -0,0 +1,73 @@
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::{log_security, KafkaConfig};
+use crate::validation::Rejection;
+
+pub const REJECT_REASON_HEADER: &str = "reject-reason";
+pub const REJECT_DETAIL_HEADER: &str = "reject-detail";
+/// `producer` or `consumer`, whichever rejected the record.
+pub const REJECTED_BY_HEADER: &str = "rejected-by";
+
+/// Forwards rejected records to a dead-letter topic, keeping their payload
+/// and headers so they can be inspected or replayed.
+#[derive(Clone)]
+pub struct RejectRouter {
+    producer: FutureProducer,
+    topic: String,
+    rejected_by: &'static str,
+}
+
+impl RejectRouter {
+    pub fn new(kafka: &KafkaConfig, topic: &str, rejected_by: &'static str) -> Result<Self, Box<dyn std::error::Error>> {
+        let builder = kafka.client_builder()?
+            .set("message.timeout.ms", "5000")
+            .set("acks", "all");
+        log_security(&builder);
+
+        Ok(Self {
+            producer: builder.build().create()?,
+            topic: topic.to_string(),
+            rejected_by,
+        })
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Sends a rejected record in the background. Routing is best effort: a
+    /// failed delivery is logged and the record dropped.
+    pub fn route(&self, key: &str, payload: &[u8], headers: &HashMap<String, String>, rejection: &Rejection) {
+        let mut owned = OwnedHeaders::new();
+        for (key, value) in headers {
+            owned = owned.insert(Header { key, value: Some(value) });
+        }
+        let owned = owned
+            .insert(Header { key: REJECT_REASON_HEADER, value: Some(rejection.reason.as_str()) })
+            .insert(Header { key: REJECT_DETAIL_HEADER, value: Some(&rejection.detail) })
+            .insert(Header { key: REJECTED_BY_HEADER, value: Some(self.rejected_by) });
+        let record = FutureRecord::to(&self.topic).key(key).payload(payload).headers(owned);
+
+        let delivery = match self.producer.send_result(record) {
+            Ok(delivery) => delivery,
+            Err((e, _)) => {
+                tracing::error!(error = %e, topic = %self.topic, "failed to enqueue rejected record");
+                return;
+            }
+        };
+        let topic = self.topic.clone();
+        tokio::spawn(async move {
+            if let Ok(Err((e, _))) = delivery.await {
+                tracing::error!(error = %e, %topic, "failed to route rejected record");
+            }
+        });
+    }
+
+    pub fn flush(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.producer.flush(Duration::from_secs(10))?;
+        Ok(())
+    }
+}
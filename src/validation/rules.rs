@@ -0,0 +1,285 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::config::ValidationConfig;
+use crate::models::{price_to_f64, Decimal, TradeData};
+
+/// Consecutive deviating trades, each within the deviation limit of the one
+/// before, after which the price is taken to have really moved.
+const REANCHOR_AFTER: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RejectReason {
+    EmptySymbol,
+    UnknownSymbol,
+    UnknownExchange,
+    InvalidPrice,
+    PriceOutOfBand,
+    PriceDeviation,
+    InvalidVolume,
+    VolumeTooLarge,
+    NotionalOverflow,
+    FutureTimestamp,
+    StaleTimestamp,
+}
+
+impl RejectReason {
+    /// Label for reject metrics and the reject header.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RejectReason::EmptySymbol => "empty_symbol",
+            RejectReason::UnknownSymbol => "unknown_symbol",
+            RejectReason::UnknownExchange => "unknown_exchange",
+            RejectReason::InvalidPrice => "invalid_price",
+            RejectReason::PriceOutOfBand => "price_out_of_band",
+            RejectReason::PriceDeviation => "price_deviation",
+            RejectReason::InvalidVolume => "invalid_volume",
+            RejectReason::VolumeTooLarge => "volume_too_large",
+            RejectReason::NotionalOverflow => "notional_overflow",
+            RejectReason::FutureTimestamp => "future_timestamp",
+            RejectReason::StaleTimestamp => "stale_timestamp",
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a trade failed validation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub reason: RejectReason,
+    pub detail: String,
+}
+
+impl Rejection {
+    fn new(reason: RejectReason, detail: String) -> Self {
+        Self { reason, detail }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.reason, self.detail)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Reference price of one symbol for the deviation check.
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    price: Decimal,
+    /// Last of the consecutive trades rejected for deviating, and how many
+    /// of them agree with each other.
+    deviating: Option<(Decimal, u32)>,
+}
+
+/// Checks trades against the configured rules. The last accepted price per
+/// symbol is kept for the deviation check; clones share it. After
+/// `REANCHOR_AFTER` consistent deviating trades the reference moves to the
+/// new level, so a real gap does not reject the symbol for good.
+#[derive(Clone)]
+pub struct TradeValidator {
+    config: Arc<ValidationConfig>,
+    anchors: Arc<Mutex<HashMap<String, Anchor>>>,
+}
+
+impl Default for TradeValidator {
+    fn default() -> Self {
+        Self::new(&ValidationConfig::default())
+    }
+}
+
+impl TradeValidator {
+    pub fn new(config: &ValidationConfig) -> Self {
+        Self { config: Arc::new(config.clone()), anchors: Arc::default() }
+    }
+
+    pub fn validate(&self, trade: &TradeData) -> Result<(), Rejection> {
+        self.validate_at(trade, Utc::now())
+    }
+
+    /// Validates against the clock reading `now`, and remembers the price of
+    /// an accepted trade.
+    pub fn validate_at(&self, trade: &TradeData, now: DateTime<Utc>) -> Result<(), Rejection> {
+        let config = &self.config;
+        if !config.enabled {
+            return Ok(());
+        }
+
+        if trade.symbol.trim().is_empty() {
+            return Err(Rejection::new(RejectReason::EmptySymbol, "trade without a symbol".to_string()));
+        }
+        if !config.symbols.is_empty() && !config.symbols.contains(&trade.symbol) {
+            return Err(Rejection::new(RejectReason::UnknownSymbol, format!("unknown symbol '{}'", trade.symbol)));
+        }
+        if !config.exchanges.is_empty() && !config.exchanges.contains(&trade.exchange) {
+            return Err(Rejection::new(RejectReason::UnknownExchange, format!("unknown exchange '{}'", trade.exchange)));
+        }
+
+        if trade.price <= Decimal::ZERO {
+            return Err(Rejection::new(RejectReason::InvalidPrice, format!("price {} is not positive", trade.price)));
+        }
+        let below = config.min_price.is_some_and(|min| trade.price < min);
+        let above = config.max_price.is_some_and(|max| trade.price > max);
+        if below || above {
+            return Err(Rejection::new(RejectReason::PriceOutOfBand, format!("price {} outside the allowed band", trade.price)));
+        }
+
+        if trade.volume == 0 {
+            return Err(Rejection::new(RejectReason::InvalidVolume, "zero volume".to_string()));
+        }
+        if let Some(max) = config.max_volume.filter(|max| trade.volume > *max) {
+            return Err(Rejection::new(RejectReason::VolumeTooLarge, format!("volume {} above {}", trade.volume, max)));
+        }
+        if trade.notional().is_none() {
+            return Err(Rejection::new(
+                RejectReason::NotionalOverflow,
+                format!("price {} times volume {} overflows", trade.price, trade.volume),
+            ));
+        }
+
+        let ahead = (trade.timestamp - now).num_milliseconds();
+        if ahead > config.max_future_skew_ms as i64 {
+            return Err(Rejection::new(RejectReason::FutureTimestamp, format!("timestamp {}ms in the future", ahead)));
+        }
+        if let Some(max_age) = config.max_age_secs {
+            let age = (now - trade.timestamp).num_seconds();
+            if age > max_age as i64 {
+                return Err(Rejection::new(RejectReason::StaleTimestamp, format!("timestamp {}s old", age)));
+            }
+        }
+
+        let mut anchors = self.anchors.lock().unwrap();
+        if let (Some(max_pct), Some(anchor)) = (config.max_deviation_pct, anchors.get_mut(&trade.symbol)) {
+            let deviation = deviation_pct(trade.price, anchor.price);
+            if deviation > max_pct {
+                let agreeing = match anchor.deviating {
+                    Some((previous, count)) if deviation_pct(trade.price, previous) <= max_pct => count + 1,
+                    _ => 1,
+                };
+                let detail = format!("price {} is {:.2}% from the last price {}", trade.price, deviation, anchor.price);
+                if agreeing >= REANCHOR_AFTER {
+                    tracing::warn!(symbol = %trade.symbol, from = %anchor.price, to = %trade.price, "deviation reference moved to a new price level");
+                    *anchor = Anchor { price: trade.price, deviating: None };
+                } else {
+                    anchor.deviating = Some((trade.price, agreeing));
+                }
+                return Err(Rejection::new(RejectReason::PriceDeviation, detail));
+            }
+        }
+        anchors.insert(trade.symbol.clone(), Anchor { price: trade.price, deviating: None });
+        Ok(())
+    }
+}
+
+/// How far `price` is from `reference`, in percent.
+fn deviation_pct(price: Decimal, reference: Decimal) -> f64 {
+    price_to_f64((price - reference).abs() / reference * Decimal::ONE_HUNDRED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TradeSide;
+    use chrono::TimeDelta;
+
+    fn trade(symbol: &str, price: &str) -> TradeData {
+        TradeData::new(symbol.to_string(), price.parse().unwrap(), 100, TradeSide::Buy, "NASDAQ".to_string())
+    }
+
+    fn reason(validator: &TradeValidator, trade: &TradeData) -> Option<RejectReason> {
+        validator.validate_at(trade, trade.timestamp).err().map(|rejection| rejection.reason)
+    }
+
+    #[test]
+    fn each_rule_rejects_with_its_reason() {
+        let validator = TradeValidator::new(&ValidationConfig {
+            min_price: Some("1".parse().unwrap()),
+            max_price: Some("1000".parse().unwrap()),
+            max_volume: Some(1000),
+            symbols: ["AAPL".to_string()].into(),
+            exchanges: ["NASDAQ".to_string()].into(),
+            max_age_secs: Some(60),
+            ..ValidationConfig::default()
+        });
+        assert_eq!(reason(&validator, &trade("AAPL", "150")), None);
+        assert_eq!(reason(&validator, &trade(" ", "150")), Some(RejectReason::EmptySymbol));
+        assert_eq!(reason(&validator, &trade("MSFT", "150")), Some(RejectReason::UnknownSymbol));
+        let elsewhere = TradeData { exchange: "NYSE".to_string(), ..trade("AAPL", "150") };
+        assert_eq!(reason(&validator, &elsewhere), Some(RejectReason::UnknownExchange));
+        assert_eq!(reason(&validator, &trade("AAPL", "0")), Some(RejectReason::InvalidPrice));
+        assert_eq!(reason(&validator, &trade("AAPL", "0.5")), Some(RejectReason::PriceOutOfBand));
+        assert_eq!(reason(&validator, &trade("AAPL", "1000.01")), Some(RejectReason::PriceOutOfBand));
+        let empty = TradeData { volume: 0, ..trade("AAPL", "150") };
+        assert_eq!(reason(&validator, &empty), Some(RejectReason::InvalidVolume));
+        let large = TradeData { volume: 1001, ..trade("AAPL", "150") };
+        assert_eq!(reason(&validator, &large), Some(RejectReason::VolumeTooLarge));
+
+        let now = Utc::now();
+        let ahead = TradeData { timestamp: now + TimeDelta::seconds(6), ..trade("AAPL", "150") };
+        assert_eq!(validator.validate_at(&ahead, now).unwrap_err().reason, RejectReason::FutureTimestamp);
+        let stale = TradeData { timestamp: now - TimeDelta::seconds(61), ..trade("AAPL", "150") };
+        assert_eq!(validator.validate_at(&stale, now).unwrap_err().reason, RejectReason::StaleTimestamp);
+    }
+
+    #[test]
+    fn trades_whose_notional_overflows_are_rejected() {
+        let validator = TradeValidator::default();
+        let whale = TradeData { volume: u64::MAX, ..trade("AAPL", "100000000000000000000") };
+        assert_eq!(reason(&validator, &whale), Some(RejectReason::NotionalOverflow));
+        let largest = TradeData { volume: u64::MAX, ..trade("AAPL", "1000") };
+        assert_eq!(reason(&validator, &largest), None);
+    }
+
+    #[test]
+    fn disabled_validation_accepts_anything() {
+        let validator = TradeValidator::new(&ValidationConfig { enabled: false, ..ValidationConfig::default() });
+        assert_eq!(reason(&validator, &trade("", "-1")), None);
+    }
+
+    #[test]
+    fn deviation_is_measured_from_the_last_accepted_price() {
+        let validator = TradeValidator::new(&ValidationConfig { max_deviation_pct: Some(10.0), ..ValidationConfig::default() });
+        assert_eq!(reason(&validator, &trade("AAPL", "100")), None);
+        assert_eq!(reason(&validator, &trade("AAPL", "109")), None);
+        // 20% from 109; rejected trades do not move the reference
+        assert_eq!(reason(&validator, &trade("AAPL", "130.8")), Some(RejectReason::PriceDeviation));
+        assert_eq!(reason(&validator, &trade("AAPL", "115")), None);
+        // Symbols are tracked separately
+        assert_eq!(reason(&validator, &trade("MSFT", "400")), None);
+    }
+
+    #[test]
+    fn consistent_deviating_trades_re_anchor_the_reference() {
+        let validator = TradeValidator::new(&ValidationConfig { max_deviation_pct: Some(10.0), ..ValidationConfig::default() });
+        assert_eq!(reason(&validator, &trade("AAPL", "100")), None);
+        for price in ["150", "151", "149", "150", "152"] {
+            assert_eq!(reason(&validator, &trade("AAPL", price)), Some(RejectReason::PriceDeviation));
+        }
+        // The fifth moved the reference to 152
+        assert_eq!(reason(&validator, &trade("AAPL", "150")), None);
+        assert_eq!(reason(&validator, &trade("AAPL", "100")), Some(RejectReason::PriceDeviation));
+    }
+
+    #[test]
+    fn scattered_deviating_trades_do_not_re_anchor() {
+        let validator = TradeValidator::new(&ValidationConfig { max_deviation_pct: Some(10.0), ..ValidationConfig::default() });
+        assert_eq!(reason(&validator, &trade("AAPL", "100")), None);
+        for price in ["150", "50", "150", "50", "150", "50", "150"] {
+            assert_eq!(reason(&validator, &trade("AAPL", price)), Some(RejectReason::PriceDeviation));
+        }
+        // Three more make four in a row; an accepted trade then resets the run
+        for price in ["150", "150", "150", "101", "150"] {
+            let expected = if price == "101" { None } else { Some(RejectReason::PriceDeviation) };
+            assert_eq!(reason(&validator, &trade("AAPL", price)), expected);
+        }
+        assert_eq!(reason(&validator, &trade("AAPL", "100")), None);
+    }
+}
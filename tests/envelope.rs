@@ -0,0 +1,131 @@
+use chrono::{TimeZone, Utc};
+use std::collections::HashMap;
+
+use trading_system::codec::{
+    upgrade, DecodeError, Envelope, EnvelopeStamper, MessageType, RecordDecoder, RecordEncoder, CURRENT_SCHEMA_VERSION,
+    LEGACY_SCHEMA_VERSION, SCHEMA_VERSION_HEADER, SEQUENCE_HEADER,
+};
+use trading_system::models::{Decimal, RsiData, RsiSignal, TradeData, TradeSide};
+
+/// Written by producers before envelopes existed: bare JSON, no headers.
+const LEGACY_TRADE: &str = r#"{"id":"0b7f4c1e-6f5a-4a8e-9d0c-3f1e2a4b5c6d","symbol":"AAPL","price":150.25,"volume":300,"timestamp":"2024-01-15T14:30:00.123456789Z","side":"Sell","exchange":"NASDAQ"}"#;
+const LEGACY_RSI: &str = r#"{"id":"9a8b7c6d-5e4f-4a3b-2c1d-0e9f8a7b6c5d","symbol":"TSLA","rsi_value":72.5,"timestamp":"2024-01-15T14:30:00Z","period":14,"signal":"Overbought"}"#;
+
+fn legacy_headers() -> HashMap<String, String> {
+    // Trace and record headers predate the envelope
+    HashMap::from([("record-id".to_string(), "0b7f4c1e".to_string()), ("symbol".to_string(), "AAPL".to_string())])
+}
+
+fn headers(envelope: &Envelope) -> HashMap<String, String> {
+    envelope.to_headers().into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+}
+
+#[tokio::test]
+async fn legacy_trade_payload_still_deserializes() {
+    let envelope = Envelope::from_headers(&legacy_headers()).unwrap();
+    assert!(envelope.is_none());
+
+    let produced_at = Utc.with_ymd_and_hms(2024, 1, 15, 14, 30, 1).unwrap();
+    let envelope = Envelope::legacy(MessageType::Trade, produced_at);
+    assert!(envelope.is_legacy());
+
+    let trade: TradeData = RecordDecoder::default().decode(LEGACY_TRADE.as_bytes(), &envelope).await.unwrap();
+    assert_eq!(trade.symbol, "AAPL");
+    assert_eq!(trade.price, Decimal::new(15025, 2));
+    assert_eq!(trade.volume, 300);
+    assert_eq!(trade.side, TradeSide::Sell);
+    assert_eq!(trade.exchange, "NASDAQ");
+    assert_eq!(trade.timestamp.timestamp_subsec_nanos(), 123_456_789);
+}
+
+#[tokio::test]
+async fn legacy_rsi_payload_still_deserializes() {
+    let envelope = Envelope::legacy(MessageType::Rsi, Utc::now());
+    let rsi: RsiData = RecordDecoder::default().decode(LEGACY_RSI.as_bytes(), &envelope).await.unwrap();
+    assert_eq!(rsi.symbol, "TSLA");
+    assert_eq!(rsi.rsi_value, 72.5);
+    assert_eq!(rsi.period, 14);
+    assert_eq!(rsi.signal, RsiSignal::Overbought);
+}
+
+#[test]
+fn every_older_version_upgrades_to_current() {
+    let body: serde_json::Value = serde_json::from_str(LEGACY_TRADE).unwrap();
+    for version in LEGACY_SCHEMA_VERSION..=CURRENT_SCHEMA_VERSION {
+        let upgraded = upgrade(MessageType::Trade, version, body.clone()).unwrap();
+        serde_json::from_value::<TradeData>(upgraded).unwrap();
+    }
+    let body: serde_json::Value = serde_json::from_str(LEGACY_RSI).unwrap();
+    for version in LEGACY_SCHEMA_VERSION..=CURRENT_SCHEMA_VERSION {
+        let upgraded = upgrade(MessageType::Rsi, version, body.clone()).unwrap();
+        serde_json::from_value::<RsiData>(upgraded).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn current_payload_round_trips_with_envelope_headers() {
+    let trade = TradeData::new("MSFT".to_string(), Decimal::new(4123, 1), 25, TradeSide::Buy, "NYSE".to_string());
+    let payload = RecordEncoder::default().encode("trade-data", &trade).await.unwrap();
+
+    let stamper = EnvelopeStamper::new("producer-a");
+    let sent = stamper.next(MessageType::Trade);
+    assert_eq!(sent.schema_version, CURRENT_SCHEMA_VERSION);
+    assert_eq!(sent.sequence, 1);
+
+    let received = Envelope::from_headers(&headers(&sent)).unwrap().unwrap();
+    assert_eq!(received, sent);
+
+    let decoded: TradeData = RecordDecoder::default().decode(&payload, &received).await.unwrap();
+    assert_eq!(decoded.id, trade.id);
+    assert_eq!(decoded.timestamp, trade.timestamp);
+    assert_eq!(decoded.price.to_string(), "412.3");
+}
+
+#[test]
+fn numeric_prices_upgrade_to_exact_decimals() {
+    let body = serde_json::json!({"symbol": "AAPL", "price": 0.1, "volume": 1});
+    let upgraded = upgrade(MessageType::Trade, 2, body).unwrap();
+    assert_eq!(upgraded["price"], "0.1");
+
+    let quote = serde_json::json!({"bid_price": 99.99, "ask_price": 100.01});
+    let upgraded = upgrade(MessageType::Quote, 2, quote).unwrap();
+    assert_eq!(upgraded["bid_price"], "99.99");
+    assert_eq!(upgraded["ask_price"], "100.01");
+}
+
+#[test]
+fn sequences_count_per_message_type() {
+    let stamper = EnvelopeStamper::new("producer-a");
+    assert_eq!(stamper.next(MessageType::Trade).sequence, 1);
+    assert_eq!(stamper.next(MessageType::Trade).sequence, 2);
+    assert_eq!(stamper.next(MessageType::Rsi).sequence, 1);
+    assert_eq!(stamper.clone().next(MessageType::Trade).sequence, 3);
+}
+
+#[tokio::test]
+async fn newer_schema_version_is_rejected() {
+    let mut envelope = EnvelopeStamper::new("producer-b").next(MessageType::Trade);
+    envelope.schema_version = CURRENT_SCHEMA_VERSION + 1;
+
+    let error = RecordDecoder::default().decode::<TradeData>(LEGACY_TRADE.as_bytes(), &envelope).await.unwrap_err();
+    assert!(matches!(error, DecodeError::Envelope(_)), "{}", error);
+    assert_eq!(error.reason(), "envelope_error");
+}
+
+#[tokio::test]
+async fn mismatched_message_type_is_rejected() {
+    let envelope = EnvelopeStamper::new("producer-b").next(MessageType::Rsi);
+    let error = RecordDecoder::default().decode::<TradeData>(LEGACY_TRADE.as_bytes(), &envelope).await.unwrap_err();
+    assert!(matches!(error, DecodeError::Envelope(_)), "{}", error);
+}
+
+#[test]
+fn partial_or_invalid_envelope_is_an_error() {
+    let mut partial = headers(&EnvelopeStamper::new("producer-c").next(MessageType::Trade));
+    partial.remove(SEQUENCE_HEADER);
+    assert!(Envelope::from_headers(&partial).is_err());
+
+    let mut invalid = headers(&EnvelopeStamper::new("producer-c").next(MessageType::Trade));
+    invalid.insert(SCHEMA_VERSION_HEADER.to_string(), "two".to_string());
+    assert!(Envelope::from_headers(&invalid).is_err());
+}
@@ -0,0 +1,182 @@
+use chrono::Utc;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use warp::http::{HeaderMap, StatusCode};
+use warp::Filter;
+
+use trading_system::config::{WebhookEndpoint, WebhookEvent, WebhooksConfig};
+use trading_system::consumer::{
+    sign, DataProcessor, MarketEvent, SignalChange, WebhookDispatcher, WebhookPayload, WEBHOOK_ATTEMPT_HEADER,
+    WEBHOOK_ID_HEADER, WEBHOOK_SIGNATURE_HEADER, WEBHOOK_TIMESTAMP_HEADER,
+};
+use trading_system::models::RsiSignal;
+
+const SECRET: &str = "stub-secret";
+
+struct Request {
+    headers: HeaderMap,
+    body: String,
+    at: Instant,
+}
+
+/// Local receiver answering with `responses` in turn, then 200.
+struct Stub {
+    address: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl Stub {
+    fn start(responses: &[u16]) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responses = Arc::new(responses.to_vec());
+        let recorded = requests.clone();
+        let hook = warp::path("hook")
+            .and(warp::post())
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(move |headers: HeaderMap, body: warp::hyper::body::Bytes| {
+                let mut requests = recorded.lock().unwrap();
+                let status = responses.get(requests.len()).copied().unwrap_or(200);
+                requests.push(Request { headers, body: String::from_utf8_lossy(&body).to_string(), at: Instant::now() });
+                StatusCode::from_u16(status).unwrap()
+            });
+        let (address, server) = warp::serve(hook).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        Self { address, requests }
+    }
+
+    fn url(&self) -> String {
+        format!("http://{}/hook", self.address)
+    }
+
+    fn count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    async fn wait_for(&self, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while self.count() < count {
+            assert!(Instant::now() < deadline, "stub got {} of {} requests", self.count(), count);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+fn outbox_path() -> PathBuf {
+    std::env::temp_dir().join(format!("webhook-outbox-{}.json", uuid::Uuid::new_v4()))
+}
+
+fn config(url: String, outbox_path: &Path, initial_backoff_ms: u64) -> WebhooksConfig {
+    WebhooksConfig {
+        endpoints: vec![WebhookEndpoint {
+            name: "stub".to_string(),
+            url,
+            secret: Some(SECRET.to_string()),
+            secret_file: None,
+            events: WebhookEvent::all(),
+        }],
+        outbox_path: outbox_path.display().to_string(),
+        max_attempts: 5,
+        initial_backoff_ms,
+        max_backoff_ms: initial_backoff_ms * 4,
+        timeout_ms: 1000,
+    }
+}
+
+fn signal_change() -> MarketEvent {
+    MarketEvent::Signal(SignalChange {
+        symbol: "AAPL".to_string(),
+        previous: Some(RsiSignal::Neutral),
+        signal: RsiSignal::Overbought,
+        rsi_value: 72.5,
+        timestamp: Utc::now(),
+    })
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> &'a str {
+    request.headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default()
+}
+
+#[tokio::test]
+async fn deliveries_are_signed_and_retried_with_backoff() {
+    let stub = Stub::start(&[503, 500]);
+    let path = outbox_path();
+    let dispatcher = WebhookDispatcher::new(&config(stub.url(), &path, 100)).unwrap();
+    dispatcher.start(&DataProcessor::new());
+
+    dispatcher.enqueue(&signal_change());
+    stub.wait_for(3).await;
+
+    {
+        let requests = stub.requests.lock().unwrap();
+        let id = header(&requests[0], WEBHOOK_ID_HEADER).to_string();
+        for (attempt, request) in requests.iter().enumerate() {
+            let timestamp = header(request, WEBHOOK_TIMESTAMP_HEADER).parse().unwrap();
+            assert_eq!(header(request, WEBHOOK_SIGNATURE_HEADER), sign(SECRET, timestamp, &request.body));
+            assert_eq!(header(request, WEBHOOK_ATTEMPT_HEADER), (attempt + 1).to_string());
+            assert_eq!(header(request, WEBHOOK_ID_HEADER), id);
+        }
+        let payload: WebhookPayload = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(payload.event, WebhookEvent::Signals);
+        assert_eq!(payload.data["signal"], "Overbought");
+        assert!(requests[1].at - requests[0].at >= Duration::from_millis(100));
+        assert!(requests[2].at - requests[1].at >= Duration::from_millis(200));
+    }
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let status = &dispatcher.status()[0];
+    assert_eq!((status.delivered, status.retries, status.failed, status.pending), (1, 2, 0, 0));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let stub = Stub::start(&[404]);
+    let path = outbox_path();
+    let dispatcher = WebhookDispatcher::new(&config(stub.url(), &path, 50)).unwrap();
+    dispatcher.start(&DataProcessor::new());
+
+    dispatcher.enqueue(&signal_change());
+    stub.wait_for(1).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    assert_eq!(stub.count(), 1);
+    let status = &dispatcher.status()[0];
+    assert_eq!((status.delivered, status.failed, status.pending), (0, 1, 0));
+    assert_eq!(status.last_status, Some(404));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn undelivered_notifications_survive_a_restart() {
+    // Nothing listens here any more; the retry is scheduled a second out
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let unreachable = format!("http://{}/hook", closed);
+    let path = outbox_path();
+    let before = WebhookDispatcher::new(&config(unreachable, &path, 1000)).unwrap();
+    before.start(&DataProcessor::new());
+    before.enqueue(&signal_change());
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    before.flush().await;
+    assert_eq!(before.status()[0].pending, 1);
+
+    let stub = Stub::start(&[]);
+    let after = WebhookDispatcher::new(&config(stub.url(), &path, 50)).unwrap();
+    assert_eq!(after.status()[0].pending, 1);
+    after.start(&DataProcessor::new());
+    stub.wait_for(1).await;
+
+    {
+        let requests = stub.requests.lock().unwrap();
+        assert_eq!(header(&requests[0], WEBHOOK_ATTEMPT_HEADER), "2");
+        let payload: WebhookPayload = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(payload.data["symbol"], "AAPL");
+    }
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    assert_eq!(after.status()[0].delivered, 1);
+    let outbox: Vec<serde_json::Value> = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(outbox.is_empty());
+    let _ = std::fs::remove_file(&path);
+}